@@ -1,9 +1,13 @@
+use crate::files::{FileBackend, ReloadResult};
 use crate::metrics::Metrics;
+use arc_swap::ArcSwapOption;
 use axum::{
     Router,
-    extract::State,
-    response::{IntoResponse, Json},
-    routing::get,
+    extract::{Path, Request, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
 };
 use serde_json::json;
 use std::sync::Arc;
@@ -11,17 +15,73 @@ use std::sync::Arc;
 #[derive(Clone)]
 pub struct ApiState {
     pub metrics: Arc<Metrics>,
+    /// Zones that can be reloaded, when served from zone files
+    pub zones: Option<Arc<FileBackend>>,
+    /// Bearer token required by the routes that change state, if set.
+    /// Swapped when the configuration is reloaded.
+    pub token: Arc<ArcSwapOption<String>>,
 }
 
-pub fn create_router(metrics: Arc<Metrics>) -> Router {
-    let state = ApiState { metrics };
+pub fn create_router(
+    metrics: Arc<Metrics>,
+    zones: Option<Arc<FileBackend>>,
+    token: Arc<ArcSwapOption<String>>,
+) -> Router {
+    let state = ApiState {
+        metrics,
+        zones,
+        token,
+    };
+
+    let changes = Router::new()
+        .route("/zones/reload", post(reload_zones))
+        .route("/zones/:name/reload", post(reload_zone))
+        .route(
+            "/zones/:name/versions/:version/rollback",
+            post(rollback_zone),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token));
 
     Router::new()
         .route("/health", get(health_check))
         .route("/metrics", get(get_metrics))
+        .route("/zones", get(get_zones))
+        .route("/zones/:name/versions", get(get_zone_versions))
+        .merge(changes)
         .with_state(state)
 }
 
+/// Rejects requests without the configured bearer token, if one is set
+async fn require_token(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    if let Some(token) = state.token.load().as_deref() {
+        let given = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !given.is_some_and(|given| tokens_match(given, token)) {
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                Json(json!({ "error": "Missing or invalid API token" })),
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Compares tokens without stopping at the first differing byte, so the
+/// response time does not reveal how much of a guess was right
+fn tokens_match(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
 async fn health_check() -> impl IntoResponse {
     Json(json!({
         "status": "healthy",
@@ -66,10 +126,122 @@ async fn get_metrics(State(state): State<ApiState>) -> impl IntoResponse {
             }
         },
         "rate_limited": snapshot.rate_limited,
-        "errors": snapshot.errors
+        "errors": snapshot.errors,
+        "auto_reloads": {
+            "succeeded": snapshot.auto_reloads,
+            "failed": snapshot.auto_reload_failures
+        }
     }))
 }
 
+fn not_reloadable() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "Zones can only be reloaded with the file backend" })),
+    )
+        .into_response()
+}
+
+/// Outcome of the last load of every zone
+async fn get_zones(State(state): State<ApiState>) -> Response {
+    let Some(zones) = state.zones else {
+        return not_reloadable();
+    };
+    // status() waits on the reload lock, which a reload may hold for a while
+    match tokio::task::spawn_blocking(move || zones.status()).await {
+        Ok(statuses) => Json(statuses).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Reloads every zone whose files changed, answering 422 if any failed
+async fn reload_zones(State(state): State<ApiState>) -> Response {
+    let Some(zones) = state.zones else {
+        return not_reloadable();
+    };
+    let statuses = match tokio::task::spawn_blocking(move || zones.reload_all()).await {
+        Ok(statuses) => statuses,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    let code = if statuses.iter().any(|s| s.result == ReloadResult::Failed) {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+    (code, Json(statuses)).into_response()
+}
+
+/// Reloads one zone if its files changed, answering 422 if it failed and
+/// is still serving its previous version
+async fn reload_zone(State(state): State<ApiState>, Path(name): Path<String>) -> Response {
+    let Some(zones) = state.zones else {
+        return not_reloadable();
+    };
+    let zone = name.clone();
+    let status = match tokio::task::spawn_blocking(move || zones.reload_zone(&zone)).await {
+        Ok(status) => status,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    match status {
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Unknown zone {}", name) })),
+        )
+            .into_response(),
+        Some(status) if status.result == ReloadResult::Failed => {
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(status)).into_response()
+        }
+        Some(status) => Json(status).into_response(),
+    }
+}
+
+/// The versions of a zone kept for rollback, oldest first
+async fn get_zone_versions(State(state): State<ApiState>, Path(name): Path<String>) -> Response {
+    let Some(zones) = state.zones else {
+        return not_reloadable();
+    };
+    let zone = name.clone();
+    let versions = match tokio::task::spawn_blocking(move || zones.versions(&zone)).await {
+        Ok(versions) => versions,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    match versions {
+        Some(versions) => Json(versions).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Unknown zone {}", name) })),
+        )
+            .into_response(),
+    }
+}
+
+/// Serves a kept version of a zone again
+async fn rollback_zone(
+    State(state): State<ApiState>,
+    Path((name, version)): Path<(String, u64)>,
+) -> Response {
+    let Some(zones) = state.zones else {
+        return not_reloadable();
+    };
+    let zone = name.clone();
+    let status = match tokio::task::spawn_blocking(move || zones.rollback(&zone, version)).await {
+        Ok(status) => status,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    match status {
+        Some(status) => Json(status).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("No version {} of zone {}", version, name) })),
+        )
+            .into_response(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -82,7 +254,7 @@ mod tests {
     #[tokio::test]
     async fn test_health_check() {
         let metrics = Arc::new(Metrics::new());
-        let app = create_router(metrics);
+        let app = create_router(metrics, None, Default::default());
 
         let response = app
             .oneshot(
@@ -100,7 +272,7 @@ mod tests {
     #[tokio::test]
     async fn test_metrics_endpoint() {
         let metrics = Arc::new(Metrics::new());
-        let app = create_router(metrics);
+        let app = create_router(metrics, None, Default::default());
 
         let response = app
             .oneshot(
@@ -114,4 +286,204 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_zone_reload_endpoints() {
+        use crate::config::Config;
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("example.com.zone"),
+            "$ORIGIN example.com.\n@ 3600 IN SOA ns1 admin 7 7200 3600 1209600 3600\n\
+             @ 3600 IN NS ns1\nns1 3600 IN A 192.0.2.1\n",
+        )
+        .unwrap();
+        let config: Config = serde_yaml::from_str(&format!(
+            "server: {{}}\nzones:\n- name: example.com.\n  file: {}/example.com.zone\n",
+            dir.path().display()
+        ))
+        .unwrap();
+        let zones = Arc::new(FileBackend::open(&config).unwrap());
+        let app = create_router(Arc::new(Metrics::new()), Some(zones), Default::default());
+
+        let reload = |uri: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(reload("/zones/example.com./reload"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status["zone"], "example.com.");
+        assert_eq!(status["serial"], 7);
+
+        let response = app
+            .clone()
+            .oneshot(reload("/zones/example.net./reload"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/zones")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let app = create_router(Arc::new(Metrics::new()), None, Default::default());
+        let response = app.oneshot(reload("/zones/reload")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_token_required_for_changes() {
+        let token = Arc::new(ArcSwapOption::from_pointee("secret".to_string()));
+        let app = create_router(Arc::new(Metrics::new()), None, token.clone());
+
+        let request = |method: &str, uri: &str, authorization: Option<&str>| {
+            let mut request = Request::builder().method(method).uri(uri);
+            if let Some(authorization) = authorization {
+                request = request.header(header::AUTHORIZATION, authorization);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        let status = |request: Request<Body>| {
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        for uri in [
+            "/zones/reload",
+            "/zones/example.com./reload",
+            "/zones/example.com./versions/1/rollback",
+        ] {
+            assert_eq!(
+                status(request("POST", uri, None)).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(request("POST", uri, Some("Bearer secreT"))).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(request("POST", uri, Some("secret"))).await,
+                StatusCode::UNAUTHORIZED
+            );
+            // Past the check, answering that zones cannot be reloaded
+            assert_eq!(
+                status(request("POST", uri, Some("Bearer secret"))).await,
+                StatusCode::NOT_FOUND
+            );
+        }
+        assert_eq!(
+            status(request("GET", "/health", None)).await,
+            StatusCode::OK
+        );
+
+        // A reloaded configuration without a token opens the routes again
+        token.store(None);
+        assert_eq!(
+            status(request("POST", "/zones/reload", None)).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_zone_rollback_endpoints() {
+        use crate::config::Config;
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("example.com.zone");
+        let write_zone = |serial: u32, extra: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "$ORIGIN example.com.\n@ 3600 IN SOA ns1 admin {} 7200 3600 1209600 3600\n\
+                     @ 3600 IN NS ns1\nns1 3600 IN A 192.0.2.1\n{}",
+                    serial, extra
+                ),
+            )
+            .unwrap();
+        };
+        write_zone(1, "");
+        let config: Config = serde_yaml::from_str(&format!(
+            "server: {{}}\nzones:\n- name: example.com.\n  file: {}\n",
+            path.display()
+        ))
+        .unwrap();
+        let zones = Arc::new(FileBackend::open(&config).unwrap());
+        write_zone(2, "www 3600 IN A 192.0.2.2\nftp 3600 IN A 192.0.2.3\n");
+        zones.reload_all();
+        let app = create_router(Arc::new(Metrics::new()), Some(zones), Default::default());
+
+        let request = |method: &str, uri: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let json = |response: Response| async {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(request("GET", "/zones/example.com./versions"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let versions = json(response).await;
+        assert_eq!(versions[1]["version"], 2);
+        assert_eq!(versions[1]["serial"], 2);
+        assert_eq!(versions[1]["records_added"], 2);
+        assert_eq!(versions[1]["records_removed"], 0);
+
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/zones/example.com./versions/1/rollback"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let status = json(response).await;
+        assert_eq!(status["result"], "rolled_back");
+        assert_eq!(status["serial"], 1);
+
+        let response = app
+            .clone()
+            .oneshot(request("GET", "/zones/example.com./versions"))
+            .await
+            .unwrap();
+        let versions = json(response).await;
+        assert_eq!(versions[2]["rollback_of"], 1);
+        assert_eq!(versions[2]["records_removed"], 2);
+
+        for uri in [
+            "/zones/example.com./versions/9/rollback",
+            "/zones/example.net./versions/1/rollback",
+        ] {
+            let response = app.clone().oneshot(request("POST", uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+    }
 }
@@ -0,0 +1,331 @@
+#[cfg(test)]
+use crate::zone::Zone;
+use crate::zone::ZoneStore;
+use arc_swap::ArcSwap;
+use hickory_proto::rr::{Name, RData, Record, RecordType};
+use std::sync::Arc;
+
+/// Maximum number of CNAME/ALIAS hops followed when flattening an ALIAS
+const MAX_ALIAS_CHAIN: usize = 8;
+
+/// Source of the zones the server answers from. Lookups hand out shared
+/// snapshots, so a zone replaced by a reload stays valid for queries that
+/// are already using it.
+pub trait ZoneBackend: Send + Sync {
+    /// The zones currently published. A query takes one snapshot and does
+    /// every lookup against it, so its answer never mixes two reloads.
+    fn snapshot(&self) -> Arc<ZoneStore>;
+
+    /// Find the most specific zone containing `name` in the current snapshot
+    #[cfg(test)]
+    fn find_zone(&self, name: &Name) -> Option<Arc<Zone>> {
+        self.snapshot().find_zone(name).cloned()
+    }
+}
+
+impl ZoneStore {
+    /// Resolve an ALIAS target to address records using only the zones we serve.
+    /// CNAMEs at the target are followed (up to MAX_ALIAS_CHAIN hops).
+    /// Returns None if the target is not in any served zone or has no records of `rtype`.
+    pub fn resolve_alias(&self, target: &Name, rtype: RecordType) -> Option<Vec<Record>> {
+        let mut current = target.clone();
+
+        for _ in 0..MAX_ALIAS_CHAIN {
+            let zone = self.find_zone(&current)?;
+
+            if let Some(records) = zone.lookup(&current, rtype) {
+                return Some(records.clone());
+            }
+
+            // Follow a CNAME (or nested ALIAS) at the target
+            let next = zone
+                .lookup(&current, RecordType::CNAME)
+                .or_else(|| zone.lookup(&current, RecordType::ANAME))
+                .and_then(|records| records.first())
+                .and_then(|record| match record.data() {
+                    Some(RData::CNAME(cname)) => Some(cname.0.clone()),
+                    Some(RData::ANAME(aname)) => Some(aname.0.clone()),
+                    _ => None,
+                })?;
+
+            current = next;
+        }
+
+        tracing::warn!("ALIAS chain too long while resolving {}", target);
+        None
+    }
+}
+
+/// A fixed set of zones. Snapshots are copies that share the zones.
+impl ZoneBackend for ZoneStore {
+    fn snapshot(&self) -> Arc<ZoneStore> {
+        Arc::new(self.clone())
+    }
+
+    #[cfg(test)]
+    fn find_zone(&self, name: &Name) -> Option<Arc<Zone>> {
+        ZoneStore::find_zone(self, name).cloned()
+    }
+}
+
+/// The default backend: zones loaded from files. A reload publishes a whole
+/// new store with one atomic swap, so lookups never wait for a reload and
+/// never see some zones reloaded and others not.
+impl ZoneBackend for ArcSwap<ZoneStore> {
+    fn snapshot(&self) -> Arc<ZoneStore> {
+        self.load_full()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zone::SoaRecord;
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    fn empty_zone(origin: &str, serial: u32) -> Zone {
+        let soa = SoaRecord {
+            mname: Name::from_str("ns1.example.net.").unwrap(),
+            rname: Name::from_str("admin.example.net.").unwrap(),
+            serial,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 86400,
+        };
+        Zone::new(Name::from_str(origin).unwrap(), soa)
+    }
+
+    #[test]
+    fn test_swap_publishes_all_zones_at_once() {
+        let mut store = ZoneStore::new();
+        store.add_zone(empty_zone("example.com.", 1));
+        store.add_zone(empty_zone("example.net.", 1));
+        let backend = ArcSwap::from_pointee(store);
+
+        let com = Name::from_str("www.example.com.").unwrap();
+        let net = Name::from_str("www.example.net.").unwrap();
+        let held = backend.find_zone(&com).unwrap();
+
+        let mut reloaded = ZoneStore::new();
+        reloaded.add_zone(empty_zone("example.com.", 2));
+        reloaded.add_zone(empty_zone("example.org.", 2));
+        backend.store(Arc::new(reloaded));
+
+        // A zone already handed out stays usable after the swap
+        assert_eq!(held.soa.serial, 1);
+        assert_eq!(backend.find_zone(&com).unwrap().soa.serial, 2);
+        assert!(backend.find_zone(&net).is_none());
+    }
+
+    #[test]
+    fn test_snapshot_survives_swap_between_lookups() {
+        let mut store = ZoneStore::new();
+        store.add_zone(empty_zone("example.com.", 1));
+        store.add_zone(empty_zone("example.net.", 1));
+        let backend = ArcSwap::from_pointee(store);
+
+        let com = Name::from_str("www.example.com.").unwrap();
+        let net = Name::from_str("www.example.net.").unwrap();
+        let snapshot = backend.snapshot();
+        assert_eq!(snapshot.find_zone(&com).unwrap().soa.serial, 1);
+
+        let mut reloaded = ZoneStore::new();
+        reloaded.add_zone(empty_zone("example.com.", 2));
+        backend.store(Arc::new(reloaded));
+
+        // Later lookups through the snapshot still see the store it was taken from
+        assert_eq!(snapshot.find_zone(&com).unwrap().soa.serial, 1);
+        assert_eq!(snapshot.find_zone(&net).unwrap().soa.serial, 1);
+
+        let current = backend.snapshot();
+        assert_eq!(current.find_zone(&com).unwrap().soa.serial, 2);
+        assert!(current.find_zone(&net).is_none());
+    }
+
+    #[test]
+    fn test_resolve_alias_follows_cname_across_zones() {
+        let mut cdn = empty_zone("example.net.", 1);
+        cdn.add_record(Record::from_rdata(
+            Name::from_str("cdn.example.net.").unwrap(),
+            60,
+            RData::CNAME(hickory_proto::rr::rdata::CNAME(
+                Name::from_str("edge.example.net.").unwrap(),
+            )),
+        ));
+        cdn.add_record(Record::from_rdata(
+            Name::from_str("edge.example.net.").unwrap(),
+            120,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(198, 51, 100, 7))),
+        ));
+
+        let mut store = ZoneStore::new();
+        store.add_zone(cdn);
+
+        let records = store
+            .resolve_alias(&Name::from_str("cdn.example.net.").unwrap(), RecordType::A)
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].ttl(), 120);
+
+        assert!(
+            store
+                .resolve_alias(
+                    &Name::from_str("cdn.example.net.").unwrap(),
+                    RecordType::AAAA
+                )
+                .is_none()
+        );
+        assert!(
+            store
+                .resolve_alias(&Name::from_str("cdn.example.org.").unwrap(), RecordType::A)
+                .is_none(),
+            "Targets outside served zones cannot be flattened"
+        );
+    }
+
+    /// Zone publication before stores were swapped atomically, kept to
+    /// compare against
+    impl ZoneBackend for std::sync::RwLock<ZoneStore> {
+        fn snapshot(&self) -> Arc<ZoneStore> {
+            Arc::new(self.read().unwrap().clone())
+        }
+
+        fn find_zone(&self, name: &Name) -> Option<Arc<Zone>> {
+            self.read().unwrap().find_zone(name).cloned()
+        }
+    }
+
+    struct ReloadRun {
+        queries: u64,
+        reloads: u64,
+        max_latency: std::time::Duration,
+    }
+
+    /// Answers queries from `readers` threads while another thread reloads
+    /// a copy of `template` back to back through `publish`
+    fn queries_during_reloads(
+        backend: Arc<dyn ZoneBackend>,
+        publish: impl Fn(ZoneStore) + Send + 'static,
+        template: &Arc<Zone>,
+        readers: usize,
+        duration: std::time::Duration,
+    ) -> ReloadRun {
+        use crate::protocol::QueryProcessor;
+        use hickory_proto::op::{Message, Query};
+        use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+        use std::time::Instant;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let reloads = Arc::new(AtomicU64::new(0));
+
+        let writer = {
+            let (stop, reloads, template) = (stop.clone(), reloads.clone(), template.clone());
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let mut store = ZoneStore::new();
+                    store.add_zone(Zone::clone(&template));
+                    publish(store);
+                    reloads.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        };
+
+        let readers: Vec<_> = (0..readers)
+            .map(|reader| {
+                let (stop, backend) = (stop.clone(), backend.clone());
+                std::thread::spawn(move || {
+                    let runtime = tokio::runtime::Builder::new_current_thread()
+                        .build()
+                        .unwrap();
+                    let processor = QueryProcessor::new(backend);
+                    let mut queries = 0u64;
+                    let mut max_latency = std::time::Duration::ZERO;
+                    while !stop.load(Ordering::Relaxed) {
+                        let name =
+                            format!("h{}.example.com.", (queries * 7919 + reader as u64) % 1000);
+                        let mut query = Message::new();
+                        query
+                            .add_query(Query::query(Name::from_str(&name).unwrap(), RecordType::A));
+
+                        let start = Instant::now();
+                        runtime.block_on(processor.process_query(&query)).unwrap();
+                        max_latency = max_latency.max(start.elapsed());
+                        queries += 1;
+                    }
+                    (queries, max_latency)
+                })
+            })
+            .collect();
+
+        std::thread::sleep(duration);
+        stop.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+
+        let mut run = ReloadRun {
+            queries: 0,
+            reloads: reloads.load(Ordering::Relaxed),
+            max_latency: std::time::Duration::ZERO,
+        };
+        for reader in readers {
+            let (queries, max_latency) = reader.join().unwrap();
+            run.queries += queries;
+            run.max_latency = run.max_latency.max(max_latency);
+        }
+        run
+    }
+
+    /// Query throughput and worst-case latency while a 100k record zone is
+    /// reloaded continuously, with atomic swaps and with a lock. Run with
+    /// `cargo test --release bench_queries_during_reloads -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_queries_during_reloads() {
+        let mut zone = empty_zone("example.com.", 1);
+        for i in 0..100_000u32 {
+            zone.add_record(Record::from_rdata(
+                Name::from_str(&format!("h{}.example.com.", i)).unwrap(),
+                300,
+                RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::from(0x0a00_0000 + i))),
+            ));
+        }
+        let template = Arc::new(zone);
+        let duration = std::time::Duration::from_secs(3);
+
+        let swap = Arc::new(ArcSwap::from_pointee(ZoneStore::new()));
+        let published = swap.clone();
+        let swapped = queries_during_reloads(
+            swap,
+            move |store| published.store(Arc::new(store)),
+            &template,
+            4,
+            duration,
+        );
+
+        let lock = Arc::new(std::sync::RwLock::new(ZoneStore::new()));
+        let published = lock.clone();
+        let locked = queries_during_reloads(
+            lock,
+            move |store| *published.write().unwrap() = store,
+            &template,
+            4,
+            duration,
+        );
+
+        println!(
+            "{:<8} {:>12} {:>8} {:>12}",
+            "publish", "queries/s", "reloads", "max latency"
+        );
+        for (label, run) in [("swap", &swapped), ("rwlock", &locked)] {
+            println!(
+                "{:<8} {:>12.0} {:>8} {:>12.1?}",
+                label,
+                run.queries as f64 / duration.as_secs_f64(),
+                run.reloads,
+                run.max_latency
+            );
+        }
+        assert!(swapped.queries > 0 && swapped.reloads > 0);
+    }
+}
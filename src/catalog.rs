@@ -0,0 +1,234 @@
+use crate::config::{ZoneConfig, ZoneFormat};
+use crate::zone::Zone;
+use anyhow::{Context, Result};
+use hickory_proto::rr::{Name, RData, RecordType};
+use std::path::{Component, Path};
+use std::str::FromStr;
+
+/// Catalog zone schema version supported (RFC 9432 section 4.2.1)
+const VERSION: &str = "2";
+
+/// The zones listed in a catalog zone (RFC 9432), as zone configurations.
+/// Every member is a single PTR record at `<id>.zones.<catalog>` naming the
+/// zone to serve. Custom properties set its file and format as TXT records
+/// at `file.ext.<id>.zones.<catalog>` and `format.ext.<id>.zones.<catalog>`;
+/// paths are resolved from the directory of the catalog's own file and may
+/// not leave it, and the file defaults to `<zone name>zone` there. Members
+/// with `primaries` are secondary zones, which this server cannot transfer,
+/// so they are skipped, as are members with a broken PTR or properties.
+pub fn members(catalog: &Zone, config: &ZoneConfig) -> Result<Vec<ZoneConfig>> {
+    let origin = &catalog.origin;
+    let version = Name::from_str("version")?.append_domain(origin)?;
+    let versions = txt_values(catalog, &version);
+    if versions.as_slice() != [VERSION] {
+        anyhow::bail!(
+            "Unsupported catalog zone version {:?}, expected \"{}\"",
+            versions.join(" "),
+            VERSION
+        );
+    }
+
+    let zones = Name::from_str("zones")?.append_domain(origin)?;
+    let dir = config.file.parent().unwrap_or(Path::new(""));
+    let mut ids: Vec<&Name> = catalog
+        .records()
+        .keys()
+        .filter(|name| name.num_labels() == zones.num_labels() + 1 && zones.zone_of(name))
+        .collect();
+    ids.sort();
+
+    let mut members: Vec<ZoneConfig> = Vec::new();
+    for id in ids {
+        match member(catalog, id, dir) {
+            Ok(Some(member)) => {
+                let name = Name::from_str(&member.name)?;
+                if members
+                    .iter()
+                    .any(|other| Name::from_str(&other.name).ok().as_ref() == Some(&name))
+                {
+                    tracing::warn!(
+                        "Catalog {}: zone {} listed more than once, ignoring {}",
+                        config.name,
+                        member.name,
+                        id
+                    );
+                    continue;
+                }
+                members.push(member);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Catalog {}: ignoring member {}: {:#}", config.name, id, e),
+        }
+    }
+    Ok(members)
+}
+
+/// The configuration of the member zone at `id`, or `None` if it is not to
+/// be served from a file
+fn member(catalog: &Zone, id: &Name, dir: &Path) -> Result<Option<ZoneConfig>> {
+    let targets: Vec<&Name> = catalog
+        .lookup(id, RecordType::PTR)
+        .into_iter()
+        .flatten()
+        .filter_map(|record| match record.data() {
+            Some(RData::PTR(ptr)) => Some(&ptr.0),
+            _ => None,
+        })
+        .collect();
+    let [name] = targets.as_slice() else {
+        anyhow::bail!("Expected one PTR record, found {}", targets.len());
+    };
+    let name = name.to_string();
+
+    let property = |property: &str| -> Result<Option<String>> {
+        let owner = Name::from_str(&format!("{}.ext", property))?.append_domain(id)?;
+        let values = txt_values(catalog, &owner);
+        match values.as_slice() {
+            [] => Ok(None),
+            [value] => Ok(Some(value.clone())),
+            _ => anyhow::bail!("Property {} has more than one value", property),
+        }
+    };
+
+    // Primaries are listed as records at or below `primaries.ext.<id>`
+    let primaries = Name::from_str("primaries.ext")?.append_domain(id)?;
+    if catalog
+        .records()
+        .keys()
+        .any(|owner| primaries.zone_of(owner))
+    {
+        tracing::warn!(
+            "Catalog member {} has primaries; secondary zones are not supported, skipping",
+            name
+        );
+        return Ok(None);
+    }
+
+    let file = match property("file")? {
+        Some(file) => file,
+        None => format!("{}zone", name),
+    };
+    // Whoever publishes the catalog must not reach files outside its
+    // directory
+    if Path::new(&file)
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        anyhow::bail!("File {:?} is not a relative path below the catalog", file);
+    }
+    let file = dir.join(file);
+    let format = match property("format")? {
+        Some(format) => ZoneFormat::from_str(&format).context("Invalid format property")?,
+        None => ZoneFormat::default(),
+    };
+
+    Ok(Some(ZoneConfig {
+        name,
+        file,
+        format,
+        snapshot: None,
+        serial_policy: None,
+        catalog: false,
+    }))
+}
+
+/// The TXT strings at `owner`, each record's strings joined together
+fn txt_values(zone: &Zone, owner: &Name) -> Vec<String> {
+    zone.lookup(owner, RecordType::TXT)
+        .into_iter()
+        .flatten()
+        .filter_map(|record| match record.data() {
+            Some(RData::TXT(txt)) => Some(
+                txt.txt_data()
+                    .iter()
+                    .map(|part| String::from_utf8_lossy(part))
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zone::parse_zone_file;
+    use std::path::PathBuf;
+    use tempfile::NamedTempFile;
+
+    fn catalog(records: &str) -> Zone {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            format!(
+                "$ORIGIN catalog.invalid.\n$TTL 0\n\
+                 @ IN SOA invalid. invalid. 1 3600 600 86400 0\n\
+                 @ IN NS invalid.\n{}",
+                records
+            ),
+        )
+        .unwrap();
+        parse_zone_file(file.path(), "catalog.invalid.").unwrap()
+    }
+
+    fn config() -> ZoneConfig {
+        ZoneConfig {
+            name: "catalog.invalid.".to_string(),
+            file: PathBuf::from("/srv/zones/catalog.zone"),
+            format: ZoneFormat::Master,
+            snapshot: None,
+            serial_policy: None,
+            catalog: true,
+        }
+    }
+
+    #[test]
+    fn test_members() {
+        let zone = catalog(
+            "version IN TXT \"2\"\n\
+             b.zones IN PTR example.org.\n\
+             file.ext.b.zones IN TXT \"org/example.yaml\"\n\
+             format.ext.b.zones IN TXT \"yaml\"\n\
+             a.zones IN PTR example.com.\n\
+             c.zones IN PTR example.net.\n\
+             primaries.ext.c.zones IN A 192.0.2.1\n\
+             d.zones IN PTR example.com.\n\
+             e.zones IN PTR one.example.\n\
+             e.zones IN PTR two.example.\n\
+             f.zones IN PTR absolute.example.\n\
+             file.ext.f.zones IN TXT \"/etc/passwd\"\n\
+             g.zones IN PTR parent.example.\n\
+             file.ext.g.zones IN TXT \"org/../../secret.zone\"\n",
+        );
+        let members: Vec<(String, PathBuf, ZoneFormat)> = members(&zone, &config())
+            .unwrap()
+            .into_iter()
+            .map(|member| (member.name, member.file, member.format))
+            .collect();
+        assert_eq!(
+            members,
+            vec![
+                (
+                    "example.com.".to_string(),
+                    PathBuf::from("/srv/zones/example.com.zone"),
+                    ZoneFormat::Master
+                ),
+                (
+                    "example.org.".to_string(),
+                    PathBuf::from("/srv/zones/org/example.yaml"),
+                    ZoneFormat::Yaml
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let err = members(&catalog("version IN TXT \"1\"\n"), &config()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported catalog zone version \"1\", expected \"2\""
+        );
+        assert!(members(&catalog(""), &config()).is_err());
+    }
+}
@@ -1,11 +1,17 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
     pub server: ServerConfig,
+    #[serde(default)]
     pub zones: Vec<ZoneConfig>,
+
+    /// Directories whose zone files are all served
+    #[serde(default)]
+    pub zone_dirs: Vec<ZoneDirConfig>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -25,11 +31,89 @@ pub struct ServerConfig {
     #[serde(default)]
     pub api_listen: Option<String>,
 
+    /// Bearer token required by the API's POST routes. Without one they
+    /// are open to anyone who can reach `api_listen`.
+    #[serde(default)]
+    pub api_token: Option<String>,
+
     #[serde(default)]
     pub dnssec: Option<DnssecConfig>,
 
     #[serde(default)]
     pub tcp: Option<TcpConfig>,
+
+    #[serde(default)]
+    pub any: Option<AnyConfig>,
+
+    /// How zone lint violations are treated when zones are loaded
+    #[serde(default)]
+    pub zone_validation: ValidationMode,
+
+    /// Where zones are read from (default: the files listed under `zones`)
+    #[serde(default)]
+    pub backend: BackendConfig,
+
+    /// Reload zone files automatically when they change (default: off)
+    #[serde(default)]
+    pub watch: Option<WatchConfig>,
+
+    /// How SOA serials are managed when zones change, for zones that do
+    /// not set their own (default: keep)
+    #[serde(default)]
+    pub serial_policy: SerialPolicy,
+
+    /// Versions of each zone file kept in memory for rollback, including
+    /// the one being served (default: 3)
+    #[serde(default = "default_zone_history")]
+    pub zone_history: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct WatchConfig {
+    /// Milliseconds to wait for writes to a zone's files to stop before
+    /// reloading it (default: 500)
+    #[serde(default = "default_watch_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BackendConfig {
+    /// Zone files listed under `zones`, held in memory
+    #[default]
+    File,
+    /// Zones stored in an embedded SQLite database
+    Sqlite {
+        path: PathBuf,
+        /// Seconds between checks for database changes (default: 1)
+        #[serde(default = "default_sqlite_poll_interval")]
+        poll_interval: u64,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationMode {
+    /// Log violations and load the zone anyway
+    #[default]
+    Warn,
+    /// Refuse to load a zone with any violation
+    Fatal,
+}
+
+/// What happens to the SOA serial when a reloaded zone's contents change
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SerialPolicy {
+    /// Serve the zone's own serial, checking that it was increased
+    #[default]
+    Keep,
+    /// One more than the serial being served
+    Increment,
+    /// Seconds since the Unix epoch
+    Unixtime,
+    /// Today's date as YYYYMMDDnn
+    Dateserial,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -43,6 +127,128 @@ pub struct TcpConfig {
     pub max_queries_per_connection: usize,
 }
 
+/// Handling of QTYPE=ANY queries (RFC 8482)
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AnyConfig {
+    /// Minimal response sent for ANY over UDP (default: hinfo)
+    #[serde(default)]
+    pub udp_response: AnyResponse,
+
+    /// Answer ANY over TCP with every RRset at the name (default: true)
+    #[serde(default = "default_any_tcp_full")]
+    pub tcp_full: bool,
+
+    /// Client addresses or CIDR networks allowed full ANY over TCP, parsed
+    /// into address and prefix length when the configuration is read.
+    /// Empty means every TCP client; other clients get the UDP response.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_networks",
+        serialize_with = "serialize_networks"
+    )]
+    pub tcp_allow: Vec<(IpAddr, u8)>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AnyResponse {
+    /// A single synthesized HINFO record ("RFC8482", "")
+    #[default]
+    Hinfo,
+    /// One representative RRset that exists at the name
+    Rrset,
+}
+
+impl Default for AnyConfig {
+    fn default() -> Self {
+        AnyConfig {
+            udp_response: AnyResponse::default(),
+            tcp_full: default_any_tcp_full(),
+            tcp_allow: Vec::new(),
+        }
+    }
+}
+
+impl AnyConfig {
+    /// Check whether `client` may receive a full ANY response over TCP
+    pub fn allows_full_tcp(&self, client: Option<IpAddr>) -> bool {
+        if !self.tcp_full {
+            return false;
+        }
+        if self.tcp_allow.is_empty() {
+            return true;
+        }
+        client.is_some_and(|ip| {
+            self.tcp_allow
+                .iter()
+                .any(|&(net, prefix)| cidr_contains(net, prefix, ip))
+        })
+    }
+}
+
+fn deserialize_networks<'de, D>(deserializer: D) -> std::result::Result<Vec<(IpAddr, u8)>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|network| parse_cidr(network).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+fn serialize_networks<S>(
+    networks: &[(IpAddr, u8)],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_seq(
+        networks
+            .iter()
+            .map(|(addr, prefix)| format!("{}/{}", addr, prefix)),
+    )
+}
+
+/// Parse a network given as an address or CIDR into its address and
+/// prefix length
+fn parse_cidr(network: &str) -> Result<(IpAddr, u8)> {
+    let (addr, prefix) = match network.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (network, None),
+    };
+    let addr: IpAddr = addr
+        .parse()
+        .context(format!("Invalid address in network {}", network))?;
+    let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix: u8 = match prefix {
+        Some(p) => p
+            .parse()
+            .context(format!("Invalid prefix length in network {}", network))?,
+        None => max_prefix,
+    };
+    if prefix > max_prefix {
+        anyhow::bail!("Prefix length out of range in network {}", network);
+    }
+    Ok((addr, prefix))
+}
+
+/// Check whether `ip` falls within the network `net`/`prefix`
+fn cidr_contains(net: IpAddr, prefix: u8, ip: IpAddr) -> bool {
+    let prefix = u32::from(prefix);
+    match (net, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DnssecConfig {
     #[serde(default)]
@@ -55,10 +261,80 @@ pub struct DnssecConfig {
     pub auto_include_dnssec: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct ZoneConfig {
     pub name: String,
     pub file: PathBuf,
+
+    /// Format of `file` (default: master)
+    #[serde(default)]
+    pub format: ZoneFormat,
+
+    /// Compiled snapshot of `file`, loaded instead of parsing `file` while
+    /// it is up to date
+    #[serde(default)]
+    pub snapshot: Option<PathBuf>,
+
+    /// Overrides the server's `serial_policy` for this zone
+    #[serde(default)]
+    pub serial_policy: Option<SerialPolicy>,
+
+    /// Zone is a catalog zone (RFC 9432) listing further zones to serve
+    #[serde(default)]
+    pub catalog: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ZoneDirConfig {
+    pub path: PathBuf,
+
+    /// Names of the files to serve, with `*` and `?` wildcards as in the
+    /// shell (default: `*.zone`)
+    #[serde(default = "default_zone_dir_pattern")]
+    pub pattern: String,
+
+    /// Format of the files (default: master)
+    #[serde(default)]
+    pub format: ZoneFormat,
+
+    /// Where each zone's name comes from (default: filename)
+    #[serde(default)]
+    pub origin: OriginSource,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OriginSource {
+    /// The file name, without the fixed text around the pattern's wildcards
+    #[default]
+    Filename,
+    /// The owner of the SOA record in the file
+    Soa,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ZoneFormat {
+    /// RFC 1035 master file
+    #[default]
+    Master,
+    /// Structured zone document in YAML
+    Yaml,
+    /// Structured zone document in JSON
+    Json,
+}
+
+impl std::str::FromStr for ZoneFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "master" => Ok(ZoneFormat::Master),
+            "yaml" => Ok(ZoneFormat::Yaml),
+            "json" => Ok(ZoneFormat::Json),
+            other => anyhow::bail!("Unknown zone format {}", other),
+        }
+    }
 }
 
 fn default_listen() -> String {
@@ -85,6 +361,26 @@ fn default_tcp_max_queries() -> usize {
     100
 }
 
+fn default_any_tcp_full() -> bool {
+    true
+}
+
+fn default_sqlite_poll_interval() -> u64 {
+    1
+}
+
+fn default_watch_debounce_ms() -> u64 {
+    500
+}
+
+fn default_zone_dir_pattern() -> String {
+    "*.zone".to_string()
+}
+
+fn default_zone_history() -> usize {
+    3
+}
+
 impl Config {
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let content =
@@ -97,8 +393,22 @@ impl Config {
     }
 
     pub fn validate(&self) -> Result<()> {
-        if self.zones.is_empty() {
-            anyhow::bail!("At least one zone must be configured");
+        match &self.server.backend {
+            BackendConfig::File if self.zones.is_empty() && self.zone_dirs.is_empty() => {
+                anyhow::bail!("At least one zone or zone directory must be configured");
+            }
+            BackendConfig::Sqlite { poll_interval, .. } => {
+                if !self.zones.is_empty() || !self.zone_dirs.is_empty() {
+                    anyhow::bail!("Zones are read from the database with the sqlite backend");
+                }
+                if *poll_interval == 0 {
+                    anyhow::bail!("Backend poll_interval must be at least 1 second");
+                }
+                if self.server.watch.is_some() {
+                    anyhow::bail!("Watching zone files requires the file backend");
+                }
+            }
+            BackendConfig::File => {}
         }
 
         for zone in &self.zones {
@@ -111,6 +421,34 @@ impl Config {
             }
         }
 
+        for dir in &self.zone_dirs {
+            if !dir.path.is_dir() {
+                anyhow::bail!("Zone directory does not exist: {}", dir.path.display());
+            }
+            if dir.pattern.is_empty() || dir.pattern.contains('/') {
+                anyhow::bail!("Invalid zone file pattern {:?}", dir.pattern);
+            }
+            if dir.origin == OriginSource::Soa && dir.format != ZoneFormat::Master {
+                anyhow::bail!(
+                    "Zone directory {}: origin soa requires master files",
+                    dir.path.display()
+                );
+            }
+        }
+
+        if self.server.zone_history == 0 {
+            anyhow::bail!("zone_history must be at least 1");
+        }
+
+        self.server
+            .log_level
+            .parse::<tracing::level_filters::LevelFilter>()
+            .map_err(|_| anyhow::anyhow!("Invalid log_level {}", self.server.log_level))?;
+
+        if self.server.api_token.as_deref().is_some_and(str::is_empty) {
+            anyhow::bail!("api_token must not be empty");
+        }
+
         Ok(())
     }
 }
@@ -148,13 +486,13 @@ server:
 
     #[test]
     fn test_missing_required_fields() {
-        // Missing zones
+        // Missing zones, which may come from zone directories instead
         let yaml = r#"
 server:
   listen: "127.0.0.1:5353"
 "#;
-        let result: Result<Config, _> = serde_yaml::from_str(yaml);
-        assert!(result.is_err(), "Should fail without zones");
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_err(), "Should fail without zones");
 
         // Missing server
         let yaml = r#"
@@ -227,6 +565,77 @@ zones: []
         );
     }
 
+    #[test]
+    fn test_sqlite_backend() {
+        let yaml = r#"
+server:
+  backend:
+    type: sqlite
+    path: /var/lib/lrmdns/zones.db
+zones: []
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            config.server.backend,
+            BackendConfig::Sqlite {
+                path: PathBuf::from("/var/lib/lrmdns/zones.db"),
+                poll_interval: 1,
+            }
+        );
+        assert!(config.validate().is_ok());
+
+        let yaml = r#"
+server:
+  backend:
+    type: sqlite
+    path: zones.db
+zones:
+  - name: example.com
+    file: example.com.zone
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_zone_dirs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let yaml = format!(
+            "server: {{}}\nzone_dirs:\n  - path: {}\n",
+            dir.path().display()
+        );
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(
+            config.zone_dirs,
+            vec![ZoneDirConfig {
+                path: dir.path().to_path_buf(),
+                pattern: "*.zone".to_string(),
+                format: ZoneFormat::Master,
+                origin: OriginSource::Filename,
+            }]
+        );
+        assert!(config.validate().is_ok());
+
+        let invalid = |dir: &str| {
+            let yaml = format!("server: {{}}\nzone_dirs:\n  - {}\n", dir);
+            let config: Config = serde_yaml::from_str(&yaml).unwrap();
+            config.validate().unwrap_err().to_string()
+        };
+        let path = dir.path().display();
+        assert!(
+            invalid(&format!("path: {}/missing", path))
+                .starts_with("Zone directory does not exist")
+        );
+        assert_eq!(
+            invalid(&format!("{{ path: {}, pattern: 'a/*' }}", path)),
+            "Invalid zone file pattern \"a/*\""
+        );
+        assert!(
+            invalid(&format!("{{ path: {}, format: yaml, origin: soa }}", path))
+                .ends_with("origin soa requires master files")
+        );
+    }
+
     #[test]
     fn test_all_optional_fields() {
         use std::io::Write;
@@ -244,6 +653,10 @@ server:
   log_level: debug
   rate_limit: 500
   api_listen: "127.0.0.1:8080"
+  zone_validation: fatal
+  watch:
+    debounce_ms: 250
+  serial_policy: dateserial
 zones:
   - name: example.com
     file: {}
@@ -256,6 +669,9 @@ zones:
         assert_eq!(config.server.log_level, "debug");
         assert_eq!(config.server.rate_limit, Some(500));
         assert_eq!(config.server.api_listen, Some("127.0.0.1:8080".to_string()));
+        assert_eq!(config.server.zone_validation, ValidationMode::Fatal);
+        assert_eq!(config.server.watch, Some(WatchConfig { debounce_ms: 250 }));
+        assert_eq!(config.server.serial_policy, SerialPolicy::Dateserial);
 
         assert!(config.validate().is_ok());
     }
@@ -296,8 +712,11 @@ zones:
     file: {}
   - name: example.org
     file: {}
+    format: yaml
   - name: example.net
     file: {}
+    format: json
+    snapshot: example.net.snap
 "#,
             temp_file1.path().display(),
             temp_file2.path().display(),
@@ -306,6 +725,14 @@ zones:
 
         let config: Config = serde_yaml::from_str(&yaml).unwrap();
         assert_eq!(config.zones.len(), 3);
+        assert_eq!(config.zones[0].format, ZoneFormat::Master);
+        assert_eq!(config.zones[1].format, ZoneFormat::Yaml);
+        assert_eq!(config.zones[2].format, ZoneFormat::Json);
+        assert_eq!(config.zones[0].snapshot, None);
+        assert_eq!(
+            config.zones[2].snapshot,
+            Some(PathBuf::from("example.net.snap"))
+        );
         assert!(config.validate().is_ok());
     }
 
@@ -478,6 +905,23 @@ zones:
         assert!(config.validate().is_ok());
     }
 
+    #[test]
+    fn test_validate_log_level() {
+        use tempfile::NamedTempFile;
+
+        let zone_file = NamedTempFile::new().unwrap();
+        let yaml = format!(
+            "server:\n  log_level: verbose\nzones:\n  - name: example.com\n    file: {}\n",
+            zone_file.path().display()
+        );
+
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "Invalid log_level verbose"
+        );
+    }
+
     #[test]
     fn test_partial_zone_name() {
         use std::io::Write;
@@ -591,4 +1035,68 @@ zones:
         assert_eq!(tcp_config.idle_timeout, 45);
         assert_eq!(tcp_config.max_queries_per_connection, 100); // default
     }
+
+    #[test]
+    fn test_any_config() {
+        let yaml = r#"
+server:
+  listen: "127.0.0.1:5353"
+  any:
+    udp_response: rrset
+    tcp_allow: ["10.0.0.0/8", "2001:db8::1"]
+zones:
+  - name: example.com
+    file: /tmp/example.com.zone
+"#;
+
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let any = config.server.any.unwrap();
+        assert_eq!(any.udp_response, AnyResponse::Rrset);
+        assert!(any.tcp_full);
+        assert!(any.allows_full_tcp(Some("10.1.2.3".parse().unwrap())));
+        assert!(any.allows_full_tcp(Some("2001:db8::1".parse().unwrap())));
+        assert!(!any.allows_full_tcp(Some("192.0.2.1".parse().unwrap())));
+        assert!(!any.allows_full_tcp(None));
+
+        let defaults = AnyConfig::default();
+        assert_eq!(defaults.udp_response, AnyResponse::Hinfo);
+        assert!(defaults.allows_full_tcp(None));
+    }
+
+    #[test]
+    fn test_parse_cidr() {
+        assert_eq!(
+            parse_cidr("10.0.0.0/8").unwrap(),
+            (IpAddr::from([10, 0, 0, 0]), 8)
+        );
+        assert_eq!(
+            parse_cidr("2001:db8::1").unwrap(),
+            ("2001:db8::1".parse().unwrap(), 128)
+        );
+        assert!(parse_cidr("10.0.0.0/33").is_err());
+        assert!(parse_cidr("10.0.0.0/x").is_err());
+        assert!(parse_cidr("example.com").is_err());
+    }
+
+    #[test]
+    fn test_any_config_invalid_network() {
+        use tempfile::NamedTempFile;
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let yaml = format!(
+            r#"
+server:
+  any:
+    tcp_allow: ["10.0.0.0/33"]
+zones:
+  - name: example.com
+    file: {}
+"#,
+            temp_file.path().display()
+        );
+
+        // Networks are parsed along with the rest of the configuration
+        let err = serde_yaml::from_str::<Config>(&yaml).unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+    }
 }
@@ -0,0 +1,1430 @@
+use crate::backend::ZoneBackend;
+use crate::catalog;
+use crate::config::{
+    Config, OriginSource, SerialPolicy, ValidationMode, ZoneConfig, ZoneDirConfig,
+};
+use crate::lint;
+use crate::serial;
+use crate::snapshot::{self, SourceStamp};
+use crate::zone::{self, Zone, ZoneStore};
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use hickory_proto::rr::{Name, RecordType};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Zones loaded from the files listed in the configuration. Zones are
+/// reloaded one at a time or all together; either way a zone that fails to
+/// load keeps serving its previous version, and a zone whose files have not
+/// changed is not parsed again. Every reload publishes a whole new store
+/// with one atomic swap, so lookups never wait for a reload. Zones listed
+/// in catalog zones are served like configured zones, and are added and
+/// removed as their catalogs are reloaded, and so are zones found in the
+/// configured zone directories as files appear and disappear.
+pub struct FileBackend {
+    store: ArcSwap<ZoneStore>,
+    /// Configured zones and their reload state. Also keeps reloads from
+    /// running concurrently.
+    state: Mutex<State>,
+}
+
+struct State {
+    zones: Vec<ZoneConfig>,
+    validation: ValidationMode,
+    serial_policy: SerialPolicy,
+    /// Versions of each zone kept, including the one being served
+    history: usize,
+    zone_dirs: Vec<ZoneDirConfig>,
+    /// Zones found in each zone directory, keyed by its configured path
+    discovered: HashMap<PathBuf, Vec<ZoneConfig>>,
+    /// Origins read from SOA records, with the stamp of the file read
+    origins: HashMap<PathBuf, (SourceStamp, String)>,
+    /// Zones listed in each catalog zone, keyed by the catalog's name
+    members: HashMap<String, Vec<ZoneConfig>>,
+    /// Per-zone reload state, keyed by configured zone name
+    loaded: HashMap<String, ZoneState>,
+}
+
+#[derive(Default)]
+struct ZoneState {
+    /// Origin of the version being served
+    origin: Option<Name>,
+    /// Files the served version of the zone was read from
+    files: Vec<PathBuf>,
+    /// Stamps of `files`, empty if they may not describe what was read
+    sources: Vec<SourceStamp>,
+    /// SHA-256 of the contents of `sources`
+    digest: Vec<u8>,
+    status: Option<ZoneStatus>,
+    /// Versions served so far, oldest first; the last one is being served
+    history: VecDeque<ZoneVersion>,
+    /// Number of the last version served
+    last_version: u64,
+}
+
+/// A version of a zone kept for rollback
+struct ZoneVersion {
+    info: VersionInfo,
+    zone: Arc<Zone>,
+}
+
+/// A version of a zone, as listed by the API
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VersionInfo {
+    /// Counts up from 1 for every version served since startup
+    pub version: u64,
+    pub serial: u32,
+    /// Unix time the version started being served
+    pub served_at: u64,
+    /// Records added and removed relative to the version before, not
+    /// counting the SOA
+    pub records_added: usize,
+    pub records_removed: usize,
+    /// The version this one restored, if it came from a rollback
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rollback_of: Option<u64>,
+}
+
+/// Outcome of the most recent load or reload of a zone
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ZoneStatus {
+    pub zone: String,
+    pub result: ReloadResult,
+    /// SOA serial of the version being served
+    pub serial: Option<u32>,
+    /// Why the last reload failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix time of the last reload attempt
+    pub checked_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReloadResult {
+    /// A new version of the zone was loaded
+    Loaded,
+    /// The zone's files had not changed
+    Unchanged,
+    /// The zone failed to load; the previous version, if any, is served
+    Failed,
+    /// An earlier version was restored
+    #[serde(rename = "rolled_back")]
+    RolledBack,
+}
+
+impl FileBackend {
+    /// Loads every configured zone, failing if any of them fails to load
+    pub fn open(config: &Config) -> Result<Self> {
+        let backend = FileBackend {
+            store: ArcSwap::from_pointee(ZoneStore::new()),
+            state: Mutex::new(State {
+                zones: config.zones.clone(),
+                validation: config.server.zone_validation,
+                serial_policy: config.server.serial_policy,
+                history: config.server.zone_history,
+                zone_dirs: config.zone_dirs.clone(),
+                discovered: HashMap::new(),
+                origins: HashMap::new(),
+                members: HashMap::new(),
+                loaded: HashMap::new(),
+            }),
+        };
+        for status in backend.reload_all() {
+            if let Some(error) = status.error {
+                anyhow::bail!("Failed to load zone {}: {}", status.zone, error);
+            }
+        }
+        Ok(backend)
+    }
+
+    /// Picks up zone files added to and removed from the zone directories
+    /// and reloads every zone whose files have changed
+    pub fn reload_all(&self) -> Vec<ZoneStatus> {
+        let mut state = self.state.lock().unwrap();
+        state.discover();
+        self.reload(&mut state, ZoneStore::clone(&self.store.load()), |_| true)
+    }
+
+    /// Picks up zone files added to and removed from the zone directories,
+    /// loading only the zones that are new or whose settings changed
+    pub fn rediscover(&self) -> Vec<ZoneStatus> {
+        let mut state = self.state.lock().unwrap();
+        let changed = state.discover();
+        self.reload(&mut state, ZoneStore::clone(&self.store.load()), |config| {
+            changed.contains(&config.name)
+        })
+    }
+
+    /// The configured zone directories
+    pub fn zone_dirs(&self) -> Vec<ZoneDirConfig> {
+        self.state.lock().unwrap().zone_dirs.clone()
+    }
+
+    /// Reloads one zone if its files have changed. Returns `None` if no such
+    /// zone is configured.
+    pub fn reload_zone(&self, name: &str) -> Option<ZoneStatus> {
+        self.reload_zones(&[name.to_string()]).pop()
+    }
+
+    /// Reloads the named zones whose files have changed, ignoring names of
+    /// zones that are not being served
+    pub fn reload_zones(&self, names: &[String]) -> Vec<ZoneStatus> {
+        let names: Vec<Name> = names
+            .iter()
+            .filter_map(|name| Name::from_str(name).ok())
+            .collect();
+        let mut state = self.state.lock().unwrap();
+        self.reload(&mut state, ZoneStore::clone(&self.store.load()), |config| {
+            Name::from_str(&config.name).is_ok_and(|name| names.contains(&name))
+        })
+    }
+
+    /// Switches to the zones of a new configuration: zones no longer
+    /// configured stop being served, new zones are loaded, and zones whose
+    /// configuration or validation mode changed are loaded again. The rest
+    /// are reloaded if their files have changed.
+    pub fn reconfigure(&self, config: &Config) -> Vec<ZoneStatus> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let mut store = ZoneStore::clone(&self.store.load());
+
+        let validation_changed = state.validation != config.server.zone_validation;
+        for (name, zone_state) in state.loaded.iter_mut() {
+            let changed = config
+                .zones
+                .iter()
+                .find(|zone| &zone.name == name)
+                .is_some_and(|zone| state.zones.iter().all(|old| old != zone));
+            if changed || validation_changed {
+                zone_state.sources.clear();
+                zone_state.digest.clear();
+            }
+        }
+        state.zones = config.zones.clone();
+        state.validation = config.server.zone_validation;
+        state.serial_policy = config.server.serial_policy;
+        state.history = config.server.zone_history;
+        state.zone_dirs = config.zone_dirs.clone();
+
+        // Members of catalogs no longer configured stop being served, and
+        // configured zones take over from members of the same name
+        state.members.retain(|name, members| {
+            if !config
+                .zones
+                .iter()
+                .any(|zone| zone.catalog && &zone.name == name)
+            {
+                return false;
+            }
+            members.retain(|member| zone_config(&config.zones, &member.name).is_none());
+            true
+        });
+        state.discover();
+        state.prune(&mut store);
+
+        self.reload(state, store, |_| true)
+    }
+
+    /// Every zone with the files it was last loaded from: the configured
+    /// file and any files it `$INCLUDE`s
+    pub fn zone_files(&self) -> Vec<(String, Vec<PathBuf>)> {
+        let state = self.state.lock().unwrap();
+        state
+            .all_zones()
+            .map(|config| {
+                let mut files = vec![config.file.clone()];
+                if let Some(zone_state) = state.loaded.get(&config.name) {
+                    files.extend(zone_state.files.iter().cloned());
+                }
+                (config.name.clone(), files)
+            })
+            .collect()
+    }
+
+    /// The outcome of the last load of every zone
+    pub fn status(&self) -> Vec<ZoneStatus> {
+        let state = self.state.lock().unwrap();
+        state
+            .all_zones()
+            .filter_map(|config| state.loaded.get(&config.name)?.status.clone())
+            .collect()
+    }
+
+    /// The versions of a zone kept for rollback, oldest first. Returns
+    /// `None` if no such zone is served.
+    pub fn versions(&self, name: &str) -> Option<Vec<VersionInfo>> {
+        let state = self.state.lock().unwrap();
+        let config = zone_config(state.all_zones(), name)?;
+        let zone_state = state.loaded.get(&config.name)?;
+        Some(zone_state.history.iter().map(|v| v.info.clone()).collect())
+    }
+
+    /// Serves an earlier version of a zone again, in one atomic swap, until
+    /// its files change. The zone's serial policy applies as if the version
+    /// had been reloaded, but a serial that does not increase only causes a
+    /// warning. Rolling back a catalog zone restores its list of members
+    /// too. Returns `None` if no such zone or version is kept.
+    pub fn rollback(&self, name: &str, version: u64) -> Option<ZoneStatus> {
+        let mut state = self.state.lock().unwrap();
+        let config = zone_config(state.all_zones(), name)?.clone();
+        let policy = config.serial_policy.unwrap_or(state.serial_policy);
+        let history = state.history;
+        let zone_state = state.loaded.get_mut(&config.name)?;
+        let target = zone_state
+            .history
+            .iter()
+            .find(|v| v.info.version == version)?;
+        let served = zone_state.history.back()?;
+
+        let mut zone = Zone::clone(&target.zone);
+        let now = SystemTime::now();
+        if let Err(e) = serial::apply(
+            policy,
+            ValidationMode::Warn,
+            Some(&served.zone),
+            &mut zone,
+            now,
+        ) {
+            tracing::error!("Failed to pick a serial for zone {}: {:#}", config.name, e);
+        }
+        tracing::info!(
+            "Zone {} rolled back to version {}, serial {}",
+            config.name,
+            version,
+            zone.soa.serial
+        );
+
+        let zone = Arc::new(zone);
+        let mut store = ZoneStore::clone(&self.store.load());
+        store.insert(zone.clone());
+
+        // Keep the rolled back version until the files change again, even
+        // if the version they hold was loaded right after they were written
+        if zone_state.sources.is_empty()
+            && let Ok((sources, digest)) = stamp(&zone_state.files, now)
+        {
+            zone_state.sources = sources;
+            zone_state.digest = digest;
+        }
+
+        let status = ZoneStatus {
+            zone: config.name.clone(),
+            result: ReloadResult::RolledBack,
+            serial: Some(zone.soa.serial),
+            error: None,
+            checked_at: unix_time(now),
+        };
+        zone_state.push_version(zone.clone(), Some(version), history);
+        zone_state.status = Some(status.clone());
+
+        if config.catalog {
+            match catalog::members(&zone, &config) {
+                Ok(members) => {
+                    let changed = state.set_members(&config.name, members);
+                    state.prune(&mut store);
+                    let members: Vec<ZoneConfig> = state
+                        .members()
+                        .filter(|member| changed.contains(&member.name))
+                        .cloned()
+                        .collect();
+                    for member in &members {
+                        state.reload_zone(member, &mut store);
+                    }
+                }
+                Err(e) => tracing::error!(
+                    "Catalog {} rolled back, keeping its current members: {:#}",
+                    config.name,
+                    e
+                ),
+            }
+        }
+        self.store.store(Arc::new(store));
+        Some(status)
+    }
+
+    /// Reloads the selected zones into `store` and publishes it. Catalogs
+    /// are reloaded before their members, so that zones they add or change
+    /// are loaded in the same pass and zones they drop stop being served.
+    fn reload(
+        &self,
+        state: &mut State,
+        mut store: ZoneStore,
+        select: impl Fn(&ZoneConfig) -> bool,
+    ) -> Vec<ZoneStatus> {
+        let configs: Vec<ZoneConfig> = state
+            .zones
+            .iter()
+            .chain(state.discovered())
+            .filter(|c| select(c))
+            .cloned()
+            .collect();
+        let mut statuses = Vec::new();
+        let mut changed = Vec::new();
+        for config in &configs {
+            let (status, members) = state.reload_zone(config, &mut store);
+            statuses.push(status);
+            if let Some(members) = members {
+                changed.extend(state.set_members(&config.name, members));
+            }
+        }
+        state.prune(&mut store);
+
+        let members: Vec<ZoneConfig> = state
+            .members()
+            .filter(|member| select(member) || changed.contains(&member.name))
+            .cloned()
+            .collect();
+        for member in &members {
+            statuses.push(state.reload_zone(member, &mut store).0);
+        }
+
+        self.store.store(Arc::new(store));
+        statuses
+    }
+}
+
+impl State {
+    /// Configured zones, then zones found in zone directories, then the
+    /// members of each catalog
+    fn all_zones(&self) -> impl Iterator<Item = &ZoneConfig> {
+        self.zones
+            .iter()
+            .chain(self.discovered())
+            .chain(self.members())
+    }
+
+    /// Zones found in the zone directories, in configuration order
+    fn discovered(&self) -> impl Iterator<Item = &ZoneConfig> {
+        self.zone_dirs
+            .iter()
+            .filter_map(|dir| self.discovered.get(&dir.path))
+            .flatten()
+    }
+
+    /// Members of the configured catalogs, in configuration order
+    fn members(&self) -> impl Iterator<Item = &ZoneConfig> {
+        self.zones
+            .iter()
+            .filter_map(|config| self.members.get(&config.name))
+            .flatten()
+    }
+
+    /// Reloads a zone into `store` if its files have changed. A catalog
+    /// zone that was loaded also returns its members; a catalog that is
+    /// not valid fails to load like a broken zone.
+    fn reload_zone(
+        &mut self,
+        config: &ZoneConfig,
+        store: &mut ZoneStore,
+    ) -> (ZoneStatus, Option<Vec<ZoneConfig>>) {
+        let validation = self.validation;
+        let policy = config.serial_policy.unwrap_or(self.serial_policy);
+        let history = self.history;
+        let zone_state = self.loaded.entry(config.name.clone()).or_default();
+        let served = zone_state.status.as_ref().and_then(|s| s.serial);
+        let mut status = ZoneStatus {
+            zone: config.name.clone(),
+            result: ReloadResult::Unchanged,
+            serial: served,
+            error: None,
+            checked_at: unix_time(SystemTime::now()),
+        };
+        let mut members = None;
+
+        if served.is_some() && unchanged(zone_state) {
+            tracing::debug!("Zone {} unchanged", config.name);
+        } else {
+            let previous = zone_state.history.back().map(|version| &version.zone);
+            match load_zone(config, validation).and_then(|mut loaded| {
+                let now = SystemTime::now();
+                serial::apply(
+                    policy,
+                    validation,
+                    previous.map(AsRef::as_ref),
+                    &mut loaded.zone,
+                    now,
+                )?;
+                let members = match config.catalog {
+                    true => Some(catalog::members(&loaded.zone, config)?),
+                    false => None,
+                };
+                Ok((loaded, members))
+            }) {
+                Ok((
+                    LoadedZone {
+                        zone,
+                        files,
+                        sources,
+                        digest,
+                    },
+                    catalog_members,
+                )) => {
+                    tracing::info!(
+                        "Zone {} loaded: {} records, serial {}",
+                        config.name,
+                        record_count(&zone),
+                        zone.soa.serial
+                    );
+                    status.result = ReloadResult::Loaded;
+                    status.serial = Some(zone.soa.serial);
+                    zone_state.origin = Some(zone.origin.clone());
+                    zone_state.files = files;
+                    zone_state.sources = sources;
+                    zone_state.digest = digest;
+                    let zone = Arc::new(zone);
+                    zone_state.push_version(zone.clone(), None, history);
+                    store.insert(zone);
+                    members = catalog_members;
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to load zone {}, keeping the previous version: {:#}",
+                        config.name,
+                        e
+                    );
+                    status.result = ReloadResult::Failed;
+                    status.error = Some(format!("{:#}", e));
+                }
+            }
+        }
+
+        zone_state.status = Some(status.clone());
+        (status, members)
+    }
+
+    /// Replaces the members of `catalog`, ignoring zones that are already
+    /// configured or listed in another catalog. Returns the names of the
+    /// members that were added or whose properties changed; changed ones
+    /// are loaded again even if their files have not changed.
+    fn set_members(&mut self, catalog: &str, members: Vec<ZoneConfig>) -> Vec<String> {
+        let old = self.members.remove(catalog).unwrap_or_default();
+        let mut kept = Vec::new();
+        for member in members {
+            if zone_config(self.all_zones(), &member.name).is_some() {
+                tracing::warn!(
+                    "Zone {} listed in catalog {} is already served, ignoring it",
+                    member.name,
+                    catalog
+                );
+                continue;
+            }
+            kept.push(member);
+        }
+
+        let changed: Vec<String> = kept
+            .iter()
+            .filter(|member| !old.contains(member))
+            .map(|member| member.name.clone())
+            .collect();
+        for name in &changed {
+            if let Some(zone_state) = self.loaded.get_mut(name) {
+                zone_state.sources.clear();
+                zone_state.digest.clear();
+            }
+        }
+        self.members.insert(catalog.to_string(), kept);
+        changed
+    }
+
+    /// Scans the zone directories, ignoring zones that are already
+    /// configured or listed in a catalog. A directory that cannot be read
+    /// keeps its zones. Returns the names of the zones found for the first
+    /// time or whose settings changed; changed ones are loaded again even
+    /// if their files have not changed. Zones whose files are gone are left
+    /// for `prune`.
+    fn discover(&mut self) -> Vec<String> {
+        let old = std::mem::take(&mut self.discovered);
+        let dirs = self.zone_dirs.clone();
+        let mut found_files = Vec::new();
+        for dir in &dirs {
+            let found = match scan(dir, &mut self.origins) {
+                Ok(found) => found,
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to scan zone directory {}, keeping its zones: {:#}",
+                        dir.path.display(),
+                        e
+                    );
+                    old.get(&dir.path).cloned().unwrap_or_default()
+                }
+            };
+            let mut kept: Vec<ZoneConfig> = Vec::new();
+            for zone in found {
+                found_files.push(zone.file.clone());
+                if zone_config(self.all_zones().chain(&kept), &zone.name).is_some() {
+                    tracing::warn!(
+                        "Zone {} in {} is already served, ignoring it",
+                        zone.name,
+                        zone.file.display()
+                    );
+                    continue;
+                }
+                kept.push(zone);
+            }
+            self.discovered.insert(dir.path.clone(), kept);
+        }
+        self.origins.retain(|path, _| found_files.contains(path));
+
+        let changed: Vec<String> = self
+            .discovered()
+            .filter(|zone| !old.values().flatten().any(|old| old == *zone))
+            .map(|zone| zone.name.clone())
+            .collect();
+        for name in &changed {
+            if let Some(zone_state) = self.loaded.get_mut(name) {
+                zone_state.sources.clear();
+                zone_state.digest.clear();
+            }
+        }
+        changed
+    }
+
+    /// Stops serving zones that are no longer configured, found in a zone
+    /// directory or listed in a catalog
+    fn prune(&mut self, store: &mut ZoneStore) {
+        let names: Vec<String> = self.all_zones().map(|config| config.name.clone()).collect();
+        self.loaded.retain(|name, zone_state| {
+            if names.contains(name) {
+                return true;
+            }
+            tracing::info!("Zone {} removed", name);
+            if let Some(origin) = &zone_state.origin {
+                store.remove_zone(origin);
+            }
+            false
+        });
+    }
+}
+
+impl ZoneState {
+    /// Records a newly served version, dropping the oldest versions beyond
+    /// `keep`
+    fn push_version(&mut self, zone: Arc<Zone>, rollback_of: Option<u64>, keep: usize) {
+        let previous = self.history.back().map(|version| version.zone.as_ref());
+        let (records_added, records_removed) = record_changes(previous, &zone);
+        self.last_version += 1;
+        self.history.push_back(ZoneVersion {
+            info: VersionInfo {
+                version: self.last_version,
+                serial: zone.soa.serial,
+                served_at: unix_time(SystemTime::now()),
+                records_added,
+                records_removed,
+                rollback_of,
+            },
+            zone,
+        });
+        while self.history.len() > keep.max(1) {
+            self.history.pop_front();
+        }
+    }
+}
+
+/// The zone named `name` among `zones`, compared as domain names
+fn zone_config<'a>(
+    zones: impl IntoIterator<Item = &'a ZoneConfig>,
+    name: &str,
+) -> Option<&'a ZoneConfig> {
+    let name = Name::from_str(name).ok()?;
+    zones
+        .into_iter()
+        .find(|config| Name::from_str(&config.name).ok().as_ref() == Some(&name))
+}
+
+/// The zone files in a zone directory, sorted by file name
+fn scan(
+    dir: &ZoneDirConfig,
+    origins: &mut HashMap<PathBuf, (SourceStamp, String)>,
+) -> Result<Vec<ZoneConfig>> {
+    let entries =
+        std::fs::read_dir(&dir.path).context(format!("Failed to read {}", dir.path.display()))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file()
+            && path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| matches_pattern(&dir.pattern, name))
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut zones = Vec::new();
+    for path in paths {
+        let name = match dir.origin {
+            OriginSource::Filename => origin_from_file_name(&dir.pattern, &path),
+            OriginSource::Soa => origin_from_soa(&path, origins),
+        };
+        match name {
+            Ok(name) => zones.push(ZoneConfig {
+                name,
+                file: path,
+                format: dir.format,
+                snapshot: None,
+                serial_policy: None,
+                catalog: false,
+            }),
+            Err(e) => tracing::warn!("Skipping zone file {}: {:#}", path.display(), e),
+        }
+    }
+    Ok(zones)
+}
+
+/// Whether a file name matches a pattern with `*` and `?` wildcards. As in
+/// the shell, a leading dot must be matched explicitly, so hidden files
+/// such as rsync's temporary files are left alone.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Backtrack to the last `*` on a mismatch, letting it match one more
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// The zone name in a file name: the file name without the fixed text
+/// before the pattern's first wildcard and after its last, so
+/// `example.com.zone` holds `example.com.` for `*.zone`
+fn origin_from_file_name(pattern: &str, path: &Path) -> Result<String> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("File name is not valid UTF-8")?;
+    let prefix = &pattern[..pattern.find(['*', '?']).unwrap_or(pattern.len())];
+    let suffix = &pattern[pattern.rfind(['*', '?']).map_or(pattern.len(), |i| i + 1)..];
+    let name = file_name
+        .strip_prefix(prefix)
+        .and_then(|name| name.strip_suffix(suffix))
+        .filter(|name| !name.is_empty())
+        .unwrap_or(file_name);
+    let name = match name.ends_with('.') {
+        true => name.to_string(),
+        false => format!("{}.", name),
+    };
+    Name::from_str(&name).context(format!("Invalid zone name {}", name))?;
+    Ok(name)
+}
+
+/// The owner of the SOA record in a master file, read again only when the
+/// file changes. Names in the file are relative to the root unless it sets
+/// `$ORIGIN`.
+fn origin_from_soa(
+    path: &Path,
+    origins: &mut HashMap<PathBuf, (SourceStamp, String)>,
+) -> Result<String> {
+    if let Some((stamp, name)) = origins.get(path)
+        && stamp.is_current()
+    {
+        return Ok(name.clone());
+    }
+    let stamp = SourceStamp::of(path)?;
+    let check = zone::check_zone_file(path, ".")?;
+    let zone = check.zone.context("No SOA record")?;
+    let name = zone
+        .records()
+        .iter()
+        .find(|(_, rrsets)| rrsets.contains_key(&RecordType::SOA))
+        .map(|(owner, _)| owner.to_string())
+        .context("No SOA record")?;
+    origins.insert(path.to_path_buf(), (stamp, name.clone()));
+    Ok(name)
+}
+
+/// Loads and lints a zone
+fn load_zone(config: &ZoneConfig, validation: ValidationMode) -> Result<LoadedZone> {
+    tracing::info!(
+        "Loading zone: {} from {}",
+        config.name,
+        config.file.display()
+    );
+
+    let started = SystemTime::now();
+    let (mut zone, files) = snapshot::load_zone(config)?;
+    let (sources, digest) = stamp(&files, started)?;
+
+    let violations = lint::lint_zone(&zone);
+    if !violations.is_empty() && validation == ValidationMode::Fatal {
+        let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        anyhow::bail!("Zone failed validation:\n{}", violations.join("\n"));
+    }
+    for violation in &violations {
+        tracing::warn!("Zone {}: {}", config.name, violation);
+    }
+    zone.remove_out_of_zone();
+
+    Ok(LoadedZone {
+        zone,
+        files,
+        sources,
+        digest,
+    })
+}
+
+/// A freshly loaded zone and what `ZoneState` records about its files
+struct LoadedZone {
+    zone: Zone,
+    files: Vec<PathBuf>,
+    sources: Vec<SourceStamp>,
+    digest: Vec<u8>,
+}
+
+impl ZoneBackend for FileBackend {
+    fn snapshot(&self) -> Arc<ZoneStore> {
+        self.store.load_full()
+    }
+}
+
+/// Whether the files a zone was loaded from are unchanged: same size and
+/// modification time, or failing that the same contents. Files that were
+/// only touched get their new stamps recorded.
+fn unchanged(state: &mut ZoneState) -> bool {
+    if state.sources.is_empty() {
+        return false;
+    }
+    if state.sources.iter().all(SourceStamp::is_current) {
+        return true;
+    }
+
+    let paths: Vec<PathBuf> = state.sources.iter().map(|s| s.path.clone()).collect();
+    let Ok(digest) = digest(&paths) else {
+        return false;
+    };
+    if digest != state.digest {
+        return false;
+    }
+    match paths.iter().map(|p| SourceStamp::of(p)).collect() {
+        Ok(stamps) => {
+            state.sources = stamps;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+fn digest(paths: &[PathBuf]) -> Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    for path in paths {
+        let content = std::fs::read(path).context(format!("Failed to read {}", path.display()))?;
+        hasher.update((content.len() as u64).to_be_bytes());
+        hasher.update(&content);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Stamps and digest of `files` as read at `read_at`. Both are empty if a
+/// file was modified around then, so the next reload must not skip the
+/// zone.
+fn stamp(files: &[PathBuf], read_at: SystemTime) -> Result<(Vec<SourceStamp>, Vec<u8>)> {
+    match snapshot::settled_stamps(files, read_at)? {
+        Some(sources) => Ok((sources, digest(files)?)),
+        None => Ok((Vec::new(), Vec::new())),
+    }
+}
+
+/// Records added and removed going from `old` to `new`, not counting the SOA
+fn record_changes(old: Option<&Zone>, new: &Zone) -> (usize, usize) {
+    let missing = |from: Option<&Zone>, to: Option<&Zone>| -> usize {
+        let Some(from) = from else {
+            return 0;
+        };
+        from.records()
+            .iter()
+            .flat_map(|(name, rrsets)| {
+                rrsets
+                    .iter()
+                    .filter(|(rtype, _)| **rtype != RecordType::SOA)
+                    .map(move |(rtype, records)| (name, rtype, records))
+            })
+            .map(|(name, rtype, records)| {
+                let other = to
+                    .and_then(|to| to.records().get(name))
+                    .and_then(|rrsets| rrsets.get(rtype));
+                records
+                    .iter()
+                    .filter(|record| !other.is_some_and(|other| other.contains(record)))
+                    .count()
+            })
+            .sum()
+    };
+    (missing(Some(new), old), missing(old, Some(new)))
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn record_count(zone: &Zone) -> usize {
+    zone.records()
+        .values()
+        .map(|type_map| type_map.values().map(Vec::len).sum::<usize>())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Writes a zone file dated `age` seconds ago, so reloads do not treat
+    /// it as being modified while read
+    fn write_zone(dir: &TempDir, origin: &str, serial: u32, extra: &str, age: u64) {
+        let path = dir.path().join(format!("{}zone", origin));
+        std::fs::write(
+            &path,
+            format!(
+                "$ORIGIN {}\n$TTL 3600\n\
+                 @ IN SOA ns1 admin {} 7200 3600 1209600 3600\n\
+                 @ IN NS ns1\nns1 IN A 192.0.2.1\n{}",
+                origin, serial, extra
+            ),
+        )
+        .unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age))
+            .unwrap();
+    }
+
+    fn open(dir: &TempDir) -> Result<FileBackend> {
+        let config: Config = serde_yaml::from_str(&format!(
+            "server: {{}}\nzones:\n\
+             - name: example.com.\n  file: {0}/example.com.zone\n\
+             - name: example.org.\n  file: {0}/example.org.zone\n",
+            dir.path().display()
+        ))
+        .unwrap();
+        FileBackend::open(&config)
+    }
+
+    fn served_serial(backend: &FileBackend, origin: &str) -> u32 {
+        let zone = backend.find_zone(&Name::from_str(origin).unwrap()).unwrap();
+        zone.soa.serial
+    }
+
+    fn results(statuses: &[ZoneStatus]) -> Vec<(&str, ReloadResult, Option<u32>)> {
+        statuses
+            .iter()
+            .map(|s| (s.zone.as_str(), s.result, s.serial))
+            .collect()
+    }
+
+    #[test]
+    fn test_reload_only_changed_zones() {
+        let dir = TempDir::new().unwrap();
+        write_zone(&dir, "example.com.", 1, "", 100);
+        write_zone(&dir, "example.org.", 1, "", 100);
+        let backend = open(&dir).unwrap();
+        assert_eq!(
+            results(&backend.status()),
+            vec![
+                ("example.com.", ReloadResult::Loaded, Some(1)),
+                ("example.org.", ReloadResult::Loaded, Some(1)),
+            ]
+        );
+
+        assert_eq!(
+            results(&backend.reload_all()),
+            vec![
+                ("example.com.", ReloadResult::Unchanged, Some(1)),
+                ("example.org.", ReloadResult::Unchanged, Some(1)),
+            ]
+        );
+
+        write_zone(&dir, "example.com.", 2, "www IN A 192.0.2.2\n", 50);
+        let status = backend.reload_zone("example.com").unwrap();
+        assert_eq!(status.result, ReloadResult::Loaded);
+        assert_eq!(served_serial(&backend, "www.example.com."), 2);
+
+        // Touched but identical
+        write_zone(&dir, "example.org.", 1, "", 10);
+        assert_eq!(
+            backend.reload_zone("example.org.").unwrap().result,
+            ReloadResult::Unchanged
+        );
+
+        assert!(backend.reload_zone("example.net.").is_none());
+    }
+
+    #[test]
+    fn test_failed_reload_keeps_previous_version() {
+        let dir = TempDir::new().unwrap();
+        write_zone(&dir, "example.com.", 1, "", 100);
+        write_zone(&dir, "example.org.", 1, "", 100);
+        let backend = open(&dir).unwrap();
+
+        write_zone(&dir, "example.com.", 2, "", 50);
+        write_zone(&dir, "example.org.", 2, "www IN A not-an-address\n", 50);
+        let statuses = backend.reload_all();
+        assert_eq!(
+            results(&statuses),
+            vec![
+                ("example.com.", ReloadResult::Loaded, Some(2)),
+                ("example.org.", ReloadResult::Failed, Some(1)),
+            ]
+        );
+        assert!(
+            statuses[1]
+                .error
+                .as_ref()
+                .unwrap()
+                .contains("Invalid A record")
+        );
+        assert_eq!(served_serial(&backend, "example.com."), 2);
+        assert_eq!(served_serial(&backend, "example.org."), 1);
+
+        // Still broken, so tried again rather than skipped
+        assert_eq!(
+            backend.reload_zone("example.org.").unwrap().result,
+            ReloadResult::Failed
+        );
+        assert_eq!(backend.status()[1].result, ReloadResult::Failed);
+    }
+
+    #[test]
+    fn test_zone_modified_while_loading_is_not_skipped() {
+        let dir = TempDir::new().unwrap();
+        write_zone(&dir, "example.com.", 1, "", 0);
+        write_zone(&dir, "example.org.", 1, "", 100);
+        let backend = open(&dir).unwrap();
+
+        // Its stamps may describe a later edit than what was parsed
+        assert_eq!(
+            results(&backend.reload_all()),
+            vec![
+                ("example.com.", ReloadResult::Loaded, Some(1)),
+                ("example.org.", ReloadResult::Unchanged, Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reconfigure() {
+        let dir = TempDir::new().unwrap();
+        write_zone(&dir, "example.com.", 1, "", 100);
+        write_zone(&dir, "example.org.", 1, "", 100);
+        write_zone(&dir, "example.net.", 1, "", 100);
+        let backend = open(&dir).unwrap();
+
+        let config: Config = serde_yaml::from_str(&format!(
+            "server: {{}}\nzones:\n\
+             - name: example.org.\n  file: {0}/example.org.zone\n  snapshot: {0}/org.snapshot\n\
+             - name: example.net.\n  file: {0}/example.net.zone\n",
+            dir.path().display()
+        ))
+        .unwrap();
+        assert_eq!(
+            results(&backend.reconfigure(&config)),
+            vec![
+                ("example.org.", ReloadResult::Loaded, Some(1)),
+                ("example.net.", ReloadResult::Loaded, Some(1)),
+            ]
+        );
+        assert!(
+            backend
+                .find_zone(&Name::from_str("example.com.").unwrap())
+                .is_none()
+        );
+        assert_eq!(served_serial(&backend, "example.net."), 1);
+        assert_eq!(backend.status().len(), 2);
+        assert!(backend.reload_zone("example.com.").is_none());
+
+        // Nothing changed this time
+        assert_eq!(
+            results(&backend.reconfigure(&config)),
+            vec![
+                ("example.org.", ReloadResult::Unchanged, Some(1)),
+                ("example.net.", ReloadResult::Unchanged, Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serial_policy_on_reload() {
+        let dir = TempDir::new().unwrap();
+        write_zone(&dir, "example.com.", 5, "", 100);
+        write_zone(&dir, "example.org.", 5, "", 100);
+        let config: Config = serde_yaml::from_str(&format!(
+            "server:\n  zone_validation: fatal\nzones:\n\
+             - name: example.com.\n  file: {0}/example.com.zone\n  serial_policy: increment\n\
+             - name: example.org.\n  file: {0}/example.org.zone\n",
+            dir.path().display()
+        ))
+        .unwrap();
+        let backend = FileBackend::open(&config).unwrap();
+
+        // Changed without a serial increase
+        write_zone(&dir, "example.com.", 5, "www IN A 192.0.2.2\n", 50);
+        write_zone(&dir, "example.org.", 5, "www IN A 192.0.2.2\n", 50);
+        let statuses = backend.reload_all();
+        assert_eq!(
+            results(&statuses),
+            vec![
+                ("example.com.", ReloadResult::Loaded, Some(6)),
+                ("example.org.", ReloadResult::Failed, Some(5)),
+            ]
+        );
+        assert!(
+            statuses[1]
+                .error
+                .as_ref()
+                .unwrap()
+                .starts_with("Zone changed without a serial increase")
+        );
+        assert_eq!(served_serial(&backend, "www.example.com."), 6);
+
+        // Rewritten with the same records, keeping the generated serial
+        write_zone(&dir, "example.com.", 5, "www IN A 192.0.2.2\n", 10);
+        assert_eq!(backend.reload_zone("example.com.").unwrap().serial, Some(6));
+    }
+
+    #[test]
+    fn test_rollback() {
+        let dir = TempDir::new().unwrap();
+        write_zone(&dir, "example.com.", 1, "", 100);
+        write_zone(&dir, "example.org.", 1, "", 100);
+        let config: Config = serde_yaml::from_str(&format!(
+            "server:\n  serial_policy: increment\nzones:\n\
+             - name: example.com.\n  file: {0}/example.com.zone\n\
+             - name: example.org.\n  file: {0}/example.org.zone\n",
+            dir.path().display()
+        ))
+        .unwrap();
+        let backend = FileBackend::open(&config).unwrap();
+        write_zone(&dir, "example.com.", 2, "www IN A 192.0.2.2\n", 50);
+        backend.reload_all();
+
+        // Served with a newer serial than the broken version
+        let status = backend.rollback("example.com.", 1).unwrap();
+        assert_eq!(status.result, ReloadResult::RolledBack);
+        assert_eq!(status.serial, Some(3));
+        let origin = Name::from_str("example.com.").unwrap();
+        let zone = backend.find_zone(&origin).unwrap();
+        assert!(!zone.contains_name(&Name::from_str("www.example.com.").unwrap()));
+        assert_eq!(zone.soa.serial, 3);
+        let versions: Vec<_> = backend
+            .versions("example.com.")
+            .unwrap()
+            .into_iter()
+            .map(|v| {
+                (
+                    v.version,
+                    v.serial,
+                    v.records_added,
+                    v.records_removed,
+                    v.rollback_of,
+                )
+            })
+            .collect();
+        assert_eq!(
+            versions,
+            vec![
+                (1, 1, 2, 0, None),
+                (2, 2, 1, 0, None),
+                (3, 3, 0, 1, Some(1))
+            ]
+        );
+
+        // Kept until the files change
+        assert_eq!(
+            backend.reload_zone("example.com.").unwrap().result,
+            ReloadResult::Unchanged
+        );
+        write_zone(&dir, "example.com.", 5, "www IN A 192.0.2.5\n", 10);
+        assert_eq!(backend.reload_zone("example.com.").unwrap().serial, Some(5));
+
+        // Only the last three versions are kept
+        assert!(backend.rollback("example.com.", 1).is_none());
+        assert_eq!(backend.rollback("example.com.", 2).unwrap().serial, Some(6));
+        assert!(backend.rollback("example.net.", 1).is_none());
+        assert_eq!(backend.versions("example.org.").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_catalog_members() {
+        let dir = TempDir::new().unwrap();
+        for origin in ["example.com.", "example.org.", "example.net."] {
+            write_zone(&dir, origin, 1, "", 100);
+        }
+        write_zone(
+            &dir,
+            "catalog.example.",
+            1,
+            "version IN TXT \"2\"\n\
+             a.zones IN PTR example.com.\n\
+             b.zones IN PTR example.org.\n",
+            100,
+        );
+        let config: Config = serde_yaml::from_str(&format!(
+            "server: {{}}\nzones:\n\
+             - name: example.com.\n  file: {0}/example.com.zone\n\
+             - name: catalog.example.\n  file: {0}/catalog.example.zone\n  catalog: true\n",
+            dir.path().display()
+        ))
+        .unwrap();
+        let backend = FileBackend::open(&config).unwrap();
+        assert_eq!(
+            results(&backend.status()),
+            vec![
+                ("example.com.", ReloadResult::Loaded, Some(1)),
+                ("catalog.example.", ReloadResult::Loaded, Some(1)),
+                ("example.org.", ReloadResult::Loaded, Some(1)),
+            ]
+        );
+
+        // One member replaced by another
+        write_zone(
+            &dir,
+            "catalog.example.",
+            2,
+            "version IN TXT \"2\"\n\
+             b.zones IN PTR example.net.\n",
+            50,
+        );
+        assert_eq!(
+            results(&backend.reload_zones(&["catalog.example.".to_string()])),
+            vec![
+                ("catalog.example.", ReloadResult::Loaded, Some(2)),
+                ("example.net.", ReloadResult::Loaded, Some(1)),
+            ]
+        );
+        assert!(
+            backend
+                .find_zone(&Name::from_str("example.org.").unwrap())
+                .is_none()
+        );
+        assert!(backend.reload_zone("example.org.").is_none());
+        assert_eq!(
+            backend.reload_zone("example.net.").unwrap().result,
+            ReloadResult::Unchanged
+        );
+        let files = backend.zone_files();
+        assert_eq!(files[2].0, "example.net.");
+
+        // A catalog that is not valid keeps its members
+        write_zone(&dir, "catalog.example.", 3, "version IN TXT \"1\"\n", 10);
+        assert_eq!(
+            backend.reload_zone("catalog.example.").unwrap().result,
+            ReloadResult::Failed
+        );
+        assert_eq!(served_serial(&backend, "example.net."), 1);
+
+        // Rolling back the catalog restores its members
+        backend.rollback("catalog.example.", 1).unwrap();
+        assert_eq!(served_serial(&backend, "example.org."), 1);
+        assert!(
+            backend
+                .find_zone(&Name::from_str("example.net.").unwrap())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_zone_dirs() {
+        let dir = TempDir::new().unwrap();
+        for origin in ["example.com.", "example.org."] {
+            write_zone(&dir, origin, 1, "", 100);
+        }
+        std::fs::write(dir.path().join("notes.txt"), "not a zone").unwrap();
+        std::fs::write(dir.path().join(".example.net.zone"), "partial").unwrap();
+        let soa_dir = TempDir::new().unwrap();
+        std::fs::write(
+            soa_dir.path().join("db.1"),
+            "$ORIGIN example.info.\n$TTL 3600\n\
+             @ IN SOA ns1 admin 7 7200 3600 1209600 3600\n\
+             @ IN NS ns1\nns1 IN A 192.0.2.1\n",
+        )
+        .unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(soa_dir.path().join("db.1"))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(100))
+            .unwrap();
+        let config: Config = serde_yaml::from_str(&format!(
+            "server: {{}}\nzones:\n\
+             - name: example.com.\n  file: {0}/example.com.zone\n\
+             zone_dirs:\n\
+             - path: {0}\n\
+             - {{ path: {1}, pattern: 'db.*', origin: soa }}\n",
+            dir.path().display(),
+            soa_dir.path().display()
+        ))
+        .unwrap();
+        let backend = FileBackend::open(&config).unwrap();
+        assert_eq!(
+            results(&backend.status()),
+            vec![
+                ("example.com.", ReloadResult::Loaded, Some(1)),
+                ("example.org.", ReloadResult::Loaded, Some(1)),
+                ("example.info.", ReloadResult::Loaded, Some(7)),
+            ]
+        );
+        assert!(backend.rediscover().is_empty());
+
+        // One file added and one removed
+        write_zone(&dir, "example.net.", 1, "", 100);
+        std::fs::remove_file(dir.path().join("example.org.zone")).unwrap();
+        assert_eq!(
+            results(&backend.rediscover()),
+            vec![("example.net.", ReloadResult::Loaded, Some(1))]
+        );
+        assert!(
+            backend
+                .find_zone(&Name::from_str("example.org.").unwrap())
+                .is_none()
+        );
+        assert_eq!(
+            results(&backend.reload_all()),
+            vec![
+                ("example.com.", ReloadResult::Unchanged, Some(1)),
+                ("example.net.", ReloadResult::Unchanged, Some(1)),
+                ("example.info.", ReloadResult::Unchanged, Some(7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zone_file_names() {
+        assert!(matches_pattern("*.zone", "example.com.zone"));
+        assert!(!matches_pattern("*.zone", ".zone.zone"));
+        assert!(matches_pattern(".*", ".zone"));
+        assert!(matches_pattern("db.?*", "db.example.com"));
+        assert!(!matches_pattern("db.?*", "db."));
+        assert!(matches_pattern("*", "example.com"));
+        assert!(!matches_pattern("*.zone", "example.com.zone.bak"));
+        assert!(matches_pattern("*.zone*", "a.zone.zone"));
+
+        let origin = |pattern: &str, file: &str| origin_from_file_name(pattern, Path::new(file));
+        assert_eq!(
+            origin("*.zone", "/srv/example.com.zone").unwrap(),
+            "example.com."
+        );
+        assert_eq!(origin("db.*", "db.example.com").unwrap(), "example.com.");
+        assert_eq!(origin("*", "example.com.").unwrap(), "example.com.");
+        assert!(origin("*", "bad..name").is_err());
+    }
+
+    #[test]
+    fn test_fatal_validation_refuses_cname_conflict() {
+        let dir = TempDir::new().unwrap();
+        write_zone(
+            &dir,
+            "example.com.",
+            1,
+            "www IN CNAME ns1\nwww IN A 192.0.2.2\n",
+            100,
+        );
+        let config = |validation: &str| -> Config {
+            serde_yaml::from_str(&format!(
+                "server:\n  zone_validation: {}\nzones:\n\
+                 - name: example.com.\n  file: {}/example.com.zone\n",
+                validation,
+                dir.path().display()
+            ))
+            .unwrap()
+        };
+
+        assert!(FileBackend::open(&config("warn")).is_ok());
+        let err = FileBackend::open(&config("fatal")).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Failed to load zone example.com.: Zone failed validation:\n\
+             www.example.com.: CNAME alongside other data (A)"
+        );
+    }
+
+    #[test]
+    fn test_fatal_validation_refuses_out_of_zone_record() {
+        let dir = TempDir::new().unwrap();
+        write_zone(
+            &dir,
+            "example.com.",
+            1,
+            "www IN A 192.0.2.2
+www.example.net. IN A 192.0.2.3
+",
+            100,
+        );
+        let config = |validation: &str| -> Config {
+            serde_yaml::from_str(&format!(
+                "server:\n  zone_validation: {}\nzones:\n\
+                 - name: example.com.\n  file: {}/example.com.zone\n",
+                validation,
+                dir.path().display()
+            ))
+            .unwrap()
+        };
+
+        // Without fatal validation the record is dropped, as before
+        let backend = FileBackend::open(&config("warn")).unwrap();
+        let zone = backend
+            .find_zone(&Name::from_str("example.com.").unwrap())
+            .unwrap();
+        assert!(
+            !zone
+                .records()
+                .contains_key(&Name::from_str("www.example.net.").unwrap())
+        );
+        assert_eq!(
+            zone.get_all_records().len(),
+            5,
+            "SOA twice, NS and the two in-zone A records"
+        );
+
+        let err = FileBackend::open(&config("fatal")).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Failed to load zone example.com.: Zone failed validation:\n\
+             www.example.net.: Record outside the zone origin"
+        );
+    }
+
+    #[test]
+    fn test_open_fails_on_broken_zone() {
+        let dir = TempDir::new().unwrap();
+        write_zone(&dir, "example.com.", 1, "", 100);
+        write_zone(&dir, "example.org.", 1, "www IN A not-an-address\n", 100);
+        let err = open(&dir).err().unwrap();
+        assert!(
+            err.to_string()
+                .starts_with("Failed to load zone example.org.: 1 error(s) in zone file")
+        );
+    }
+}
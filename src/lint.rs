@@ -0,0 +1,247 @@
+use crate::zone::Zone;
+use hickory_proto::rr::{Name, RData, RecordType};
+
+/// Largest SOA minimum (negative caching TTL) considered sane; RFC 2308
+/// recommends one to three hours and resolvers commonly cap it at a day
+const MAX_SOA_MINIMUM: u32 = 86400;
+
+/// A loaded zone that breaks an RFC constraint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub owner: Name,
+    pub message: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.owner, self.message)
+    }
+}
+
+/// Checks a loaded zone against constraints the parser cannot see from a
+/// single record: records outside the origin, CNAMEs sharing a name with
+/// other data, NS and MX targets that cannot be resolved to addresses, and
+/// SOA timer sanity. Out-of-zone records only reach this from master files;
+/// the structured loaders reject them. Targets outside the zone are not
+/// checked.
+pub fn lint_zone(zone: &Zone) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut report = |owner: &Name, message: String| {
+        violations.push(Violation {
+            owner: owner.clone(),
+            message,
+        })
+    };
+
+    check_soa(zone, &mut report);
+
+    let mut names: Vec<&Name> = zone.records().keys().collect();
+    names.sort();
+    for name in names {
+        let type_map = &zone.records()[name];
+        if !zone.origin.zone_of(name) {
+            report(name, "Record outside the zone origin".to_string());
+            continue;
+        }
+        if type_map.contains_key(&RecordType::CNAME) {
+            let mut others: Vec<RecordType> = type_map
+                .keys()
+                .copied()
+                .filter(|t| *t != RecordType::CNAME && !coexists_with_cname(*t))
+                .collect();
+            if !others.is_empty() {
+                others.sort();
+                let others: Vec<String> = others.iter().map(ToString::to_string).collect();
+                report(
+                    name,
+                    format!("CNAME alongside other data ({})", others.join(", ")),
+                );
+            }
+            if type_map[&RecordType::CNAME].len() > 1 {
+                report(name, "More than one CNAME record".to_string());
+            }
+        }
+
+        for record in type_map.values().flatten() {
+            match record.data() {
+                Some(RData::NS(ns)) if zone.origin.zone_of(&ns.0) && !has_address(zone, &ns.0) => {
+                    report(name, format!("NS target {} has no A or AAAA records", ns.0));
+                }
+                // RFC 2181 section 10.3
+                Some(RData::MX(mx)) if zone.lookup(mx.exchange(), RecordType::CNAME).is_some() => {
+                    report(name, format!("MX target {} is a CNAME", mx.exchange()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    violations
+}
+
+/// Record types allowed at a CNAME's owner besides the CNAME (RFC 1034
+/// section 3.6.2): DNSSEC records (RFC 4035 section 2.5)
+fn coexists_with_cname(rtype: RecordType) -> bool {
+    matches!(
+        rtype,
+        RecordType::RRSIG | RecordType::SIG | RecordType::NSEC | RecordType::NSEC3
+    )
+}
+
+fn has_address(zone: &Zone, name: &Name) -> bool {
+    zone.lookup(name, RecordType::A).is_some() || zone.lookup(name, RecordType::AAAA).is_some()
+}
+
+fn check_soa(zone: &Zone, report: &mut impl FnMut(&Name, String)) {
+    let origin = &zone.origin;
+    match zone.lookup(origin, RecordType::SOA).map(Vec::len) {
+        Some(1) => {}
+        Some(n) => report(origin, format!("{} SOA records at the apex", n)),
+        None => report(origin, "No SOA record at the apex".to_string()),
+    }
+    for (name, type_map) in zone.records() {
+        if name != origin && type_map.contains_key(&RecordType::SOA) {
+            report(name, "SOA record below the apex".to_string());
+        }
+    }
+    if zone.lookup(origin, RecordType::NS).is_none() {
+        report(origin, "No NS records at the apex".to_string());
+    }
+
+    // RFC 1912 section 2.2
+    let soa = &zone.soa;
+    if soa.refresh <= 0 || soa.retry <= 0 || soa.expire <= 0 {
+        report(
+            origin,
+            "SOA refresh, retry and expire must be positive".to_string(),
+        );
+    } else {
+        if soa.retry >= soa.refresh {
+            report(
+                origin,
+                format!(
+                    "SOA retry {} is not less than refresh {}",
+                    soa.retry, soa.refresh
+                ),
+            );
+        }
+        if i64::from(soa.expire) <= i64::from(soa.refresh) + i64::from(soa.retry) {
+            report(
+                origin,
+                format!(
+                    "SOA expire {} is not greater than refresh plus retry",
+                    soa.expire
+                ),
+            );
+        }
+    }
+    if soa.minimum > MAX_SOA_MINIMUM {
+        report(
+            origin,
+            format!(
+                "SOA minimum {} exceeds {} seconds",
+                soa.minimum, MAX_SOA_MINIMUM
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(body: &str) -> Zone {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        write!(temp_file, "{}", body).unwrap();
+        temp_file.flush().unwrap();
+        crate::zone::parse_zone_file(temp_file.path(), "example.com.").unwrap()
+    }
+
+    fn messages(zone: &Zone) -> Vec<String> {
+        lint_zone(zone).iter().map(ToString::to_string).collect()
+    }
+
+    const APEX: &str = "@ IN SOA ns1 admin 1 7200 3600 1209600 3600\n\
+                        @ IN NS ns1\n\
+                        ns1 IN A 192.0.2.1\n";
+
+    #[test]
+    fn test_clean_zone_has_no_violations() {
+        let zone = parse(&format!(
+            "{}@ IN NS ns.example.net.\n@ IN MX 10 mail\nmail IN A 192.0.2.2\n\
+             www IN CNAME mail\nwww IN RRSIG CNAME 8 3 3600 1735689600 1704067200 1 example.com. AAAA\n",
+            APEX
+        ));
+        assert_eq!(messages(&zone), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_cname_and_other_data() {
+        let zone = parse(&format!(
+            "{}www IN CNAME mail\nwww IN TXT \"x\"\nwww IN A 192.0.2.3\n\
+             www IN RRSIG CNAME 8 3 3600 1767139200 1764547200 12345 example.com. AwEAAaetidLzsKWU\n\
+             alias IN CNAME a\nalias IN CNAME b\n",
+            APEX
+        ));
+        assert_eq!(
+            messages(&zone),
+            vec![
+                "alias.example.com.: More than one CNAME record",
+                "www.example.com.: CNAME alongside other data (A, TXT)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ns_target_without_address() {
+        let zone = parse(&format!("{}@ IN NS ns2\nsub IN NS ns.sub\n", APEX));
+        assert_eq!(
+            messages(&zone),
+            vec![
+                "example.com.: NS target ns2.example.com. has no A or AAAA records",
+                "sub.example.com.: NS target ns.sub.example.com. has no A or AAAA records",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_outside_origin() {
+        let zone = parse(&format!(
+            "{}www.example.net. IN A 192.0.2.2
+",
+            APEX
+        ));
+        assert_eq!(
+            messages(&zone),
+            vec!["www.example.net.: Record outside the zone origin"]
+        );
+    }
+
+    #[test]
+    fn test_mx_target_is_cname() {
+        let zone = parse(&format!("{}@ IN MX 10 mail\nmail IN CNAME ns1\n", APEX));
+        assert_eq!(
+            messages(&zone),
+            vec!["example.com.: MX target mail.example.com. is a CNAME"]
+        );
+    }
+
+    #[test]
+    fn test_soa_sanity() {
+        let zone = parse("@ IN SOA ns1 admin 1 3600 7200 3600 604800\nns1 IN A 192.0.2.1\n");
+        assert_eq!(
+            messages(&zone),
+            vec![
+                "example.com.: No NS records at the apex",
+                "example.com.: SOA retry 7200 is not less than refresh 3600",
+                "example.com.: SOA expire 3600 is not greater than refresh plus retry",
+                "example.com.: SOA minimum 604800 exceeds 86400 seconds",
+            ]
+        );
+    }
+}
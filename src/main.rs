@@ -1,31 +1,51 @@
 mod api;
+mod backend;
+mod catalog;
 mod config;
 mod dnssec;
+mod files;
+mod lint;
 mod metrics;
 mod protocol;
 mod ratelimit;
+mod reload;
+mod serial;
 mod server;
+mod snapshot;
+mod sqlite;
+mod watch;
 mod zone;
 
 #[cfg(test)]
 mod proptest_helpers;
 
 use anyhow::{Context, Result};
-use config::Config;
+use config::{Config, ZoneFormat};
 use metrics::Metrics;
-use protocol::QueryProcessor;
-use ratelimit::RateLimiter;
-use server::DnsServer;
-use std::path::PathBuf;
+use reload::Running;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use zone::ZoneStore;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Parse command line arguments
     let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("format") {
+        return format_zone_file(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("check") {
+        return check_zone_file(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("import") {
+        return import_zone_file(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("compile") {
+        return compile_zone_file(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("reload") {
+        return reload_zone(&args[2..]);
+    }
     let config_path = if args.len() > 1 {
         PathBuf::from(&args[1])
     } else {
@@ -39,12 +59,10 @@ async fn main() -> Result<()> {
     ))?;
 
     // Initialize logging
-    let log_level = config.server.log_level.clone();
+    let (log_filter, log_filter_handle) =
+        tracing_subscriber::reload::Layer::new(reload::log_filter(&config.server.log_level));
     tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| format!("lrmdns={}", log_level).into()),
-        )
+        .with(log_filter)
         .with(tracing_subscriber::fmt::layer())
         .init();
 
@@ -56,106 +74,186 @@ async fn main() -> Result<()> {
         .validate()
         .context("Configuration validation failed")?;
 
-    // Load all zones
-    let zone_store = Arc::new(RwLock::new(load_zones(&config)?));
+    // Create a cancellation token for graceful shutdown
+    let shutdown_token = tokio_util::sync::CancellationToken::new();
 
-    // Create metrics
-    let metrics = Arc::new(Metrics::new());
+    // Load all zones and start serving them
+    let running = Running::start(
+        config_path,
+        config,
+        Arc::new(Metrics::new()),
+        log_filter_handle,
+        shutdown_token.clone(),
+    )
+    .await?;
 
-    // Create rate limiter if configured
-    let rate_limiter = config
-        .server
-        .rate_limit
-        .map(|limit| Arc::new(RateLimiter::new(limit)));
+    // Spawn signal handler tasks
+    let shutdown_token_for_signals = shutdown_token.clone();
+    tokio::spawn(async move {
+        handle_signals(running, shutdown_token_for_signals).await;
+    });
 
-    // Create query processor
-    let processor = QueryProcessor::new(zone_store.clone());
+    shutdown_token.cancelled().await;
+    Ok(())
+}
 
-    // Create and run DNS server
-    let server = DnsServer::new(
-        processor,
-        config.server.listen.clone(),
-        metrics.clone(),
-        rate_limiter.clone(),
-        config.server.tcp.clone(),
-    );
+/// Parses a zone file and prints it in canonical form, as a master file or
+/// a YAML/JSON zone document. The input format follows the file extension.
+fn format_zone_file(args: &[String]) -> Result<()> {
+    let (file, origin, output) = match args {
+        [file, origin] => (file, origin, ZoneFormat::Master),
+        [file, origin, output] => (file, origin, output.parse()?),
+        _ => anyhow::bail!("Usage: lrmdns format <zone-file> <origin> [master|yaml|json]"),
+    };
 
-    tracing::info!("DNS server starting on {}", config.server.listen);
+    let zone = zone::load_zone_file(file, origin, format_of(file))
+        .context(format!("Failed to load zone {}", origin))?;
+    print!("{}", zone::write_zone_as(&zone, output)?);
+    Ok(())
+}
 
-    // Start HTTP API server if configured
-    if let Some(api_listen) = &config.server.api_listen {
-        let api_app = api::create_router(metrics.clone());
-        let api_listen = api_listen.clone();
+/// Loads a zone file and stores it in a SQLite zone database, replacing any
+/// zone with the same origin
+fn import_zone_file(args: &[String]) -> Result<()> {
+    let [database, file, origin] = args else {
+        anyhow::bail!("Usage: lrmdns import <database> <zone-file> <origin>");
+    };
 
-        tokio::spawn(async move {
-            let listener = tokio::net::TcpListener::bind(&api_listen)
-                .await
-                .expect("Failed to bind API server");
+    let zone = zone::load_zone_file(file, origin, format_of(file))
+        .context(format!("Failed to load zone {}", origin))?;
+    sqlite::import_zone(Path::new(database), &zone::zone_to_document(&zone)?).context(format!(
+        "Failed to import zone {} into {}",
+        origin, database
+    ))
+}
 
-            tracing::info!("HTTP API server listening on {}", api_listen);
+/// Parses a zone file and writes a binary snapshot of it, which the server
+/// loads instead of the zone file while neither the file nor anything it
+/// `$INCLUDE`s has changed
+fn compile_zone_file(args: &[String]) -> Result<()> {
+    let [file, origin, output] = args else {
+        anyhow::bail!("Usage: lrmdns compile <zone-file> <origin> <snapshot>");
+    };
 
-            axum::serve(listener, api_app)
-                .await
-                .expect("API server failed");
-        });
+    // A snapshot must not record stamps of files edited while they were
+    // read, so parse again until they have settled
+    for _ in 0..10 {
+        let read_at = std::time::SystemTime::now();
+        let (zone, sources) = zone::load_zone_file_with_sources(file, origin, format_of(file))
+            .context(format!("Failed to load zone {}", origin))?;
+        if let Some(stamps) = snapshot::settled_stamps(&sources, read_at)? {
+            return snapshot::write(Path::new(output), &zone, &stamps);
+        }
+        std::thread::sleep(snapshot::MODIFIED_MARGIN);
     }
+    anyhow::bail!(
+        "Zone files of {} kept changing while being compiled",
+        origin
+    )
+}
 
-    // Create a cancellation token for graceful shutdown
-    let shutdown_token = tokio_util::sync::CancellationToken::new();
+/// Asks a running server to reload one zone through its HTTP API, printing
+/// the zone's reload status
+fn reload_zone(args: &[String]) -> Result<()> {
+    let (zone, config_path) = match args {
+        [zone] => (zone, Path::new("lrmdns.yaml")),
+        [zone, config] => (zone, Path::new(config)),
+        _ => anyhow::bail!("Usage: lrmdns reload <zone> [config-file]"),
+    };
+    let config = Config::from_file(config_path).context(format!(
+        "Failed to load config from {}",
+        config_path.display()
+    ))?;
+    let Some(api_listen) = &config.server.api_listen else {
+        anyhow::bail!("Reloading a zone requires the HTTP API (api_listen)");
+    };
 
-    // Set up signal handlers
-    let config_for_reload = config.clone();
-    let zone_store_for_reload = zone_store.clone();
-    let metrics_for_stats = metrics.clone();
-    let shutdown_token_for_signals = shutdown_token.clone();
+    let (status, body) = api_request(
+        api_listen,
+        "POST",
+        &format!("/zones/{}/reload", zone),
+        config.server.api_token.as_deref(),
+    )?;
+    println!("{}", body);
+    if status != 200 {
+        anyhow::bail!("Failed to reload zone {} (HTTP {})", zone, status);
+    }
+    Ok(())
+}
 
-    // Spawn signal handler tasks
-    tokio::spawn(async move {
-        handle_signals(
-            config_for_reload,
-            zone_store_for_reload,
-            metrics_for_stats,
-            shutdown_token_for_signals,
-        )
-        .await;
-    });
+/// Sends a request without a body to the HTTP API, with the bearer token
+/// if one is configured, returning the status code and response body
+fn api_request(
+    address: &str,
+    method: &str,
+    path: &str,
+    token: Option<&str>,
+) -> Result<(u16, String)> {
+    use std::io::{Read, Write};
 
-    // Run the DNS server with shutdown token
-    server.run(shutdown_token).await
-}
+    let mut stream = std::net::TcpStream::connect(address)
+        .context(format!("Failed to connect to the API at {}", address))?;
+    let authorization = match token {
+        Some(token) => format!("Authorization: Bearer {}\r\n", token),
+        None => String::new(),
+    };
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+        method, path, address, authorization
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
 
-fn load_zones(config: &Config) -> Result<ZoneStore> {
-    let mut zone_store = ZoneStore::new();
-    for zone_config in &config.zones {
-        tracing::info!(
-            "Loading zone: {} from {}",
-            zone_config.name,
-            zone_config.file.display()
-        );
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .context("Malformed HTTP response")?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .context("Malformed HTTP status line")?;
+    Ok((status, body.to_string()))
+}
 
-        let zone = zone::parse_zone_file(&zone_config.file, &zone_config.name)
-            .context(format!("Failed to load zone {}", zone_config.name))?;
+/// Guesses a zone file's format from its extension
+fn format_of(file: &str) -> ZoneFormat {
+    match Path::new(file).extension().and_then(|e| e.to_str()) {
+        Some("yaml" | "yml") => ZoneFormat::Yaml,
+        Some("json") => ZoneFormat::Json,
+        _ => ZoneFormat::Master,
+    }
+}
 
-        let record_count: usize = zone
-            .records
-            .values()
-            .map(|type_map| type_map.values().map(|v| v.len()).sum::<usize>())
-            .sum();
+/// Parses and lints a zone file and prints every error and warning found,
+/// failing if there were any errors
+fn check_zone_file(args: &[String]) -> Result<()> {
+    let [file, origin] = args else {
+        anyhow::bail!("Usage: lrmdns check <zone-file> <origin>");
+    };
 
-        tracing::info!("Zone {} loaded: {} records", zone_config.name, record_count);
+    let check = zone::check_zone_file(file, origin)?;
+    for diagnostic in &check.diagnostics {
+        println!("{}", diagnostic);
+    }
+    if let Some(zone) = &check.zone {
+        for violation in lint::lint_zone(zone) {
+            println!("{}: warning: {}", file, violation);
+        }
+    }
 
-        zone_store.add_zone(zone);
+    let errors = check.errors().count();
+    if errors > 0 {
+        anyhow::bail!("{} error(s) in zone {}", errors, origin);
+    }
+    if check.zone.is_none() {
+        anyhow::bail!("Zone file must contain an SOA record");
     }
-    Ok(zone_store)
+    Ok(())
 }
 
 #[cfg(unix)]
-async fn handle_signals(
-    config: Config,
-    zone_store: Arc<RwLock<ZoneStore>>,
-    metrics: Arc<Metrics>,
-    shutdown_token: tokio_util::sync::CancellationToken,
-) {
+async fn handle_signals(mut running: Running, shutdown_token: tokio_util::sync::CancellationToken) {
     use tokio::signal::unix::{SignalKind, signal};
 
     let mut sighup = signal(SignalKind::hangup()).expect("Failed to register SIGHUP handler");
@@ -168,37 +266,35 @@ async fn handle_signals(
     loop {
         tokio::select! {
             _ = sighup.recv() => {
-                tracing::info!("Received SIGHUP, reloading zones...");
-                match load_zones(&config) {
-                    Ok(new_store) => {
-                        let mut store = zone_store.write().await;
-                        *store = new_store;
-                        tracing::info!("Zones reloaded successfully");
+                tracing::info!("Received SIGHUP, reloading configuration...");
+                match running.reload().await {
+                    Ok(()) => {
+                        tracing::info!("Configuration reloaded successfully");
                     }
                     Err(e) => {
-                        tracing::error!("Failed to reload zones: {}", e);
+                        tracing::error!("{:#}", e);
                     }
                 }
             }
             _ = sigusr1.recv() => {
                 tracing::info!("Received SIGUSR1, logging metrics...");
-                metrics.log_summary();
+                running.metrics().log_summary();
             }
             _ = sigint.recv() => {
                 tracing::info!("Received SIGINT, shutting down gracefully...");
-                metrics.log_summary();
+                running.metrics().log_summary();
                 shutdown_token.cancel();
                 break;
             }
             _ = sigquit.recv() => {
                 tracing::info!("Received SIGQUIT, shutting down gracefully...");
-                metrics.log_summary();
+                running.metrics().log_summary();
                 shutdown_token.cancel();
                 break;
             }
             _ = sigterm.recv() => {
                 tracing::info!("Received SIGTERM, shutting down gracefully...");
-                metrics.log_summary();
+                running.metrics().log_summary();
                 shutdown_token.cancel();
                 break;
             }
@@ -207,15 +303,10 @@ async fn handle_signals(
 }
 
 #[cfg(not(unix))]
-async fn handle_signals(
-    _config: Config,
-    _zone_store: Arc<RwLock<ZoneStore>>,
-    metrics: Arc<Metrics>,
-    shutdown_token: tokio_util::sync::CancellationToken,
-) {
+async fn handle_signals(running: Running, shutdown_token: tokio_util::sync::CancellationToken) {
     // On non-Unix platforms, just wait for Ctrl+C
     tokio::signal::ctrl_c().await.ok();
     tracing::info!("Received Ctrl+C, shutting down gracefully...");
-    metrics.log_summary();
+    running.metrics().log_summary();
     shutdown_token.cancel();
 }
@@ -39,6 +39,10 @@ pub struct Metrics {
     pub tcp_queries_per_connection: AtomicU64,
     pub tcp_connection_timeouts: AtomicU64,
 
+    // Zone reloads triggered by file changes
+    pub auto_reloads: AtomicU64,
+    pub auto_reload_failures: AtomicU64,
+
     // Start time
     start_time: Instant,
 }
@@ -64,6 +68,8 @@ impl Metrics {
             tcp_connections: AtomicU64::new(0),
             tcp_queries_per_connection: AtomicU64::new(0),
             tcp_connection_timeouts: AtomicU64::new(0),
+            auto_reloads: AtomicU64::new(0),
+            auto_reload_failures: AtomicU64::new(0),
             start_time: Instant::now(),
         }
     }
@@ -141,11 +147,19 @@ impl Metrics {
         self.errors.fetch_add(1, Ordering::Relaxed);
     }
 
+    pub fn record_auto_reload(&self, succeeded: bool) {
+        if succeeded {
+            self.auto_reloads.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.auto_reload_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
     pub fn get_snapshot(&self) -> MetricsSnapshot {
         let total = self.total_queries.load(Ordering::Relaxed);
         let total_latency = self.total_latency_us.load(Ordering::Relaxed);
 
-        let avg_latency_us = if total > 0 { total_latency / total } else { 0 };
+        let avg_latency_us = total_latency.checked_div(total).unwrap_or(0);
 
         let min_latency = self.min_latency_us.load(Ordering::Relaxed);
         let min_latency_us = if min_latency == u64::MAX {
@@ -181,6 +195,8 @@ impl Metrics {
             tcp_connections: tcp_conn,
             avg_queries_per_connection: avg_queries_per_conn,
             tcp_connection_timeouts: self.tcp_connection_timeouts.load(Ordering::Relaxed),
+            auto_reloads: self.auto_reloads.load(Ordering::Relaxed),
+            auto_reload_failures: self.auto_reload_failures.load(Ordering::Relaxed),
             uptime: self.start_time.elapsed(),
         }
     }
@@ -211,6 +227,8 @@ pub struct MetricsSnapshot {
     pub tcp_connections: u64,
     pub avg_queries_per_connection: f64,
     pub tcp_connection_timeouts: u64,
+    pub auto_reloads: u64,
+    pub auto_reload_failures: u64,
     pub uptime: Duration,
 }
 
@@ -270,6 +288,14 @@ impl MetricsSnapshot {
                 self.tcp_connection_timeouts
             );
         }
+
+        if self.auto_reloads > 0 || self.auto_reload_failures > 0 {
+            tracing::info!(
+                "Automatic zone reloads: succeeded={} failed={}",
+                self.auto_reloads,
+                self.auto_reload_failures
+            );
+        }
     }
 }
 
@@ -341,6 +367,20 @@ mod tests {
         assert_eq!(snapshot.errors, 3);
     }
 
+    #[test]
+    fn test_auto_reloads() {
+        let metrics = Metrics::new();
+
+        metrics.record_auto_reload(true);
+        metrics.record_auto_reload(true);
+        metrics.record_auto_reload(false);
+
+        let snapshot = metrics.get_snapshot();
+
+        assert_eq!(snapshot.auto_reloads, 2);
+        assert_eq!(snapshot.auto_reload_failures, 1);
+    }
+
     #[test]
     fn test_latency_min_max() {
         let metrics = Metrics::new();
@@ -24,6 +24,34 @@ pub fn arb_dns_name() -> impl Strategy<Value = Name> {
         })
 }
 
+/// Randomize the case of every letter in `name`, as resolvers using DNS 0x20
+/// encoding do
+pub fn arb_random_case(name: &str) -> impl Strategy<Value = Name> + use<> {
+    let name = name.to_string();
+    prop::collection::vec(any::<bool>(), name.len()).prop_map(move |upper| {
+        let randomized: String = name
+            .chars()
+            .zip(upper)
+            .map(|(c, up)| {
+                if up {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                }
+            })
+            .collect();
+        Name::from_str(&randomized).unwrap()
+    })
+}
+
+/// A lowercase DNS name paired with a random-case spelling of the same name
+pub fn arb_dns_name_with_case_variant() -> impl Strategy<Value = (Name, Name)> {
+    arb_dns_name().prop_flat_map(|name| {
+        let variant = arb_random_case(&name.to_string());
+        (Just(name), variant)
+    })
+}
+
 pub fn arb_record_type() -> impl Strategy<Value = RecordType> {
     prop_oneof![
         Just(RecordType::A),
@@ -108,6 +136,43 @@ pub fn arb_record(name: Name) -> impl Strategy<Value = Record> {
     ]
 }
 
+/// Arbitrary character-string contents, including quotes, backslashes,
+/// whitespace and non-printable bytes
+pub fn arb_character_string() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(any::<u8>(), 0..=255)
+}
+
+/// A record of a type both the zone file parser and writer support, with
+/// arbitrary RDATA and TTL
+pub fn arb_zone_file_record(name: Name) -> impl Strategy<Value = Record> {
+    use hickory_proto::rr::rdata::{A, AAAA, CNAME, HINFO, MX, NS, SRV, TXT};
+
+    let rdata = prop_oneof![
+        arb_ipv4().prop_map(|ip| RData::A(A(ip))),
+        arb_ipv6().prop_map(|ip| RData::AAAA(AAAA(ip))),
+        arb_dns_name().prop_map(|target| RData::NS(NS(target))),
+        arb_dns_name().prop_map(|target| RData::CNAME(CNAME(target))),
+        (any::<u16>(), arb_dns_name())
+            .prop_map(|(preference, exchange)| RData::MX(MX::new(preference, exchange))),
+        (any::<u16>(), any::<u16>(), any::<u16>(), arb_dns_name()).prop_map(
+            |(priority, weight, port, target)| {
+                RData::SRV(SRV::new(priority, weight, port, target))
+            }
+        ),
+        prop::collection::vec(arb_character_string(), 1..4).prop_map(|strings| {
+            RData::TXT(TXT::from_bytes(strings.iter().map(Vec::as_slice).collect()))
+        }),
+        (arb_character_string(), arb_character_string()).prop_map(|(cpu, os)| {
+            RData::HINFO(HINFO::from_bytes(
+                cpu.into_boxed_slice(),
+                os.into_boxed_slice(),
+            ))
+        }),
+    ];
+
+    (rdata, arb_ttl()).prop_map(move |(rdata, ttl)| Record::from_rdata(name.clone(), ttl, rdata))
+}
+
 pub fn arb_query_message() -> impl Strategy<Value = Message> {
     (any::<u16>(), arb_dns_name(), arb_record_type()).prop_map(|(id, name, rtype)| {
         let mut msg = Message::new();
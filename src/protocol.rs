@@ -1,24 +1,70 @@
-use crate::zone::ZoneStore;
+use crate::backend::ZoneBackend;
+use crate::config::{AnyConfig, AnyResponse};
+use crate::metrics::Protocol;
+use crate::zone::{Zone, ZoneStore, synthesize_owner};
 use anyhow::Result;
 use hickory_proto::op::{Message, MessageType, OpCode, ResponseCode};
-use hickory_proto::rr::RecordType;
+use hickory_proto::rr::{Name, RData, Record, RecordType};
+use std::net::IpAddr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+
+/// TTL of the synthesized HINFO record returned for minimal ANY responses
+const ANY_HINFO_TTL: u32 = 3600;
+
+/// Maximum number of SVCB/HTTPS AliasMode hops followed for additional data
+const MAX_SVCB_ALIAS_CHAIN: usize = 8;
+
+/// Transport details of a query that affect how it is answered
+#[derive(Debug, Clone, Copy)]
+pub struct QueryContext {
+    pub protocol: Protocol,
+    pub client: Option<IpAddr>,
+}
+
+impl Default for QueryContext {
+    fn default() -> Self {
+        QueryContext {
+            protocol: Protocol::Udp,
+            client: None,
+        }
+    }
+}
 
 pub struct QueryProcessor {
-    zones: Arc<RwLock<ZoneStore>>,
+    zones: Arc<dyn ZoneBackend>,
+    any_config: AnyConfig,
 }
 
 impl QueryProcessor {
-    pub fn new(zones: Arc<RwLock<ZoneStore>>) -> Self {
-        QueryProcessor { zones }
+    pub fn new(zones: Arc<dyn ZoneBackend>) -> Self {
+        QueryProcessor {
+            zones,
+            any_config: AnyConfig::default(),
+        }
+    }
+
+    pub fn with_any_config(mut self, any_config: AnyConfig) -> Self {
+        self.any_config = any_config;
+        self
     }
 
-    pub fn get_zones(&self) -> Arc<RwLock<ZoneStore>> {
+    #[cfg(test)]
+    pub fn get_zones(&self) -> Arc<dyn ZoneBackend> {
         self.zones.clone()
     }
 
+    /// Process a query as if it arrived over UDP from an unknown client
+    #[cfg(test)]
     pub async fn process_query(&self, query: &Message) -> Result<Message> {
+        self.process_query_from(query, QueryContext::default())
+            .await
+    }
+
+    pub async fn process_query_from(
+        &self,
+        query: &Message,
+        context: QueryContext,
+    ) -> Result<Message> {
         let mut response = Message::new();
 
         // Copy query ID and set response flags
@@ -35,15 +81,26 @@ impl QueryProcessor {
         }
 
         // Check for AXFR query (zone transfer)
-        // AXFR must be handled specially by the TCP server
         if let Some(question) = query.queries().first()
             && question.query_type() == RecordType::AXFR
         {
-            // AXFR queries must be over TCP
-            // We'll return the response with a special marker
-            // The TCP handler will detect this and stream the zone
             response.add_query(question.clone());
-            response.set_authoritative(true);
+            if !matches!(context.protocol, Protocol::Tcp) {
+                // AXFR queries must be over TCP; no records over UDP
+                response.set_authoritative(true);
+                return Ok(response);
+            }
+            // Every record of the zone, SOA first and last. The TCP server
+            // streams them to the client one record per message.
+            match self.zones.snapshot().find_zone(question.name()) {
+                Some(zone) => {
+                    response.set_authoritative(true);
+                    response.add_answers(zone.get_all_records());
+                }
+                None => {
+                    response.set_response_code(ResponseCode::Refused);
+                }
+            }
             return Ok(response);
         }
 
@@ -79,10 +136,11 @@ impl QueryProcessor {
             "unknown" // Will be filled in by server
         );
 
-        // Find the authoritative zone
-        let zones = self.zones.read().await;
+        // Find the authoritative zone. Every lookup for this query, including
+        // ALIAS and SVCB targets in other zones, uses the same snapshot.
+        let zones = self.zones.snapshot();
         let zone = match zones.find_zone(qname) {
-            Some(z) => z,
+            Some(z) => z.clone(),
             None => {
                 // Not authoritative for this zone
                 response.set_response_code(ResponseCode::Refused);
@@ -94,26 +152,56 @@ impl QueryProcessor {
         // Set authoritative answer flag
         response.set_authoritative(true);
 
-        // Check if the name exists in the zone
-        let name_exists = zone.contains_name(qname);
+        // Check if the name exists in the zone (empty non-terminals count as existing)
+        let name_exists = zone.name_exists(qname);
+
+        // RFC 4592: a name that doesn't exist is answered from the wildcard
+        // at its closest encloser, if there is one
+        let wildcard_source = if name_exists {
+            None
+        } else {
+            zone.wildcard_source(qname)
+        };
+
+        // Lookup at the query name, or synthesize from the wildcard with the
+        // query name as owner
+        let lookup_at_source = |rtype: RecordType| match wildcard_source {
+            Some(_) => zone.lookup_wildcard(qname, rtype),
+            None => zone.lookup(qname, rtype).cloned(),
+        };
 
-        // Lookup the requested record type
-        let lookup_result = if name_exists {
-            zone.lookup(qname, qtype)
+        // Lookup the requested record type (ANY is answered per RFC 8482)
+        let lookup_result = if qtype == RecordType::ANY {
+            self.lookup_any(&zone, qname, wildcard_source.as_ref(), dnssec_ok, context)
         } else {
-            // Try wildcard lookup if exact name doesn't exist
-            zone.lookup_wildcard(qname, qtype)
+            lookup_at_source(qtype)
         };
 
+        // Flatten an ALIAS at this name into synthesized A/AAAA records
+        let lookup_result = lookup_result.or_else(|| {
+            if name_exists && matches!(qtype, RecordType::A | RecordType::AAAA) {
+                synthesize_alias(&zones, &zone, qname, qtype)
+            } else {
+                None
+            }
+        });
+
         match lookup_result {
             Some(records) => {
                 // Found records of the requested type
-                for record in records {
+                for record in &records {
                     response.add_answer(record.clone());
                 }
 
-                // If DNSSEC OK flag is set, include RRSIG records
-                if dnssec_ok && let Some(rrsigs) = zone.lookup(qname, RecordType::SIG) {
+                if matches!(qtype, RecordType::SVCB | RecordType::HTTPS) {
+                    for record in svcb_additionals(&zones, &records) {
+                        response.add_additional(record);
+                    }
+                }
+
+                // If DNSSEC OK flag is set, include RRSIG records (for wildcard
+                // answers these are the wildcard's signatures, owned by the query name)
+                if dnssec_ok && let Some(rrsigs) = lookup_at_source(RecordType::SIG) {
                     for rrsig in rrsigs {
                         // Check if this RRSIG covers the queried record type
                         if let Some(hickory_proto::rr::RData::DNSSEC(
@@ -121,7 +209,7 @@ impl QueryProcessor {
                         )) = rrsig.data()
                             && sig.type_covered() == qtype
                         {
-                            response.add_answer(rrsig.clone());
+                            response.add_answer(rrsig);
                         }
                     }
                 }
@@ -131,15 +219,11 @@ impl QueryProcessor {
             }
             None => {
                 // Check if there's a CNAME record for this name (exact or wildcard)
-                let cname_result = if name_exists {
-                    zone.lookup(qname, RecordType::CNAME)
-                } else {
-                    zone.lookup_wildcard(qname, RecordType::CNAME)
-                };
+                let cname_result = lookup_at_source(RecordType::CNAME);
 
                 if let Some(cname_records) = cname_result {
                     // Add CNAME record(s) to answer
-                    for cname_record in cname_records {
+                    for cname_record in &cname_records {
                         response.add_answer(cname_record.clone());
 
                         // Chase the CNAME to find the target records
@@ -164,8 +248,9 @@ impl QueryProcessor {
                         }
                     }
                     response.set_response_code(ResponseCode::NoError);
-                } else if name_exists {
-                    // Name exists but no record of this type and no CNAME
+                } else if name_exists || wildcard_source.is_some() {
+                    // Name exists (or is covered by a wildcard) but has no record
+                    // of this type and no CNAME
                     response.set_response_code(ResponseCode::NoError);
 
                     // Add SOA in authority section
@@ -209,12 +294,172 @@ impl QueryProcessor {
 
         Ok(response)
     }
+
+    /// Answer QTYPE=ANY per RFC 8482. Allowed TCP clients get every RRset at
+    /// the name; everyone else gets a synthesized HINFO or one representative
+    /// RRset so ANY cannot be used for amplification.
+    fn lookup_any(
+        &self,
+        zone: &Zone,
+        qname: &Name,
+        wildcard_source: Option<&Name>,
+        dnssec_ok: bool,
+        context: QueryContext,
+    ) -> Option<Vec<Record>> {
+        let source = wildcard_source.unwrap_or(qname);
+        let rrsets: Vec<&Vec<Record>> = zone
+            .lookup_rrsets(source)
+            .into_iter()
+            .filter(|rrset| {
+                dnssec_ok || rrset.first().map(|r| r.record_type()) != Some(RecordType::SIG)
+            })
+            .collect();
+
+        if rrsets.is_empty() {
+            return None;
+        }
+
+        let full = matches!(context.protocol, Protocol::Tcp)
+            && self.any_config.allows_full_tcp(context.client);
+
+        let records: Vec<Record> = if full {
+            rrsets.into_iter().flatten().cloned().collect()
+        } else {
+            match self.any_config.udp_response {
+                AnyResponse::Hinfo => vec![Record::from_rdata(
+                    qname.clone(),
+                    ANY_HINFO_TTL,
+                    RData::HINFO(hickory_proto::rr::rdata::HINFO::new(
+                        "RFC8482".to_string(),
+                        String::new(),
+                    )),
+                )],
+                AnyResponse::Rrset => {
+                    let rrset = rrsets
+                        .iter()
+                        .find(|rrset| {
+                            rrset.first().map(|r| r.record_type()) != Some(RecordType::SIG)
+                        })
+                        .unwrap_or(&rrsets[0]);
+                    let rtype = rrset.first().map(|r| r.record_type());
+
+                    let mut records: Vec<Record> = rrset.to_vec();
+                    // Include the signatures covering the chosen RRset
+                    for rrsig in rrsets.iter().copied().flatten() {
+                        if let Some(RData::DNSSEC(
+                            hickory_proto::rr::dnssec::rdata::DNSSECRData::SIG(sig),
+                        )) = rrsig.data()
+                            && Some(sig.type_covered()) == rtype
+                        {
+                            records.push(rrsig.clone());
+                        }
+                    }
+                    records
+                }
+            }
+        };
+
+        tracing::debug!(
+            "ANY {} over {:?}: {} records (full={})",
+            qname,
+            context.protocol,
+            records.len(),
+            full
+        );
+
+        Some(match wildcard_source {
+            Some(_) => synthesize_owner(&records, qname),
+            None => records,
+        })
+    }
+}
+
+/// RFC 9460 section 4.1: collect the A/AAAA records of each SVCB/HTTPS
+/// target we serve, following AliasMode records to the records they point
+/// at. A `.` target means the owner name in ServiceMode and "no service" in
+/// AliasMode.
+fn svcb_additionals(zones: &ZoneStore, answers: &[Record]) -> Vec<Record> {
+    let mut additionals: Vec<Record> = Vec::new();
+    let mut pending: Vec<Record> = answers.to_vec();
+    let mut alias_hops = 0;
+
+    while let Some(record) = pending.pop() {
+        let svcb = match record.data() {
+            Some(RData::SVCB(svcb)) => svcb,
+            Some(RData::HTTPS(https)) => &https.0,
+            _ => continue,
+        };
+        let alias_mode = svcb.svc_priority() == 0;
+        let target = if !svcb.target_name().is_root() {
+            svcb.target_name().clone()
+        } else if alias_mode {
+            continue;
+        } else {
+            record.name().clone()
+        };
+        let Some(zone) = zones.find_zone(&target) else {
+            continue;
+        };
+
+        let mut rtypes = vec![RecordType::A, RecordType::AAAA];
+        if alias_mode && alias_hops < MAX_SVCB_ALIAS_CHAIN {
+            alias_hops += 1;
+            rtypes.push(record.record_type());
+        }
+        for rtype in rtypes {
+            for found in zone.lookup(&target, rtype).into_iter().flatten() {
+                if answers.contains(found) || additionals.contains(found) {
+                    continue;
+                }
+                if rtype == record.record_type() {
+                    pending.push(found.clone());
+                }
+                additionals.push(found.clone());
+            }
+        }
+    }
+
+    additionals
+}
+
+/// Build A/AAAA answers for an ALIAS owner by resolving its target against the
+/// zones we serve. Answers carry the query name as owner and the target's TTL.
+fn synthesize_alias(
+    zones: &ZoneStore,
+    zone: &Zone,
+    qname: &Name,
+    qtype: RecordType,
+) -> Option<Vec<Record>> {
+    let target = match zone.lookup(qname, RecordType::ANAME)?.first()?.data() {
+        Some(RData::ANAME(aname)) => aname.0.clone(),
+        _ => return None,
+    };
+
+    let target_records = zones.resolve_alias(&target, qtype)?;
+
+    tracing::debug!(
+        "ALIAS {} -> {}, synthesized {} {:?} records",
+        qname,
+        target,
+        target_records.len(),
+        qtype
+    );
+
+    Some(
+        target_records
+            .into_iter()
+            .filter_map(|record| {
+                let rdata = record.data()?.clone();
+                Some(Record::from_rdata(qname.clone(), record.ttl(), rdata))
+            })
+            .collect(),
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::zone::{SoaRecord, Zone};
+    use crate::zone::{SoaRecord, Zone, ZoneStore};
     use hickory_proto::op::Query;
     use hickory_proto::rr::{Name, RData, Record};
     use std::net::Ipv4Addr;
@@ -259,7 +504,7 @@ mod tests {
     async fn test_successful_query() {
         let mut store = ZoneStore::new();
         store.add_zone(create_test_zone());
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         let mut query = Message::new();
         query.set_id(1234);
@@ -278,11 +523,44 @@ mod tests {
         assert_eq!(response.answers().len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_unknown_type_served_opaquely() {
+        use hickory_proto::rr::rdata::NULL;
+        use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
+
+        let mut zone = create_test_zone();
+        let rdata = RData::Unknown {
+            code: RecordType::Unknown(65534),
+            rdata: NULL::with(vec![0x0a, 0, 0, 1]),
+        };
+        zone.add_record(Record::from_rdata(
+            Name::from_str("opaque.example.com.").unwrap(),
+            300,
+            rdata.clone(),
+        ));
+        let mut store = ZoneStore::new();
+        store.add_zone(zone);
+        let processor = QueryProcessor::new(Arc::new(store));
+
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_str("opaque.example.com.").unwrap(),
+            RecordType::Unknown(65534),
+        ));
+
+        let response = processor.process_query(&query).await.unwrap();
+        let response = Message::from_bytes(&response.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].data(), Some(&rdata));
+    }
+
     #[tokio::test]
     async fn test_nxdomain_query() {
         let mut store = ZoneStore::new();
         store.add_zone(create_test_zone());
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         let mut query = Message::new();
         query.set_id(5678);
@@ -302,7 +580,7 @@ mod tests {
     async fn test_refused_query() {
         let mut store = ZoneStore::new();
         store.add_zone(create_test_zone());
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         let mut query = Message::new();
         query.add_query(Query::query(
@@ -358,7 +636,7 @@ mod tests {
 
         let mut store = ZoneStore::new();
         store.add_zone(zone);
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         // Test wildcard match for non-existent name
         let mut query = Message::new();
@@ -404,7 +682,7 @@ mod tests {
     async fn test_dnssec_ok_flag() {
         let mut store = ZoneStore::new();
         store.add_zone(create_test_zone());
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         let mut query = Message::new();
         query.set_id(3333);
@@ -442,7 +720,7 @@ mod tests {
     async fn test_empty_query() {
         let mut store = ZoneStore::new();
         store.add_zone(create_test_zone());
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         // Query with no questions
         let mut query = Message::new();
@@ -460,7 +738,7 @@ mod tests {
     async fn test_invalid_opcode() {
         let mut store = ZoneStore::new();
         store.add_zone(create_test_zone());
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         let mut query = Message::new();
         query.set_id(4444);
@@ -481,7 +759,7 @@ mod tests {
     async fn test_query_for_different_zone() {
         let mut store = ZoneStore::new();
         store.add_zone(create_test_zone());
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         let mut query = Message::new();
         query.set_id(5555);
@@ -500,7 +778,7 @@ mod tests {
     async fn test_multiple_queries_in_message() {
         let mut store = ZoneStore::new();
         store.add_zone(create_test_zone());
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         // DNS spec says multiple queries in one message are allowed but rarely used
         let mut query = Message::new();
@@ -525,7 +803,7 @@ mod tests {
     async fn test_query_without_recursion_desired() {
         let mut store = ZoneStore::new();
         store.add_zone(create_test_zone());
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         let mut query = Message::new();
         query.set_id(7777);
@@ -572,7 +850,7 @@ mod tests {
 
         let mut store = ZoneStore::new();
         store.add_zone(zone);
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         let mut query = Message::new();
         query.set_id(8888);
@@ -593,11 +871,495 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_wildcard_answer_semantics() {
+        use hickory_proto::rr::dnssec::Algorithm;
+        use hickory_proto::rr::dnssec::rdata::{DNSSECRData, SIG};
+
+        let mut zone = create_test_zone();
+        let wildcard = Name::from_str("*.example.com.").unwrap();
+        zone.add_record(Record::from_rdata(
+            wildcard.clone(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 100))),
+        ));
+        zone.add_record(Record::from_rdata(
+            wildcard.clone(),
+            3600,
+            RData::DNSSEC(DNSSECRData::SIG(SIG::new(
+                RecordType::A,
+                Algorithm::RSASHA256,
+                2,
+                3600,
+                1767139200,
+                1764547200,
+                12345,
+                Name::from_str("example.com.").unwrap(),
+                vec![1, 2, 3, 4],
+            ))),
+        ));
+        // Creates the empty non-terminal b.example.com.
+        zone.add_record(Record::from_rdata(
+            Name::from_str("a.b.example.com.").unwrap(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 50))),
+        ));
+
+        let mut store = ZoneStore::new();
+        store.add_zone(zone);
+        let processor = QueryProcessor::new(Arc::new(store));
+
+        // Synthesized answer and its RRSIG are owned by the query name
+        let qname = Name::from_str("random.example.com.").unwrap();
+        let mut query = Message::new();
+        query.add_query(Query::query(qname.clone(), RecordType::A));
+        let mut edns = hickory_proto::op::Edns::new();
+        edns.set_dnssec_ok(true);
+        query.set_edns(edns);
+
+        let response = processor.process_query(&query).await.unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 2);
+        for answer in response.answers() {
+            assert_eq!(answer.name(), &qname);
+        }
+        let rrsig = response
+            .answers()
+            .iter()
+            .find(|r| r.record_type() == RecordType::SIG)
+            .expect("wildcard RRSIG should be included");
+        let Some(RData::DNSSEC(DNSSECRData::SIG(sig))) = rrsig.data() else {
+            panic!("expected SIG data, got {:?}", rrsig.data());
+        };
+        assert!(
+            sig.num_labels() < qname.num_labels(),
+            "RRSIG labels must reveal the wildcard expansion"
+        );
+
+        // Wildcard exists but has no TXT: NODATA, not NXDOMAIN
+        let mut query = Message::new();
+        query.add_query(Query::query(qname, RecordType::TXT));
+        let response = processor.process_query(&query).await.unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+
+        // Empty non-terminal blocks the wildcard and answers NODATA
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_str("b.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let response = processor.process_query(&query).await.unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+        assert_eq!(response.name_servers().len(), 1);
+
+        // Names below an existing closer name are not wildcard-expanded
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_str("x.a.b.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let response = processor.process_query(&query).await.unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    }
+
+    #[tokio::test]
+    async fn test_nodata_for_empty_non_terminals() {
+        let mut zone = create_test_zone();
+        // Delegation below an ENT: sub.example.com. only exists via child.sub
+        zone.add_record(Record::from_rdata(
+            Name::from_str("child.sub.example.com.").unwrap(),
+            3600,
+            RData::NS(hickory_proto::rr::rdata::NS(
+                Name::from_str("ns.child.sub.example.com.").unwrap(),
+            )),
+        ));
+        zone.add_record(Record::from_rdata(
+            Name::from_str("ns.child.sub.example.com.").unwrap(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 53))),
+        ));
+        // Apex wildcard, plus a wildcard whose parent is an ENT
+        zone.add_record(Record::from_rdata(
+            Name::from_str("*.example.com.").unwrap(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 100))),
+        ));
+        zone.add_record(Record::from_rdata(
+            Name::from_str("*.wild.example.com.").unwrap(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 101))),
+        ));
+
+        let mut store = ZoneStore::new();
+        store.add_zone(zone);
+        let processor = QueryProcessor::new(Arc::new(store));
+
+        for ent in ["sub.example.com.", "wild.example.com."] {
+            let mut query = Message::new();
+            query.add_query(Query::query(Name::from_str(ent).unwrap(), RecordType::A));
+            let response = processor.process_query(&query).await.unwrap();
+
+            assert_eq!(
+                response.response_code(),
+                ResponseCode::NoError,
+                "{} is an empty non-terminal",
+                ent
+            );
+            assert!(
+                response.answers().is_empty(),
+                "{} must not be wildcard-expanded",
+                ent
+            );
+            assert!(matches!(
+                response.name_servers().first().and_then(|r| r.data()),
+                Some(RData::SOA(_))
+            ));
+        }
+
+        // Names below the ENT still use the closest wildcard
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_str("host.wild.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let response = processor.process_query(&query).await.unwrap();
+        assert!(matches!(
+            response.answers().first().and_then(|r| r.data()),
+            Some(RData::A(a)) if a.0 == Ipv4Addr::new(192, 0, 2, 101)
+        ));
+
+        // Siblings of the ENT that don't exist are still wildcard-matched
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_str("other.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let response = processor.process_query(&query).await.unwrap();
+        assert!(matches!(
+            response.answers().first().and_then(|r| r.data()),
+            Some(RData::A(a)) if a.0 == Ipv4Addr::new(192, 0, 2, 100)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_apex_alias_flattened_from_other_zone() {
+        let mut zone = create_test_zone();
+        zone.add_record(Record::from_rdata(
+            Name::from_str("example.com.").unwrap(),
+            3600,
+            RData::ANAME(hickory_proto::rr::rdata::ANAME(
+                Name::from_str("cdn.example.net.").unwrap(),
+            )),
+        ));
+
+        let cdn_soa = SoaRecord {
+            mname: Name::from_str("ns1.example.net.").unwrap(),
+            rname: Name::from_str("admin.example.net.").unwrap(),
+            serial: 1,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 86400,
+        };
+        let mut cdn = Zone::new(Name::from_str("example.net.").unwrap(), cdn_soa);
+        cdn.add_record(Record::from_rdata(
+            Name::from_str("cdn.example.net.").unwrap(),
+            60,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(198, 51, 100, 7))),
+        ));
+
+        let mut store = ZoneStore::new();
+        store.add_zone(zone);
+        store.add_zone(cdn);
+        let processor = QueryProcessor::new(Arc::new(store));
+
+        let mut query = Message::new();
+        query.set_id(9100);
+        query.add_query(Query::query(
+            Name::from_str("example.com.").unwrap(),
+            RecordType::A,
+        ));
+
+        let response = processor.process_query(&query).await.unwrap();
+
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+        let answer = &response.answers()[0];
+        assert_eq!(answer.name(), &Name::from_str("example.com.").unwrap());
+        assert_eq!(answer.ttl(), 60, "Synthesized answer uses the target's TTL");
+        assert!(matches!(
+            answer.data(),
+            Some(RData::A(a)) if a.0 == Ipv4Addr::new(198, 51, 100, 7)
+        ));
+
+        // No AAAA at the target: NODATA with SOA, not NXDOMAIN
+        let mut query = Message::new();
+        query.set_id(9101);
+        query.add_query(Query::query(
+            Name::from_str("example.com.").unwrap(),
+            RecordType::AAAA,
+        ));
+
+        let response = processor.process_query(&query).await.unwrap();
+
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+        assert_eq!(response.name_servers().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_any_query_over_udp_returns_hinfo() {
+        let mut store = ZoneStore::new();
+        store.add_zone(create_test_zone());
+        let processor = QueryProcessor::new(Arc::new(store));
+
+        let mut query = Message::new();
+        query.set_id(9200);
+        query.add_query(Query::query(
+            Name::from_str("example.com.").unwrap(),
+            RecordType::ANY,
+        ));
+
+        let response = processor.process_query(&query).await.unwrap();
+
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+        match response.answers()[0].data() {
+            Some(RData::HINFO(hinfo)) => {
+                assert_eq!(hinfo.cpu(), b"RFC8482");
+                assert!(hinfo.os().is_empty());
+            }
+            other => panic!("Expected synthesized HINFO, got {:?}", other),
+        }
+
+        // Nonexistent names are still NXDOMAIN
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_str("missing.example.com.").unwrap(),
+            RecordType::ANY,
+        ));
+        let response = processor.process_query(&query).await.unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    }
+
+    #[tokio::test]
+    async fn test_any_query_representative_rrset() {
+        let mut store = ZoneStore::new();
+        store.add_zone(create_test_zone());
+        let processor = QueryProcessor::new(Arc::new(store)).with_any_config(AnyConfig {
+            udp_response: AnyResponse::Rrset,
+            ..AnyConfig::default()
+        });
+
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::ANY,
+        ));
+
+        let response = processor.process_query(&query).await.unwrap();
+
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].record_type(), RecordType::A);
+    }
+
+    #[tokio::test]
+    async fn test_any_query_over_tcp() {
+        let mut zone = create_test_zone();
+        zone.add_record(Record::from_rdata(
+            Name::from_str("www.example.com.").unwrap(),
+            3600,
+            RData::TXT(hickory_proto::rr::rdata::TXT::new(vec![
+                "hello".to_string(),
+            ])),
+        ));
+        let mut store = ZoneStore::new();
+        store.add_zone(zone);
+        let processor = QueryProcessor::new(Arc::new(store)).with_any_config(AnyConfig {
+            tcp_allow: vec![(IpAddr::from([192, 0, 2, 0]), 24)],
+            ..AnyConfig::default()
+        });
+
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::ANY,
+        ));
+
+        // Allowed TCP client gets every RRset
+        let allowed = QueryContext {
+            protocol: Protocol::Tcp,
+            client: Some("192.0.2.53".parse().unwrap()),
+        };
+        let response = processor.process_query_from(&query, allowed).await.unwrap();
+        assert_eq!(response.answers().len(), 2);
+
+        // Other TCP clients and UDP get the minimal answer
+        let denied = QueryContext {
+            protocol: Protocol::Tcp,
+            client: Some("198.51.100.1".parse().unwrap()),
+        };
+        let response = processor.process_query_from(&query, denied).await.unwrap();
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].record_type(), RecordType::HINFO);
+
+        let udp = QueryContext {
+            protocol: Protocol::Udp,
+            client: Some("192.0.2.53".parse().unwrap()),
+        };
+        let response = processor.process_query_from(&query, udp).await.unwrap();
+        assert_eq!(response.answers()[0].record_type(), RecordType::HINFO);
+    }
+
+    #[tokio::test]
+    async fn test_https_query_adds_target_addresses() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        writeln!(temp_file, "@ IN NS ns1").unwrap();
+        writeln!(temp_file, "@ IN HTTPS 1 . alpn=h2").unwrap();
+        writeln!(temp_file, "@ IN A 192.0.2.1").unwrap();
+        writeln!(temp_file, "www IN HTTPS 0 svc").unwrap();
+        writeln!(temp_file, "svc IN HTTPS 1 . alpn=h3 port=8443").unwrap();
+        writeln!(temp_file, "svc IN A 192.0.2.2").unwrap();
+        writeln!(temp_file, "svc IN AAAA 2001:db8::2").unwrap();
+        writeln!(temp_file, "gone IN HTTPS 0 .").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut store = ZoneStore::new();
+        store.add_zone(crate::zone::parse_zone_file(temp_file.path(), "example.com.").unwrap());
+        let processor = QueryProcessor::new(Arc::new(store));
+
+        let query_https = |name: &str| {
+            let mut query = Message::new();
+            query.add_query(Query::query(
+                Name::from_str(name).unwrap(),
+                RecordType::HTTPS,
+            ));
+            query
+        };
+
+        // ServiceMode with "." target: addresses of the owner name
+        let response = processor
+            .process_query(&query_https("example.com."))
+            .await
+            .unwrap();
+        assert_eq!(response.answers().len(), 1);
+        let additional_types: Vec<RecordType> = response
+            .additionals()
+            .iter()
+            .map(|r| r.record_type())
+            .collect();
+        assert_eq!(additional_types, [RecordType::A]);
+
+        // AliasMode: the target's HTTPS record and its addresses
+        let response = processor
+            .process_query(&query_https("www.example.com."))
+            .await
+            .unwrap();
+        assert_eq!(response.answers().len(), 1);
+        let svc = Name::from_str("svc.example.com.").unwrap();
+        assert!(response.additionals().iter().all(|r| r.name() == &svc));
+        let mut additional_types: Vec<RecordType> = response
+            .additionals()
+            .iter()
+            .map(|r| r.record_type())
+            .collect();
+        additional_types.sort();
+        assert_eq!(
+            additional_types,
+            [RecordType::A, RecordType::AAAA, RecordType::HTTPS]
+        );
+
+        // AliasMode with "." target means the service is unavailable
+        let response = processor
+            .process_query(&query_https("gone.example.com."))
+            .await
+            .unwrap();
+        assert_eq!(response.answers().len(), 1);
+        assert!(response.additionals().is_empty());
+
+        // Other query types get no SVCB additional processing
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_str("example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let response = processor.process_query(&query).await.unwrap();
+        assert!(response.additionals().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_additional_rr_types_served() {
+        use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        writeln!(temp_file, "host IN HINFO \"PC\" \"Linux\"").unwrap();
+        writeln!(temp_file, "host IN RP admin txt").unwrap();
+        writeln!(temp_file, "host IN LOC 42 21 54 N 71 06 18 W -24m 30m").unwrap();
+        writeln!(temp_file, "host IN URI 10 1 \"https://example.com/\"").unwrap();
+        writeln!(temp_file, "host IN CERT PKIX 0 0 AQID").unwrap();
+        writeln!(temp_file, "host IN OPENPGPKEY AQID").unwrap();
+        writeln!(temp_file, "host IN SMIMEA 3 1 1 0102").unwrap();
+        writeln!(temp_file, "@ IN CDS 12345 8 2 {}", "ab".repeat(32)).unwrap();
+        writeln!(temp_file, "@ IN CDNSKEY 257 3 8 AwEAAQ==").unwrap();
+        writeln!(temp_file, "@ IN ZONEMD 1 1 1 {}", "cd".repeat(48)).unwrap();
+        writeln!(temp_file, "@ IN CSYNC 1 3 A NS AAAA").unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = crate::zone::parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        let mut store = ZoneStore::new();
+        store.add_zone(zone.clone());
+        let processor = QueryProcessor::new(Arc::new(store));
+
+        let cases = [
+            ("host.example.com.", RecordType::HINFO),
+            ("host.example.com.", RecordType::from(17)),
+            ("host.example.com.", RecordType::from(29)),
+            ("host.example.com.", RecordType::from(256)),
+            ("host.example.com.", RecordType::from(37)),
+            ("host.example.com.", RecordType::OPENPGPKEY),
+            ("host.example.com.", RecordType::from(53)),
+            ("example.com.", RecordType::CDS),
+            ("example.com.", RecordType::CDNSKEY),
+            ("example.com.", RecordType::from(63)),
+            ("example.com.", RecordType::CSYNC),
+        ];
+        for (name, rtype) in cases {
+            let name = Name::from_str(name).unwrap();
+            let mut query = Message::new();
+            query.add_query(Query::query(name.clone(), rtype));
+
+            let response = processor.process_query(&query).await.unwrap();
+            let response = Message::from_bytes(&response.to_bytes().unwrap()).unwrap();
+
+            assert_eq!(response.response_code(), ResponseCode::NoError);
+            assert_eq!(response.answers().len(), 1, "{}", rtype);
+            assert_eq!(response.answers()[0].record_type(), rtype);
+            assert_eq!(
+                response.answers()[0].data(),
+                zone.lookup(&name, rtype).unwrap()[0].data(),
+                "{}",
+                rtype
+            );
+        }
+    }
+
     #[tokio::test]
     async fn test_axfr_query() {
         let mut store = ZoneStore::new();
         store.add_zone(create_test_zone());
-        let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+        let processor = QueryProcessor::new(Arc::new(store));
 
         let mut query = Message::new();
         query.set_id(9000);
@@ -625,7 +1387,7 @@ mod tests {
                 tokio::runtime::Runtime::new().unwrap().block_on(async {
                     let mut store = ZoneStore::new();
                     store.add_zone(create_test_zone());
-                    let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+                    let processor = QueryProcessor::new(Arc::new(store));
 
                     let mut query = Message::new();
                     query.set_id(query_id);
@@ -647,7 +1409,7 @@ mod tests {
                 tokio::runtime::Runtime::new().unwrap().block_on(async {
                     let mut store = ZoneStore::new();
                     store.add_zone(create_test_zone());
-                    let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+                    let processor = QueryProcessor::new(Arc::new(store));
 
                     let mut query = Message::new();
                     query.set_id(query_id);
@@ -669,7 +1431,7 @@ mod tests {
                 tokio::runtime::Runtime::new().unwrap().block_on(async {
                     let mut store = ZoneStore::new();
                     store.add_zone(create_test_zone());
-                    let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+                    let processor = QueryProcessor::new(Arc::new(store));
 
                     let mut query = Message::new();
                     query.set_id(1234);
@@ -693,7 +1455,7 @@ mod tests {
                 tokio::runtime::Runtime::new().unwrap().block_on(async {
                     let mut store = ZoneStore::new();
                     store.add_zone(create_test_zone());
-                    let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+                    let processor = QueryProcessor::new(Arc::new(store));
 
                     let fqdn = format!("{}", name);
                     let is_in_zone = fqdn.ends_with("example.com.");
@@ -718,7 +1480,7 @@ mod tests {
                 tokio::runtime::Runtime::new().unwrap().block_on(async {
                     let mut store = ZoneStore::new();
                     store.add_zone(create_test_zone());
-                    let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+                    let processor = QueryProcessor::new(Arc::new(store));
 
                     let mut query = Message::new();
                     query.set_id(query_id);
@@ -736,7 +1498,7 @@ mod tests {
                 tokio::runtime::Runtime::new().unwrap().block_on(async {
                     let mut store = ZoneStore::new();
                     store.add_zone(create_test_zone());
-                    let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+                    let processor = QueryProcessor::new(Arc::new(store));
 
                     let mut query = Message::new();
                     query.set_id(query_id);
@@ -758,7 +1520,7 @@ mod tests {
                 tokio::runtime::Runtime::new().unwrap().block_on(async {
                     let mut store = ZoneStore::new();
                     store.add_zone(create_test_zone());
-                    let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+                    let processor = QueryProcessor::new(Arc::new(store));
 
                     let mut query = Message::new();
                     query.set_id(1);
@@ -777,12 +1539,96 @@ mod tests {
                 })?;
             }
 
+            #[test]
+            fn prop_mixed_case_query_preserves_case(qname in arb_random_case("www.example.com.")) {
+                tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    let mut store = ZoneStore::new();
+                    store.add_zone(create_test_zone());
+                    let processor = QueryProcessor::new(Arc::new(store));
+
+                    let mut query = Message::new();
+                    query.set_id(1);
+                    query.add_query(Query::query(qname.clone(), RecordType::A));
+
+                    let response = processor.process_query(&query).await.unwrap();
+
+                    prop_assert_eq!(response.response_code(), ResponseCode::NoError);
+                    prop_assert!(
+                        response.queries()[0].name().eq_case(&qname),
+                        "Question must be echoed exactly as sent"
+                    );
+                    prop_assert_eq!(response.answers().len(), 1);
+                    prop_assert!(
+                        response.answers()[0]
+                            .name()
+                            .eq_case(&Name::from_str("www.example.com.").unwrap()),
+                        "Answer owner should use the zone's case"
+                    );
+                    Ok(())
+                })?;
+            }
+
+            #[test]
+            fn prop_mixed_case_axfr_transfers_zone(apex in arb_random_case("example.com.")) {
+                tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    let zone = create_test_zone();
+                    let expected = zone.get_all_records();
+                    let mut store = ZoneStore::new();
+                    store.add_zone(zone);
+                    let processor = QueryProcessor::new(Arc::new(store));
+
+                    let mut query = Message::new();
+                    query.set_id(1);
+                    query.add_query(Query::query(apex.clone(), RecordType::AXFR));
+                    let context = QueryContext {
+                        protocol: Protocol::Tcp,
+                        client: None,
+                    };
+
+                    let response = processor.process_query_from(&query, context).await.unwrap();
+
+                    prop_assert_eq!(response.response_code(), ResponseCode::NoError);
+                    prop_assert!(response.queries()[0].name().eq_case(&apex));
+                    prop_assert_eq!(response.answers(), expected.as_slice());
+                    prop_assert_eq!(response.answers().first().map(Record::record_type), Some(RecordType::SOA));
+                    prop_assert_eq!(response.answers().last().map(Record::record_type), Some(RecordType::SOA));
+                    Ok(())
+                })?;
+            }
+
+            #[test]
+            fn prop_mixed_case_cname_chase(qname in arb_random_case("ftp.example.com.")) {
+                tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    let mut zone = create_test_zone();
+                    zone.add_record(Record::from_rdata(
+                        Name::from_str("ftp.example.com.").unwrap(),
+                        3600,
+                        RData::CNAME(hickory_proto::rr::rdata::CNAME(
+                            Name::from_str("WWW.Example.COM.").unwrap(),
+                        )),
+                    ));
+                    let mut store = ZoneStore::new();
+                    store.add_zone(zone);
+                    let processor = QueryProcessor::new(Arc::new(store));
+
+                    let mut query = Message::new();
+                    query.set_id(1);
+                    query.add_query(Query::query(qname, RecordType::A));
+
+                    let response = processor.process_query(&query).await.unwrap();
+
+                    prop_assert_eq!(response.response_code(), ResponseCode::NoError);
+                    prop_assert_eq!(response.answers().len(), 2, "CNAME and its target");
+                    Ok(())
+                })?;
+            }
+
             #[test]
             fn prop_dnssec_ok_flag_preserved(dnssec_ok in any::<bool>()) {
                 tokio::runtime::Runtime::new().unwrap().block_on(async {
                     let mut store = ZoneStore::new();
                     store.add_zone(create_test_zone());
-                    let processor = QueryProcessor::new(Arc::new(RwLock::new(store)));
+                    let processor = QueryProcessor::new(Arc::new(store));
 
                     let mut query = Message::new();
                     query.set_id(1);
@@ -33,6 +33,11 @@ impl RateLimiter {
         }
     }
 
+    /// Changes the limit, keeping the queries already counted
+    pub fn set_max_qps(&self, max_qps: u32) {
+        self.inner.lock().unwrap().max_qps = max_qps;
+    }
+
     pub fn check_rate_limit(&self, addr: IpAddr) -> bool {
         let mut inner = self.inner.lock().unwrap();
 
@@ -124,6 +129,24 @@ mod tests {
         assert!(!limiter.check_rate_limit(addr2));
     }
 
+    #[test]
+    fn test_set_max_qps_keeps_counted_queries() {
+        let limiter = RateLimiter::new(5);
+        let addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        for _ in 0..3 {
+            assert!(limiter.check_rate_limit(addr));
+        }
+
+        // Lowered below what this client already sent
+        limiter.set_max_qps(3);
+        assert!(!limiter.check_rate_limit(addr));
+
+        limiter.set_max_qps(4);
+        assert!(limiter.check_rate_limit(addr));
+        assert!(!limiter.check_rate_limit(addr));
+    }
+
     #[test]
     fn test_window_expiration() {
         let limiter = RateLimiter::new(5);
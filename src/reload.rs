@@ -0,0 +1,497 @@
+use crate::api;
+use crate::backend::ZoneBackend;
+use crate::config::{BackendConfig, Config};
+use crate::files::{FileBackend, ReloadResult};
+use crate::metrics::Metrics;
+use crate::protocol::QueryProcessor;
+use crate::ratelimit::RateLimiter;
+use crate::server::{DnsServer, Listeners, ServerSettings};
+use crate::sqlite::SqliteBackend;
+use crate::watch::{self, ZoneWatch};
+use anyhow::{Context, Result};
+use arc_swap::ArcSwapOption;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing_subscriber::{EnvFilter, Registry};
+
+/// Handle for changing the log filter of the running subscriber
+pub type LogFilter = tracing_subscriber::reload::Handle<EnvFilter, Registry>;
+
+/// The filter for `log_level`, unless `RUST_LOG` overrides it
+pub fn log_filter(log_level: &str) -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| format!("lrmdns={}", log_level).into())
+}
+
+/// The configured zone backend, kept concrete so it can be reloaded
+#[derive(Clone)]
+pub enum Zones {
+    File(Arc<FileBackend>),
+    Sqlite(Arc<SqliteBackend>),
+}
+
+impl Zones {
+    pub fn open(config: &Config) -> Result<Self> {
+        Ok(match &config.server.backend {
+            BackendConfig::File => Zones::File(Arc::new(FileBackend::open(config)?)),
+            BackendConfig::Sqlite { path, .. } => {
+                tracing::info!("Loading zones from database {}", path.display());
+                Zones::Sqlite(Arc::new(SqliteBackend::open(
+                    path,
+                    config.server.zone_validation,
+                    config.server.serial_policy,
+                )?))
+            }
+        })
+    }
+
+    fn backend(&self) -> Arc<dyn ZoneBackend> {
+        match self {
+            Zones::File(store) => store.clone(),
+            Zones::Sqlite(backend) => backend.clone(),
+        }
+    }
+
+    /// Switch to the zones of `config` and reload every changed zone file,
+    /// or pick up database changes right away
+    async fn reload(&self, config: &Config) -> Result<()> {
+        match self {
+            Zones::File(backend) => {
+                let backend = backend.clone();
+                let config = config.clone();
+                let statuses =
+                    tokio::task::spawn_blocking(move || backend.reconfigure(&config)).await?;
+                let failed = statuses
+                    .iter()
+                    .filter(|status| status.result == ReloadResult::Failed)
+                    .count();
+                if failed > 0 {
+                    anyhow::bail!("{} of {} zone(s) failed to reload", failed, statuses.len());
+                }
+            }
+            Zones::Sqlite(backend) => {
+                backend.reconfigure(config.server.zone_validation, config.server.serial_policy);
+                let backend = backend.clone();
+                tokio::task::spawn_blocking(move || backend.refresh()).await??;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the database for changes every `poll_interval` seconds
+    fn spawn_polling(&self, config: &Config) {
+        let (Zones::Sqlite(backend), BackendConfig::Sqlite { poll_interval, .. }) =
+            (self, &config.server.backend)
+        else {
+            return;
+        };
+        let backend = backend.clone();
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(*poll_interval));
+
+        tokio::spawn(async move {
+            loop {
+                interval.tick().await;
+                let backend = backend.clone();
+                match tokio::task::spawn_blocking(move || backend.refresh()).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => tracing::error!("Failed to refresh zones from database: {:#}", e),
+                    Err(e) => tracing::error!("Zone database refresh task failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+/// A running server and the configuration it was started or last reloaded
+/// with. Reloading re-reads the configuration file and applies what
+/// changed without dropping queries: zones are added, removed or reloaded,
+/// rate limits, TCP limits, ANY handling and the log level take effect for
+/// new queries, and listeners are only rebound when their address changes.
+pub struct Running {
+    config_path: PathBuf,
+    config: Config,
+    zones: Zones,
+    server: Arc<DnsServer>,
+    metrics: Arc<Metrics>,
+    log_filter: LogFilter,
+    shutdown_token: CancellationToken,
+    /// Cancelled to stop serving on the current DNS listeners
+    listeners_token: CancellationToken,
+    api: Option<JoinHandle<()>>,
+    /// The API token, shared with the API server so a reload can change it
+    api_token: Arc<ArcSwapOption<String>>,
+    watch: Option<ZoneWatch>,
+}
+
+/// What a reload must set up before anything is changed, since it can fail
+struct Prepared {
+    config: Config,
+    listeners: Option<Listeners>,
+    /// `Some` if the API address changed, holding the new listener if any
+    api_listener: Option<Option<tokio::net::TcpListener>>,
+}
+
+impl Running {
+    /// Loads the zones and starts serving them
+    pub async fn start(
+        config_path: PathBuf,
+        config: Config,
+        metrics: Arc<Metrics>,
+        log_filter: LogFilter,
+        shutdown_token: CancellationToken,
+    ) -> Result<Self> {
+        let zones = Zones::open(&config)?;
+        zones.spawn_polling(&config);
+
+        let rate_limiter = config
+            .server
+            .rate_limit
+            .map(|limit| Arc::new(RateLimiter::new(limit)));
+        let server = Arc::new(DnsServer::new(
+            server_settings(&config, &zones, rate_limiter),
+            metrics.clone(),
+        ));
+        let listeners = Listeners::bind(&config.server.listen).await?;
+        let api_listener = match &config.server.api_listen {
+            Some(api_listen) => Some(bind_api(api_listen).await?),
+            None => None,
+        };
+
+        let api_token = config.server.api_token.clone().map(Arc::new);
+        let mut running = Running {
+            config_path,
+            listeners_token: shutdown_token.child_token(),
+            config,
+            zones,
+            server,
+            metrics,
+            log_filter,
+            shutdown_token,
+            api: None,
+            api_token: Arc::new(ArcSwapOption::from(api_token)),
+            watch: None,
+        };
+        running.serve(listeners);
+        running.api = api_listener.map(|listener| running.serve_api(listener));
+        running.watch = running.start_watch()?;
+        Ok(running)
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Re-reads the configuration file and applies it. The running
+    /// configuration is kept if the new one is invalid, needs a restart or
+    /// names an address that cannot be bound. Zones that fail to load keep
+    /// serving their previous version and are reported as an error once
+    /// everything else has been applied.
+    pub async fn reload(&mut self) -> Result<()> {
+        let prepared = self
+            .prepare()
+            .await
+            .context("Configuration reload rejected, keeping the running configuration")?;
+        self.apply(prepared).await
+    }
+
+    async fn prepare(&self) -> Result<Prepared> {
+        let config = Config::from_file(&self.config_path).context(format!(
+            "Failed to load config from {}",
+            self.config_path.display()
+        ))?;
+        config
+            .validate()
+            .context("Configuration validation failed")?;
+
+        let running = &self.config.server;
+        if config.server.backend != running.backend {
+            anyhow::bail!("Changing the zone backend requires a restart");
+        }
+
+        // Bind new addresses first, so a failure leaves the old ones serving
+        let listeners = if config.server.listen != running.listen {
+            Some(Listeners::bind(&config.server.listen).await?)
+        } else {
+            None
+        };
+        let api_listener = match &config.server.api_listen {
+            api_listen if *api_listen == running.api_listen => None,
+            Some(api_listen) => Some(Some(bind_api(api_listen).await?)),
+            None => Some(None),
+        };
+
+        Ok(Prepared {
+            config,
+            listeners,
+            api_listener,
+        })
+    }
+
+    async fn apply(&mut self, prepared: Prepared) -> Result<()> {
+        let Prepared {
+            config,
+            listeners,
+            api_listener,
+        } = prepared;
+        let zones = self.zones.reload(&config).await;
+
+        let rate_limiter = match (
+            config.server.rate_limit,
+            &self.server.settings().rate_limiter,
+        ) {
+            (Some(limit), Some(limiter)) => {
+                limiter.set_max_qps(limit);
+                Some(limiter.clone())
+            }
+            (Some(limit), None) => Some(Arc::new(RateLimiter::new(limit))),
+            (None, _) => None,
+        };
+        self.server
+            .update(server_settings(&config, &self.zones, rate_limiter));
+
+        if config.server.log_level != self.config.server.log_level {
+            tracing::info!("Log level set to {}", config.server.log_level);
+            if let Err(e) = self.log_filter.reload(log_filter(&config.server.log_level)) {
+                tracing::error!("Failed to change the log level: {}", e);
+            }
+        }
+        if config.server.workers != self.config.server.workers {
+            tracing::warn!("Changing workers takes effect after a restart");
+        }
+
+        if let Some(listeners) = listeners {
+            tracing::info!("DNS server moving to {}", config.server.listen);
+            self.listeners_token.cancel();
+            self.listeners_token = self.shutdown_token.child_token();
+            self.serve(listeners);
+        }
+        self.api_token
+            .store(config.server.api_token.clone().map(Arc::new));
+        if let Some(api_listener) = api_listener {
+            if let Some(api) = self.api.take() {
+                api.abort();
+            }
+            self.api = api_listener.map(|listener| self.serve_api(listener));
+        }
+
+        let watch_changed = config.server.watch != self.config.server.watch;
+        self.config = config;
+        if watch_changed {
+            self.watch = None;
+            match self.start_watch() {
+                Ok(watch) => self.watch = watch,
+                Err(e) => tracing::error!("Failed to watch zone files: {:#}", e),
+            }
+        } else if let Some(watch) = &self.watch {
+            watch.refresh();
+        }
+
+        zones.context("Configuration reloaded, but not every zone")
+    }
+
+    fn serve(&self, listeners: Listeners) {
+        let server = self.server.clone();
+        let token = self.listeners_token.clone();
+        tokio::spawn(async move {
+            if let Err(e) = server.serve(listeners, token).await {
+                tracing::error!("DNS server failed: {:#}", e);
+            }
+        });
+    }
+
+    fn serve_api(&self, listener: tokio::net::TcpListener) -> JoinHandle<()> {
+        let reloadable = match &self.zones {
+            Zones::File(backend) => Some(backend.clone()),
+            Zones::Sqlite(_) => None,
+        };
+        let api_app = api::create_router(self.metrics.clone(), reloadable, self.api_token.clone());
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, api_app).await {
+                tracing::error!("API server failed: {}", e);
+            }
+        })
+    }
+
+    /// Reload zones when their files change, if configured
+    fn start_watch(&self) -> Result<Option<ZoneWatch>> {
+        match (&self.config.server.watch, &self.zones) {
+            (Some(watch_config), Zones::File(backend)) => Ok(Some(watch::spawn(
+                backend.clone(),
+                watch_config,
+                self.metrics.clone(),
+            )?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn server_settings(
+    config: &Config,
+    zones: &Zones,
+    rate_limiter: Option<Arc<RateLimiter>>,
+) -> ServerSettings {
+    let processor = QueryProcessor::new(zones.backend())
+        .with_any_config(config.server.any.clone().unwrap_or_default());
+    ServerSettings {
+        processor: Arc::new(processor),
+        rate_limiter,
+        tcp_config: config.server.tcp.clone(),
+    }
+}
+
+async fn bind_api(api_listen: &str) -> Result<tokio::net::TcpListener> {
+    let listener = tokio::net::TcpListener::bind(api_listen)
+        .await
+        .context(format!("Failed to bind API server to {}", api_listen))?;
+    tracing::info!("HTTP API server listening on {}", api_listen);
+    Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::rr::Name;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    fn write_zone(dir: &TempDir, origin: &str, extra: &str) {
+        std::fs::write(
+            dir.path().join(format!("{}zone", origin)),
+            format!(
+                "$ORIGIN {}\n$TTL 3600\n\
+                 @ IN SOA ns1 admin 1 7200 3600 1209600 3600\n\
+                 @ IN NS ns1\nns1 IN A 192.0.2.1\n{}",
+                origin, extra
+            ),
+        )
+        .unwrap();
+    }
+
+    /// Writes the config file, serving zones from `dir`
+    fn write_config(dir: &TempDir, server: &str, zones: &[&str]) -> PathBuf {
+        let mut config = format!("server:\n  listen: 127.0.0.1:0\n{}zones:\n", server);
+        for zone in zones {
+            config.push_str(&format!(
+                "  - name: {0}\n    file: {1}/{0}zone\n",
+                zone,
+                dir.path().display()
+            ));
+        }
+        let path = dir.path().join("lrmdns.yaml");
+        std::fs::write(&path, config).unwrap();
+        path
+    }
+
+    async fn start(path: PathBuf) -> (Running, impl Sized) {
+        let (layer, log_filter): (_, LogFilter) =
+            tracing_subscriber::reload::Layer::new(log_filter("info"));
+        let config = Config::from_file(&path).unwrap();
+        let running = Running::start(
+            path,
+            config,
+            Arc::new(Metrics::new()),
+            log_filter,
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        (running, layer)
+    }
+
+    fn serves(running: &Running, origin: &str) -> bool {
+        let zones = running.server.settings().processor.get_zones();
+        zones.find_zone(&Name::from_str(origin).unwrap()).is_some()
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_config_changes() {
+        let dir = TempDir::new().unwrap();
+        write_zone(&dir, "example.com.", "");
+        write_zone(&dir, "example.org.", "");
+        let path = write_config(&dir, "  rate_limit: 10\n", &["example.com."]);
+        let (mut running, _layer) = start(path).await;
+        let limiter = running.server.settings().rate_limiter.clone().unwrap();
+
+        write_config(
+            &dir,
+            "  rate_limit: 5\n  tcp:\n    idle_timeout: 5\n  log_level: debug\n",
+            &["example.org."],
+        );
+        running.reload().await.unwrap();
+
+        assert!(!serves(&running, "example.com."));
+        assert!(serves(&running, "example.org."));
+        let settings = running.server.settings();
+        // The same limiter, so clients keep their counts
+        assert!(Arc::ptr_eq(
+            settings.rate_limiter.as_ref().unwrap(),
+            &limiter
+        ));
+        assert_eq!(settings.tcp_config.as_ref().unwrap().idle_timeout, 5);
+        assert_eq!(running.config.server.log_level, "debug");
+
+        write_config(&dir, "", &["example.org."]);
+        running.reload().await.unwrap();
+        assert!(running.server.settings().rate_limiter.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_reload_keeps_running_config() {
+        let dir = TempDir::new().unwrap();
+        write_zone(&dir, "example.com.", "");
+        let path = write_config(&dir, "", &["example.com."]);
+        let (mut running, _layer) = start(path.clone()).await;
+
+        // Invalid
+        write_config(&dir, "  tcp: {}\n", &["example.com.", "example.net."]);
+        let err = running.reload().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Configuration reload rejected, keeping the running configuration"
+        );
+        assert!(format!("{:#}", err).contains("Zone file does not exist"));
+
+        // Needs a restart
+        std::fs::write(
+            &path,
+            "server:\n  backend: { type: sqlite, path: zones.db }\nzones: []\n",
+        )
+        .unwrap();
+        let err = running.reload().await.unwrap_err();
+        assert!(format!("{:#}", err).contains("Changing the zone backend requires a restart"));
+
+        // Cannot bind
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let listen = taken.local_addr().unwrap().to_string();
+        write_zone(&dir, "example.net.", "");
+        write_config(&dir, "  tcp: {}\n", &["example.com.", "example.net."]);
+        let config = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, config.replace("127.0.0.1:0", &listen)).unwrap();
+        let err = running.reload().await.unwrap_err();
+        assert!(format!("{:#}", err).contains(&format!("Failed to bind TCP to {}", listen)));
+
+        assert!(!serves(&running, "example.net."));
+        assert!(running.server.settings().tcp_config.is_none());
+        assert_eq!(running.config.server.listen, "127.0.0.1:0");
+    }
+
+    #[tokio::test]
+    async fn test_reload_reports_failed_zones() {
+        let dir = TempDir::new().unwrap();
+        write_zone(&dir, "example.com.", "");
+        write_zone(&dir, "example.org.", "www IN A not-an-address\n");
+        let path = write_config(&dir, "", &["example.com."]);
+        let (mut running, _layer) = start(path).await;
+
+        write_config(&dir, "  rate_limit: 5\n", &["example.com.", "example.org."]);
+        let err = running.reload().await.unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "Configuration reloaded, but not every zone: 1 of 2 zone(s) failed to reload"
+        );
+        assert!(serves(&running, "example.com."));
+        assert!(!serves(&running, "example.org."));
+        assert!(running.server.settings().rate_limiter.is_some());
+    }
+}
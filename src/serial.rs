@@ -0,0 +1,299 @@
+use crate::config::{SerialPolicy, ValidationMode};
+use crate::zone::{SoaRecord, Zone};
+use anyhow::Result;
+use hickory_proto::rr::RecordType;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Whether `serial` is greater than `than` in RFC 1982 serial number
+/// arithmetic. Serials exactly 2^31 apart compare as neither.
+pub fn is_newer(serial: u32, than: u32) -> bool {
+    let distance = serial.wrapping_sub(than);
+    distance != 0 && distance < 1 << 31
+}
+
+/// Picks the serial of a freshly loaded `zone` that replaces the `served`
+/// version. If the contents are the same apart from the serial, a policy
+/// other than `keep` serves the previous serial again. If they changed,
+/// `keep` checks that the zone's own serial was increased, warning or
+/// refusing the zone per `validation`, and the other policies pick a serial
+/// newer than the served one: the zone's own serial if the operator already
+/// increased it far enough, otherwise the next one the policy generates.
+pub fn apply(
+    policy: SerialPolicy,
+    validation: ValidationMode,
+    served: Option<&Zone>,
+    zone: &mut Zone,
+    now: SystemTime,
+) -> Result<()> {
+    let Some(served) = served else {
+        return Ok(());
+    };
+    let previous = served.soa.serial;
+    let loaded = zone.soa.serial;
+
+    if same_content(served, zone) {
+        if policy != SerialPolicy::Keep && !is_newer(loaded, previous) {
+            zone.set_serial(previous);
+        }
+        return Ok(());
+    }
+
+    let serial = match policy {
+        SerialPolicy::Keep => {
+            if !is_newer(loaded, previous) {
+                if validation == ValidationMode::Fatal {
+                    anyhow::bail!(
+                        "Zone changed without a serial increase: serial {} is not newer than {}",
+                        loaded,
+                        previous
+                    );
+                }
+                tracing::warn!(
+                    "Zone {} changed without a serial increase: serial {} is not newer than {}",
+                    zone.origin,
+                    loaded,
+                    previous
+                );
+            }
+            return Ok(());
+        }
+        SerialPolicy::Increment => previous.wrapping_add(1),
+        SerialPolicy::Unixtime => unix_seconds(now) as u32,
+        SerialPolicy::Dateserial => {
+            let (year, month, day) = civil_date(unix_seconds(now) / 86400);
+            (year * 10000 + month * 100 + day) * 100
+        }
+    };
+
+    let mut next = previous.wrapping_add(1);
+    for candidate in [serial, loaded] {
+        if is_newer(candidate, next) {
+            next = candidate;
+        }
+    }
+    if next != loaded {
+        tracing::info!(
+            "Zone {} changed, serial set to {} (was {})",
+            zone.origin,
+            next,
+            previous
+        );
+        zone.set_serial(next);
+    }
+    Ok(())
+}
+
+/// Whether two versions of a zone hold the same records, ignoring the SOA
+/// serial and the order of records within an RRset
+fn same_content(a: &Zone, b: &Zone) -> bool {
+    let soa = |zone: &Zone| SoaRecord {
+        serial: 0,
+        ..zone.soa.clone()
+    };
+    soa(a) == soa(b)
+        && a.records().len() == b.records().len()
+        && a.records().iter().all(|(name, rrsets)| {
+            b.records().get(name).is_some_and(|other| {
+                rrsets.len() == other.len()
+                    && rrsets.iter().all(|(rtype, records)| {
+                        *rtype == RecordType::SOA
+                            || other.get(rtype).is_some_and(|other| {
+                                records.len() == other.len()
+                                    && records.iter().all(|record| other.contains(record))
+                            })
+                    })
+            })
+        })
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Year, month and day of a count of days since 1970-01-01
+fn civil_date(days: u64) -> (u32, u32, u32) {
+    // Howard Hinnant's days_from_civil, inverted, for 400-year eras
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year as u32, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zone::parse_zone_file;
+    use std::time::Duration;
+    use tempfile::NamedTempFile;
+
+    /// 2026-10-18 12:00:00 UTC
+    const NOW: u64 = 1792324800;
+
+    fn zone(serial: u32, extra: &str) -> Zone {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            format!(
+                "$ORIGIN example.com.\n$TTL 3600\n\
+                 @ IN SOA ns1 admin {} 7200 3600 1209600 3600\n\
+                 @ IN NS ns1\nns1 IN A 192.0.2.1\n{}",
+                serial, extra
+            ),
+        )
+        .unwrap();
+        parse_zone_file(file.path(), "example.com.").unwrap()
+    }
+
+    fn applied(
+        policy: SerialPolicy,
+        served: &Zone,
+        mut zone: Zone,
+        validation: ValidationMode,
+    ) -> Result<u32> {
+        let now = UNIX_EPOCH + Duration::from_secs(NOW);
+        apply(policy, validation, Some(served), &mut zone, now)?;
+        let record = zone.get_all_records().remove(0);
+        let Some(hickory_proto::rr::RData::SOA(soa)) = record.data() else {
+            panic!("No SOA record");
+        };
+        assert_eq!(soa.serial(), zone.soa.serial);
+        Ok(zone.soa.serial)
+    }
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer(2, 1));
+        assert!(!is_newer(1, 2));
+        assert!(!is_newer(1, 1));
+        // Wrapping around
+        assert!(is_newer(0, u32::MAX));
+        assert!(is_newer(5, u32::MAX - 5));
+        assert!(!is_newer(u32::MAX, 0));
+        // Largest increment allowed
+        assert!(is_newer((1 << 31) - 1, 0));
+        // Undefined
+        assert!(!is_newer(1 << 31, 0));
+        assert!(!is_newer(0, 1 << 31));
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(NOW / 86400), (2026, 10, 18));
+        assert_eq!(civil_date(11016), (2000, 2, 29));
+        assert_eq!(civil_date(11017), (2000, 3, 1));
+    }
+
+    #[test]
+    fn test_keep_policy() {
+        let served = zone(10, "");
+        let warn = ValidationMode::Warn;
+        let fatal = ValidationMode::Fatal;
+        let changed = "www IN A 192.0.2.2\n";
+
+        assert_eq!(
+            applied(SerialPolicy::Keep, &served, zone(11, changed), fatal).unwrap(),
+            11
+        );
+        assert_eq!(
+            applied(SerialPolicy::Keep, &served, zone(10, changed), warn).unwrap(),
+            10
+        );
+        let err = applied(SerialPolicy::Keep, &served, zone(10, changed), fatal).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Zone changed without a serial increase: serial 10 is not newer than 10"
+        );
+        // Unchanged contents need no increase
+        assert_eq!(
+            applied(SerialPolicy::Keep, &served, zone(10, ""), fatal).unwrap(),
+            10
+        );
+    }
+
+    #[test]
+    fn test_generated_serials() {
+        let warn = ValidationMode::Warn;
+        let changed = "www IN A 192.0.2.2\n";
+        let served = zone(10, "");
+
+        assert_eq!(
+            applied(SerialPolicy::Increment, &served, zone(10, changed), warn).unwrap(),
+            11
+        );
+        assert_eq!(
+            applied(SerialPolicy::Unixtime, &served, zone(10, changed), warn).unwrap(),
+            NOW as u32
+        );
+        assert_eq!(
+            applied(SerialPolicy::Dateserial, &served, zone(10, changed), warn).unwrap(),
+            2026101800
+        );
+        // Increased by hand past what the policy would pick
+        assert_eq!(
+            applied(SerialPolicy::Increment, &served, zone(20, changed), warn).unwrap(),
+            20
+        );
+
+        // Second change of the day, and a zone already ahead of the clock
+        let served = zone(2026101800, "");
+        assert_eq!(
+            applied(SerialPolicy::Dateserial, &served, zone(1, changed), warn).unwrap(),
+            2026101801
+        );
+        let served = zone(NOW as u32 + 100, "");
+        assert_eq!(
+            applied(SerialPolicy::Unixtime, &served, zone(1, changed), warn).unwrap(),
+            NOW as u32 + 101
+        );
+
+        // Wrapping around
+        let served = zone(u32::MAX, "");
+        assert_eq!(
+            applied(
+                SerialPolicy::Increment,
+                &served,
+                zone(u32::MAX, changed),
+                warn
+            )
+            .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_unchanged_zone_keeps_served_serial() {
+        let warn = ValidationMode::Warn;
+        let served = zone(42, "www IN A 192.0.2.2\nwww IN A 192.0.2.3\n");
+
+        // Reordered, and with the serial from the file
+        let reloaded = zone(1, "www IN A 192.0.2.3\nwww IN A 192.0.2.2\n");
+        assert_eq!(
+            applied(SerialPolicy::Increment, &served, reloaded, warn).unwrap(),
+            42
+        );
+        assert_eq!(
+            applied(
+                SerialPolicy::Keep,
+                &served,
+                zone(1, "www IN A 192.0.2.2\nwww IN A 192.0.2.3\n"),
+                warn
+            )
+            .unwrap(),
+            1
+        );
+    }
+}
@@ -1,8 +1,9 @@
 use crate::config::TcpConfig;
 use crate::metrics::Metrics;
-use crate::protocol::QueryProcessor;
+use crate::protocol::{QueryContext, QueryProcessor};
 use crate::ratelimit::RateLimiter;
 use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
 use hickory_proto::op::Message;
 use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
 use std::sync::Arc;
@@ -16,33 +17,67 @@ const MAX_UDP_RECV_SIZE: usize = 4096;
 const MAX_TCP_DNS_PACKET_SIZE: usize = 65535;
 
 pub struct DnsServer {
-    processor: Arc<QueryProcessor>,
-    listen_addr: String,
+    settings: ArcSwap<ServerSettings>,
     metrics: Arc<Metrics>,
-    rate_limiter: Option<Arc<RateLimiter>>,
-    tcp_config: Option<TcpConfig>,
+}
+
+/// How queries are answered. Replaced as a whole when the configuration is
+/// reloaded; open TCP connections keep the settings they were accepted with.
+pub struct ServerSettings {
+    pub processor: Arc<QueryProcessor>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub tcp_config: Option<TcpConfig>,
+}
+
+/// UDP socket and TCP listener bound to the same address
+pub struct Listeners {
+    addr: String,
+    udp: UdpSocket,
+    tcp: TcpListener,
+}
+
+impl Listeners {
+    pub async fn bind(addr: &str) -> Result<Self> {
+        let udp = UdpSocket::bind(addr)
+            .await
+            .context(format!("Failed to bind UDP to {}", addr))?;
+        let tcp = TcpListener::bind(addr)
+            .await
+            .context(format!("Failed to bind TCP to {}", addr))?;
+        Ok(Listeners {
+            addr: addr.to_string(),
+            udp,
+            tcp,
+        })
+    }
 }
 
 impl DnsServer {
-    pub fn new(
-        processor: QueryProcessor,
-        listen_addr: String,
-        metrics: Arc<Metrics>,
-        rate_limiter: Option<Arc<RateLimiter>>,
-        tcp_config: Option<TcpConfig>,
-    ) -> Self {
+    pub fn new(settings: ServerSettings, metrics: Arc<Metrics>) -> Self {
         DnsServer {
-            processor: Arc::new(processor),
-            listen_addr,
+            settings: ArcSwap::from_pointee(settings),
             metrics,
-            rate_limiter,
-            tcp_config,
         }
     }
 
-    pub async fn run(&self, shutdown_token: tokio_util::sync::CancellationToken) -> Result<()> {
-        let udp_future = self.run_udp(shutdown_token.clone());
-        let tcp_future = self.run_tcp(shutdown_token.clone());
+    pub fn settings(&self) -> Arc<ServerSettings> {
+        self.settings.load_full()
+    }
+
+    /// Applies new settings to queries received from now on
+    pub fn update(&self, settings: ServerSettings) {
+        self.settings.store(Arc::new(settings));
+    }
+
+    /// Answers queries on `listeners` until `shutdown_token` is cancelled
+    pub async fn serve(
+        &self,
+        listeners: Listeners,
+        shutdown_token: tokio_util::sync::CancellationToken,
+    ) -> Result<()> {
+        let Listeners { addr, udp, tcp } = listeners;
+        let udp_future = self.run_udp(udp, &addr, shutdown_token.clone());
+        let tcp_future = self.run_tcp(tcp, &addr, shutdown_token.clone());
 
         // Run both servers concurrently until shutdown is requested
         tokio::select! {
@@ -53,19 +88,20 @@ impl DnsServer {
                 result?;
             }
             _ = shutdown_token.cancelled() => {
-                tracing::info!("Shutdown signal received, stopping servers");
+                tracing::info!("Stopping servers on {}", addr);
             }
         }
 
         Ok(())
     }
 
-    async fn run_udp(&self, shutdown_token: tokio_util::sync::CancellationToken) -> Result<()> {
-        let socket = UdpSocket::bind(&self.listen_addr)
-            .await
-            .context(format!("Failed to bind UDP to {}", self.listen_addr))?;
-
-        tracing::info!("DNS server listening on {} (UDP)", self.listen_addr);
+    async fn run_udp(
+        &self,
+        socket: UdpSocket,
+        addr: &str,
+        shutdown_token: tokio_util::sync::CancellationToken,
+    ) -> Result<()> {
+        tracing::info!("DNS server listening on {} (UDP)", addr);
 
         let socket = Arc::new(socket);
         // Size buffer to our advertised EDNS payload capability to avoid truncating
@@ -78,11 +114,12 @@ impl DnsServer {
                     match result {
                         Ok((len, addr)) => {
                             let data = buf[..len].to_vec();
-                            let processor = self.processor.clone();
+                            let settings = self.settings.load();
+                            let processor = settings.processor.clone();
                             let socket = socket.clone();
 
                             let metrics = self.metrics.clone();
-                            let rate_limiter = self.rate_limiter.clone();
+                            let rate_limiter = settings.rate_limiter.clone();
 
                             // Spawn a task to handle the query
                             tokio::spawn(async move {
@@ -100,30 +137,31 @@ impl DnsServer {
                     }
                 }
                 _ = shutdown_token.cancelled() => {
-                    tracing::info!("UDP server shutting down");
+                    tracing::info!("UDP server on {} shutting down", addr);
                     return Ok(());
                 }
             }
         }
     }
 
-    async fn run_tcp(&self, shutdown_token: tokio_util::sync::CancellationToken) -> Result<()> {
-        let listener = TcpListener::bind(&self.listen_addr)
-            .await
-            .context(format!("Failed to bind TCP to {}", self.listen_addr))?;
-
-        tracing::info!("DNS server listening on {} (TCP)", self.listen_addr);
+    async fn run_tcp(
+        &self,
+        listener: TcpListener,
+        addr: &str,
+        shutdown_token: tokio_util::sync::CancellationToken,
+    ) -> Result<()> {
+        tracing::info!("DNS server listening on {} (TCP)", addr);
 
         loop {
             tokio::select! {
                 result = listener.accept() => {
                     match result {
                         Ok((stream, addr)) => {
-                            let processor = self.processor.clone();
+                            let settings = self.settings.load();
+                            let processor = settings.processor.clone();
                             let metrics = self.metrics.clone();
-                            let rate_limiter = self.rate_limiter.clone();
-                            let zones = processor.get_zones();
-                            let tcp_config = self.tcp_config.clone();
+                            let rate_limiter = settings.rate_limiter.clone();
+                            let tcp_config = settings.tcp_config.clone();
 
                             // Spawn a task to handle the connection
                             tokio::spawn(async move {
@@ -133,7 +171,6 @@ impl DnsServer {
                                     processor,
                                     metrics,
                                     rate_limiter,
-                                    zones,
                                     tcp_config,
                                 )
                                 .await
@@ -148,7 +185,7 @@ impl DnsServer {
                     }
                 }
                 _ = shutdown_token.cancelled() => {
-                    tracing::info!("TCP server shutting down");
+                    tracing::info!("TCP server on {} shutting down", addr);
                     return Ok(());
                 }
             }
@@ -237,7 +274,11 @@ async fn handle_udp_query(
     );
 
     // Process the query
-    let response = match processor.process_query(&query).await {
+    let context = QueryContext {
+        protocol: Protocol::Udp,
+        client: Some(addr.ip()),
+    };
+    let response = match processor.process_query_from(&query, context).await {
         Ok(resp) => resp,
         Err(e) => {
             metrics.record_error();
@@ -339,7 +380,6 @@ async fn handle_tcp_connection(
     processor: Arc<QueryProcessor>,
     metrics: Arc<Metrics>,
     rate_limiter: Option<Arc<RateLimiter>>,
-    zones: Arc<tokio::sync::RwLock<crate::zone::ZoneStore>>,
     tcp_config: Option<TcpConfig>,
 ) -> Result<()> {
     use crate::metrics::Protocol;
@@ -488,70 +528,19 @@ async fn handle_tcp_connection(
             .unwrap_or(false);
 
         if is_axfr {
-            // Handle AXFR zone transfer
             tracing::info!(
                 "AXFR request from {} for {:?}",
                 addr,
                 query.queries().first().map(|q| q.name())
             );
-
-            // Get the zone
-            let zone_store = zones.read().await;
-            if let Some(question) = query.queries().first() {
-                if let Some(zone) = zone_store.find_zone(question.name()) {
-                    // Get all records in the zone
-                    let all_records = zone.get_all_records();
-
-                    tracing::debug!("AXFR: Streaming {} records to {}", all_records.len(), addr);
-
-                    // Stream each record as a separate DNS message
-                    for record in all_records {
-                        let mut axfr_msg = Message::new();
-                        axfr_msg.set_id(query.id());
-                        axfr_msg.set_message_type(hickory_proto::op::MessageType::Response);
-                        axfr_msg.set_op_code(hickory_proto::op::OpCode::Query);
-                        axfr_msg.set_authoritative(true);
-                        axfr_msg.add_query(question.clone());
-                        axfr_msg.add_answer(record);
-
-                        let msg_buf = axfr_msg
-                            .to_bytes()
-                            .context("Failed to encode AXFR message")?;
-
-                        let len = (msg_buf.len() as u16).to_be_bytes();
-                        stream.write_all(&len).await?;
-                        stream.write_all(&msg_buf).await?;
-                    }
-
-                    metrics.record_response(hickory_proto::op::ResponseCode::NoError);
-                    metrics.record_latency(start.elapsed());
-                    tracing::info!("AXFR completed for {}", addr);
-                    return Ok(());
-                } else {
-                    // Not authoritative for this zone
-                    tracing::warn!("AXFR refused for non-authoritative zone from {}", addr);
-                    let mut refused_response = Message::new();
-                    refused_response.set_id(query.id());
-                    refused_response.set_message_type(hickory_proto::op::MessageType::Response);
-                    refused_response.set_response_code(hickory_proto::op::ResponseCode::Refused);
-                    refused_response.add_query(question.clone());
-
-                    let response_buf = refused_response
-                        .to_bytes()
-                        .context("Failed to encode refused response")?;
-                    let len = (response_buf.len() as u16).to_be_bytes();
-                    stream.write_all(&len).await?;
-                    stream.write_all(&response_buf).await?;
-
-                    metrics.record_response(hickory_proto::op::ResponseCode::Refused);
-                    metrics.record_latency(start.elapsed());
-                    return Ok(());
-                }
-            }
         }
 
-        // Process the query (normal, non-AXFR)
-        let response = match processor.process_query(&query).await {
+        // Process the query
+        let context = QueryContext {
+            protocol: Protocol::Tcp,
+            client: Some(addr.ip()),
+        };
+        let mut response = match processor.process_query_from(&query, context).await {
             Ok(resp) => resp,
             Err(e) => {
                 metrics.record_error();
@@ -560,6 +549,38 @@ async fn handle_tcp_connection(
             }
         };
 
+        if is_axfr && response.response_code() == hickory_proto::op::ResponseCode::NoError {
+            let all_records = response.take_answers();
+            tracing::debug!("AXFR: Streaming {} records to {}", all_records.len(), addr);
+
+            // Stream each record as a separate DNS message
+            for record in all_records {
+                let mut axfr_msg = Message::new();
+                axfr_msg.set_id(query.id());
+                axfr_msg.set_message_type(hickory_proto::op::MessageType::Response);
+                axfr_msg.set_op_code(hickory_proto::op::OpCode::Query);
+                axfr_msg.set_authoritative(true);
+                axfr_msg.add_queries(response.queries().to_vec());
+                axfr_msg.add_answer(record);
+
+                let msg_buf = axfr_msg
+                    .to_bytes()
+                    .context("Failed to encode AXFR message")?;
+
+                let len = (msg_buf.len() as u16).to_be_bytes();
+                stream.write_all(&len).await?;
+                stream.write_all(&msg_buf).await?;
+            }
+
+            metrics.record_response(hickory_proto::op::ResponseCode::NoError);
+            metrics.record_latency(start.elapsed());
+            tracing::info!("AXFR completed for {}", addr);
+            return Ok(());
+        }
+        if is_axfr {
+            tracing::warn!("AXFR refused for non-authoritative zone from {}", addr);
+        }
+
         // Encode the response
         let response_buf = response
             .to_bytes()
@@ -596,7 +617,6 @@ mod tests {
     use hickory_proto::rr::{Name, RData, Record, RecordType};
     use std::net::Ipv4Addr;
     use std::str::FromStr;
-    use tokio::sync::RwLock;
 
     fn create_test_processor() -> QueryProcessor {
         let origin = Name::from_str("test.local.").unwrap();
@@ -622,7 +642,7 @@ mod tests {
         let mut store = ZoneStore::new();
         store.add_zone(zone);
 
-        QueryProcessor::new(Arc::new(RwLock::new(store)))
+        QueryProcessor::new(Arc::new(store))
     }
 
     #[tokio::test]
@@ -833,7 +853,7 @@ mod tests {
         zone.add_record(a_record);
         let mut store = ZoneStore::new();
         store.add_zone(zone);
-        let zone_store = Arc::new(RwLock::new(store));
+        let zone_store = Arc::new(store);
         let processor = Arc::new(QueryProcessor::new(zone_store.clone()));
         let metrics = Arc::new(Metrics::new());
 
@@ -845,7 +865,6 @@ mod tests {
         let server_limiter = rate_limiter.clone();
         let server_processor = processor.clone();
         let server_metrics = metrics.clone();
-        let server_zones = zone_store.clone();
         tokio::spawn(async move {
             if let Ok((stream, peer_addr)) = listener.accept().await {
                 let _ = handle_tcp_connection(
@@ -854,7 +873,6 @@ mod tests {
                     server_processor,
                     server_metrics,
                     Some(server_limiter),
-                    server_zones,
                     None,
                 )
                 .await;
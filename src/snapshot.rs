@@ -0,0 +1,526 @@
+use crate::config::ZoneConfig;
+use crate::zone::{self, SoaRecord, Zone};
+use anyhow::{Context, Result};
+use hickory_proto::rr::{DNSClass, Name, RData, Record, RecordType};
+use hickory_proto::serialize::binary::{
+    BinDecodable, BinDecoder, BinEncodable, BinEncoder, Restrict,
+};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// First bytes of every snapshot file
+const MAGIC: &[u8; 8] = b"LRMDNSZS";
+
+/// Layout version, bumped whenever the encoding changes. Snapshots written
+/// with another version are stale rather than corrupt.
+const FORMAT_VERSION: u32 = 1;
+
+/// Length of the trailing SHA-256 checksum
+const CHECKSUM_LEN: usize = 32;
+
+/// Files modified this close to the start of a load are assumed to have
+/// been modified while they were read
+pub const MODIFIED_MARGIN: Duration = Duration::from_secs(1);
+
+/// Size and modification time of a file a zone was loaded from, used to
+/// tell whether the file has changed since
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceStamp {
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+}
+
+impl SourceStamp {
+    pub fn of(path: &Path) -> Result<Self> {
+        let metadata =
+            std::fs::metadata(path).context(format!("Failed to stat {}", path.display()))?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(SourceStamp {
+            path: path.to_path_buf(),
+            len: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+
+    pub fn modified(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::new(self.modified_secs, self.modified_nanos)
+    }
+
+    /// Whether the file still has the size and modification time recorded
+    pub fn is_current(&self) -> bool {
+        SourceStamp::of(&self.path).ok().as_ref() == Some(self)
+    }
+}
+
+/// Stamps of `files` as read at `read_at`, or `None` if a file was
+/// modified around then: its stamp may not match what was read, so it must
+/// not be recorded as describing the zone. File system timestamps are
+/// coarse, hence the margin.
+pub fn settled_stamps(files: &[PathBuf], read_at: SystemTime) -> Result<Option<Vec<SourceStamp>>> {
+    let stamps = files
+        .iter()
+        .map(|path| SourceStamp::of(path))
+        .collect::<Result<Vec<_>>>()?;
+    let settled = read_at - MODIFIED_MARGIN;
+    if stamps.iter().any(|stamp| stamp.modified() >= settled) {
+        return Ok(None);
+    }
+    Ok(Some(stamps))
+}
+
+/// Encodes a zone and the stamps of the files it was parsed from, taken
+/// before they were read, as a snapshot:
+///
+/// ```text
+/// magic, version (u32),
+/// origin, SOA fields,
+/// source count (u32), then per source: path, length (u64), mtime (u64 s, u32 ns),
+/// record count (u32), then per record: owner, type, class, TTL, RDATA length and RDATA,
+/// SHA-256 of everything before it
+/// ```
+///
+/// Integers are big-endian, paths are length-prefixed UTF-8 and names are
+/// uncompressed wire format with their case preserved.
+pub fn encode(zone: &Zone, sources: &[SourceStamp]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+
+    emit_wire(&mut out, &zone.origin)?;
+    emit_wire(&mut out, &zone.soa.mname)?;
+    emit_wire(&mut out, &zone.soa.rname)?;
+    for value in [
+        zone.soa.serial,
+        zone.soa.refresh as u32,
+        zone.soa.retry as u32,
+        zone.soa.expire as u32,
+        zone.soa.minimum,
+    ] {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    out.extend_from_slice(&u32::try_from(sources.len())?.to_be_bytes());
+    for stamp in sources {
+        let path = stamp
+            .path
+            .canonicalize()
+            .context(format!("Failed to resolve {}", stamp.path.display()))?;
+        let path = path
+            .to_str()
+            .context(format!("Path {} is not valid UTF-8", path.display()))?;
+        out.extend_from_slice(&u32::try_from(path.len())?.to_be_bytes());
+        out.extend_from_slice(path.as_bytes());
+        out.extend_from_slice(&stamp.len.to_be_bytes());
+        out.extend_from_slice(&stamp.modified_secs.to_be_bytes());
+        out.extend_from_slice(&stamp.modified_nanos.to_be_bytes());
+    }
+
+    let records: Vec<&Record> = zone
+        .records()
+        .values()
+        .flat_map(|type_map| type_map.values().flatten())
+        .collect();
+    out.extend_from_slice(&u32::try_from(records.len())?.to_be_bytes());
+    for record in records {
+        emit_record(&mut out, record)?;
+    }
+
+    let checksum = Sha256::digest(&out);
+    out.extend_from_slice(&checksum);
+    Ok(out)
+}
+
+/// Appends `value` in wire format without name compression. Each value is
+/// encoded on its own since hickory's encoder is limited to message sizes.
+fn emit_wire(out: &mut Vec<u8>, value: &impl BinEncodable) -> Result<()> {
+    let mut wire = Vec::new();
+    let mut encoder = BinEncoder::new(&mut wire);
+    encoder.set_canonical_names(true);
+    value.emit(&mut encoder)?;
+    out.extend_from_slice(&wire);
+    Ok(())
+}
+
+/// Appends a record as in a DNS message, except that empty RDATA is kept
+/// rather than read back as an update prerequisite
+fn emit_record(out: &mut Vec<u8>, record: &Record) -> Result<()> {
+    emit_wire(out, record.name())?;
+    out.extend_from_slice(&u16::from(record.record_type()).to_be_bytes());
+    out.extend_from_slice(&u16::from(record.dns_class()).to_be_bytes());
+    out.extend_from_slice(&record.ttl().to_be_bytes());
+
+    let mut rdata = Vec::new();
+    if let Some(data) = record.data() {
+        emit_wire(&mut rdata, data)?;
+    }
+    out.extend_from_slice(&u16::try_from(rdata.len())?.to_be_bytes());
+    out.extend_from_slice(&rdata);
+    Ok(())
+}
+
+fn read_record(decoder: &mut BinDecoder<'_>) -> Result<Record> {
+    let name = Name::read(decoder)?;
+    let rtype = RecordType::from(decoder.read_u16()?.unverified());
+    let class = DNSClass::from(decoder.read_u16()?.unverified());
+    let ttl = read_u32(decoder)?;
+    let length = decoder.read_u16()?.unverified();
+    let rdata = RData::read(decoder, rtype, Restrict::new(length))?;
+
+    let mut record = Record::from_rdata(name, ttl, rdata);
+    record.set_dns_class(class);
+    Ok(record)
+}
+
+/// A decoded snapshot
+struct Snapshot {
+    zone: Zone,
+    sources: Vec<SourceStamp>,
+}
+
+/// Decodes a snapshot, returning `None` for one written with another
+/// format version. Fails if the data is not a snapshot or is corrupt.
+fn decode(data: &[u8], origin: &Name) -> Result<Option<Snapshot>> {
+    if data.len() < MAGIC.len() + 4 + CHECKSUM_LEN || !data.starts_with(MAGIC) {
+        anyhow::bail!("Not a zone snapshot");
+    }
+    let (body, checksum) = data.split_at(data.len() - CHECKSUM_LEN);
+    if Sha256::digest(body).as_slice() != checksum {
+        anyhow::bail!("Snapshot checksum mismatch");
+    }
+
+    let mut decoder = BinDecoder::new(&body[MAGIC.len()..]);
+    if read_u32(&mut decoder)? != FORMAT_VERSION {
+        return Ok(None);
+    }
+
+    let snapshot_origin = Name::read(&mut decoder)?;
+    if snapshot_origin != *origin {
+        anyhow::bail!("Snapshot is of zone {}, not {}", snapshot_origin, origin);
+    }
+    let soa = SoaRecord {
+        mname: Name::read(&mut decoder)?,
+        rname: Name::read(&mut decoder)?,
+        serial: read_u32(&mut decoder)?,
+        refresh: read_u32(&mut decoder)? as i32,
+        retry: read_u32(&mut decoder)? as i32,
+        expire: read_u32(&mut decoder)? as i32,
+        minimum: read_u32(&mut decoder)?,
+    };
+
+    let source_count = read_u32(&mut decoder)?;
+    let mut sources = Vec::new();
+    for _ in 0..source_count {
+        let len = read_u32(&mut decoder)? as usize;
+        let path = decoder.read_slice(len)?.unverified();
+        let path = PathBuf::from(std::str::from_utf8(path)?);
+        sources.push(SourceStamp {
+            path,
+            len: read_u64(&mut decoder)?,
+            modified_secs: read_u64(&mut decoder)?,
+            modified_nanos: read_u32(&mut decoder)?,
+        });
+    }
+
+    let mut zone = Zone::new(origin.clone(), soa);
+    let record_count = read_u32(&mut decoder)?;
+    for _ in 0..record_count {
+        zone.add_record(read_record(&mut decoder)?);
+    }
+    if !decoder.is_empty() {
+        anyhow::bail!("Trailing bytes after snapshot records");
+    }
+
+    Ok(Some(Snapshot { zone, sources }))
+}
+
+fn read_u32(decoder: &mut BinDecoder<'_>) -> Result<u32> {
+    Ok(decoder.read_u32()?.unverified())
+}
+
+fn read_u64(decoder: &mut BinDecoder<'_>) -> Result<u64> {
+    let bytes = decoder.read_slice(8)?.unverified();
+    Ok(u64::from_be_bytes(bytes.try_into()?))
+}
+
+/// Writes a snapshot of `zone`, replacing `path` atomically so a server
+/// never reads a half-written file
+pub fn write(path: &Path, zone: &Zone, sources: &[SourceStamp]) -> Result<()> {
+    let data = encode(zone, sources)?;
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    std::fs::write(&temp, data).context(format!("Failed to write {}", path.display()))?;
+    std::fs::rename(&temp, path).context(format!("Failed to replace {}", path.display()))
+}
+
+/// Loads a snapshot of the zone in `zone_file`. Returns `None`, logging why,
+/// if there is no snapshot or it is stale: compiled from another file or by
+/// another version, or any of its source files has changed since. Fails if
+/// the snapshot cannot be read, is corrupt or holds another zone.
+pub fn load(
+    path: &Path,
+    zone_file: &Path,
+    origin_name: &str,
+) -> Result<Option<(Zone, Vec<PathBuf>)>> {
+    let origin = Name::from_str(origin_name).context("Invalid origin name")?;
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            tracing::info!("No snapshot {} yet", path.display());
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(e).context(format!("Failed to read snapshot {}", path.display()));
+        }
+    };
+
+    let Some(snapshot) = decode(&data, &origin)? else {
+        tracing::info!(
+            "Snapshot {} was compiled by another version",
+            path.display()
+        );
+        return Ok(None);
+    };
+
+    let zone_file = zone_file.canonicalize().ok();
+    if snapshot.sources.first().map(|s| &s.path) != zone_file.as_ref() {
+        tracing::info!("Snapshot {} was compiled from another file", path.display());
+        return Ok(None);
+    }
+    for source in &snapshot.sources {
+        if !source.is_current() {
+            tracing::info!(
+                "Snapshot {} is stale: {} has changed",
+                path.display(),
+                source.path.display()
+            );
+            return Ok(None);
+        }
+    }
+
+    let sources = snapshot.sources.into_iter().map(|s| s.path).collect();
+    Ok(Some((snapshot.zone, sources)))
+}
+
+/// Loads a configured zone, from its snapshot while that is up to date.
+/// Otherwise the zone file is parsed and the snapshot rewritten, so only
+/// the first load after an edit pays for parsing. The snapshot is not
+/// rewritten while the files may still be changing, since it would record
+/// their new stamps with the old records. Also returns the files the zone
+/// was read from.
+pub fn load_zone(config: &ZoneConfig) -> Result<(Zone, Vec<PathBuf>)> {
+    let Some(snapshot) = &config.snapshot else {
+        return zone::load_zone_file_with_sources(&config.file, &config.name, config.format);
+    };
+
+    match load(snapshot, &config.file, &config.name) {
+        Ok(Some(loaded)) => {
+            tracing::info!("Zone {} loaded from snapshot", config.name);
+            return Ok(loaded);
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Ignoring snapshot {}: {:#}", snapshot.display(), e),
+    }
+
+    let read_at = SystemTime::now();
+    let (zone, sources) =
+        zone::load_zone_file_with_sources(&config.file, &config.name, config.format)?;
+    match settled_stamps(&sources, read_at).and_then(|stamps| match stamps {
+        Some(stamps) => write(snapshot, &zone, &stamps).map(|_| true),
+        None => Ok(false),
+    }) {
+        Ok(true) => {}
+        Ok(false) => tracing::info!(
+            "Not updating snapshot {}: zone files were modified while loading",
+            snapshot.display()
+        ),
+        Err(e) => tracing::warn!("Failed to update snapshot: {:#}", e),
+    }
+    Ok((zone, sources))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ZoneFormat;
+    use tempfile::TempDir;
+
+    const ZONE: &str = "$ORIGIN example.com.\n\
+        $TTL 3600\n\
+        @ IN SOA ns1 admin 2024010101 7200 3600 1209600 3600\n\
+        @ IN NS ns1\n\
+        ns1 IN A 192.0.2.1\n\
+        @ IN ALIAS cdn.example.net.\n\
+        @ IN CAA 128 tbs \"Unknown; \\\"quoted\\\"\"\n\
+        @ IN HTTPS 1 . alpn=h3,h2 port=8443 ipv4hint=192.0.2.1 ech=AAMBAgM=\n\
+        @ IN RRSIG A 8 2 3600 1767139200 1764547200 12345 example.com. AwEAAaetidLzsKWU\n\
+        @ IN NSEC www.example.com. A NS SOA RRSIG NSEC TYPE65534\n\
+        host IN LOC 42 21 54 N 71 06 18 W -24m 30m\n\
+        opaque IN TYPE65534 \\# 4 0A000001\n\
+        empty IN TYPE65533 \\# 0\n\
+        Mixed.Case IN CNAME Host.Example.net.\n\
+        $INCLUDE hosts.inc\n";
+
+    /// Writes the zone files, dated `age` seconds ago
+    fn write_zone_files(dir: &TempDir, age: u64) -> PathBuf {
+        let hosts = dir.path().join("hosts.inc");
+        std::fs::write(&hosts, "www.deep IN AAAA 2001:db8::1\n").unwrap();
+        let file = dir.path().join("example.com.zone");
+        std::fs::write(&file, ZONE).unwrap();
+        for path in [&hosts, &file] {
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(age))
+                .unwrap();
+        }
+        file
+    }
+
+    fn stamps(sources: &[PathBuf]) -> Vec<SourceStamp> {
+        sources
+            .iter()
+            .map(|path| SourceStamp::of(path).unwrap())
+            .collect()
+    }
+
+    fn config(file: &Path, snapshot: &Path) -> ZoneConfig {
+        ZoneConfig {
+            name: "example.com.".to_string(),
+            file: file.to_path_buf(),
+            format: ZoneFormat::Master,
+            snapshot: Some(snapshot.to_path_buf()),
+            serial_policy: None,
+            catalog: false,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let file = write_zone_files(&dir, 100);
+        let snapshot = dir.path().join("example.com.snap");
+
+        let (zone, sources) = zone::parse_zone_file_with_sources(&file, "example.com.").unwrap();
+        write(&snapshot, &zone, &stamps(&sources)).unwrap();
+        let (loaded, _) = load(&snapshot, &file, "example.com.").unwrap().unwrap();
+
+        assert_eq!(
+            zone::write_zone(&loaded).unwrap(),
+            zone::write_zone(&zone).unwrap()
+        );
+        assert_eq!(loaded.soa.serial, 2024010101);
+        assert!(loaded.is_empty_non_terminal(&Name::from_str("deep.example.com.").unwrap()));
+
+        // Name case is kept as parsed
+        let owner = Name::from_str("mixed.case.example.com.").unwrap();
+        let cname = |zone: &Zone| zone.lookup(&owner, RecordType::CNAME).unwrap()[0].to_string();
+        assert_eq!(cname(&loaded), cname(&zone));
+    }
+
+    #[test]
+    fn test_stale_snapshot_is_not_used() {
+        let dir = TempDir::new().unwrap();
+        let file = write_zone_files(&dir, 100);
+        let snapshot = dir.path().join("example.com.snap");
+        let (zone, sources) = zone::parse_zone_file_with_sources(&file, "example.com.").unwrap();
+        write(&snapshot, &zone, &stamps(&sources)).unwrap();
+
+        // Compiled from another file
+        let other = dir.path().join("other.zone");
+        std::fs::copy(&file, &other).unwrap();
+        assert!(load(&snapshot, &other, "example.com.").unwrap().is_none());
+
+        // An included file changed
+        std::fs::write(
+            dir.path().join("hosts.inc"),
+            "www.deep IN AAAA 2001:db8::2\nmail IN A 192.0.2.9\n",
+        )
+        .unwrap();
+        assert!(load(&snapshot, &file, "example.com.").unwrap().is_none());
+
+        // Missing
+        let missing = dir.path().join("missing.snap");
+        assert!(load(&missing, &file, "example.com.").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_corrupt_snapshot_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let file = write_zone_files(&dir, 100);
+        let (zone, sources) = zone::parse_zone_file_with_sources(&file, "example.com.").unwrap();
+        let data = encode(&zone, &stamps(&sources)).unwrap();
+        let origin = Name::from_str("example.com.").unwrap();
+
+        let mut flipped = data.clone();
+        flipped[40] ^= 0x01;
+        let err = decode(&flipped, &origin).err().unwrap();
+        assert_eq!(err.to_string(), "Snapshot checksum mismatch");
+
+        let err = decode(b"$ORIGIN example.com.\n", &origin).err().unwrap();
+        assert_eq!(err.to_string(), "Not a zone snapshot");
+
+        let err = decode(&data, &Name::from_str("example.org.").unwrap())
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Snapshot is of zone example.com., not example.org."
+        );
+
+        // Another format version is stale rather than corrupt
+        let mut body = data[..data.len() - CHECKSUM_LEN].to_vec();
+        body[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(FORMAT_VERSION + 1).to_be_bytes());
+        let checksum = Sha256::digest(&body);
+        body.extend_from_slice(&checksum);
+        assert!(decode(&body, &origin).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_load_zone_falls_back_and_recompiles() {
+        let dir = TempDir::new().unwrap();
+        let file = write_zone_files(&dir, 100);
+        let snapshot = dir.path().join("example.com.snap");
+        let config = config(&file, &snapshot);
+
+        let (parsed, _) = load_zone(&config).unwrap();
+        assert!(load(&snapshot, &file, "example.com.").unwrap().is_some());
+
+        std::fs::write(&snapshot, b"garbage").unwrap();
+        let (reparsed, _) = load_zone(&config).unwrap();
+        assert_eq!(
+            zone::write_zone(&reparsed).unwrap(),
+            zone::write_zone(&parsed).unwrap()
+        );
+        let (loaded, _) = load(&snapshot, &file, "example.com.").unwrap().unwrap();
+        assert_eq!(
+            zone::write_zone(&loaded).unwrap(),
+            zone::write_zone(&parsed).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_snapshot_not_written_while_files_change() {
+        let dir = TempDir::new().unwrap();
+        let file = write_zone_files(&dir, 0);
+        let snapshot = dir.path().join("example.com.snap");
+
+        load_zone(&config(&file, &snapshot)).unwrap();
+        assert!(!snapshot.exists());
+
+        let (_, sources) = zone::parse_zone_file_with_sources(&file, "example.com.").unwrap();
+        assert!(
+            settled_stamps(&sources, SystemTime::now())
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,486 @@
+use crate::backend::ZoneBackend;
+use crate::config::{SerialPolicy, ValidationMode};
+use crate::lint;
+use crate::serial;
+use crate::zone::{self, RrsetDocument, SoaDocument, Zone, ZoneDocument, ZoneStore};
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use hickory_proto::rr::Name;
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Tables follow the structured zone document: one row per zone holding the
+/// SOA fields and one row per record with presentation-format RDATA. Every
+/// write bumps a global change counter and stamps the affected zone with it,
+/// so a poll can tell which zones changed without reading their records.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS zones (
+    name TEXT PRIMARY KEY,
+    mname TEXT NOT NULL,
+    rname TEXT NOT NULL,
+    serial INTEGER NOT NULL,
+    refresh INTEGER NOT NULL,
+    retry INTEGER NOT NULL,
+    expire INTEGER NOT NULL,
+    minimum INTEGER NOT NULL,
+    soa_ttl INTEGER,
+    version INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE IF NOT EXISTS records (
+    id INTEGER PRIMARY KEY,
+    zone TEXT NOT NULL REFERENCES zones(name) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    type TEXT NOT NULL,
+    ttl INTEGER NOT NULL,
+    rdata TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS records_zone ON records(zone);
+CREATE TABLE IF NOT EXISTS changes (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    counter INTEGER NOT NULL
+);
+INSERT OR IGNORE INTO changes VALUES (1, 0);
+CREATE TRIGGER IF NOT EXISTS zones_insert AFTER INSERT ON zones BEGIN
+    UPDATE changes SET counter = counter + 1;
+    UPDATE zones SET version = (SELECT counter FROM changes) WHERE name = NEW.name;
+END;
+CREATE TRIGGER IF NOT EXISTS zones_update
+AFTER UPDATE OF mname, rname, serial, refresh, retry, expire, minimum, soa_ttl ON zones BEGIN
+    UPDATE changes SET counter = counter + 1;
+    UPDATE zones SET version = (SELECT counter FROM changes) WHERE name = NEW.name;
+END;
+CREATE TRIGGER IF NOT EXISTS zones_delete AFTER DELETE ON zones BEGIN
+    UPDATE changes SET counter = counter + 1;
+END;
+CREATE TRIGGER IF NOT EXISTS records_insert AFTER INSERT ON records BEGIN
+    UPDATE changes SET counter = counter + 1;
+    UPDATE zones SET version = (SELECT counter FROM changes) WHERE name = NEW.zone;
+END;
+CREATE TRIGGER IF NOT EXISTS records_update AFTER UPDATE ON records BEGIN
+    UPDATE changes SET counter = counter + 1;
+    UPDATE zones SET version = (SELECT counter FROM changes) WHERE name IN (OLD.zone, NEW.zone);
+END;
+CREATE TRIGGER IF NOT EXISTS records_delete AFTER DELETE ON records BEGIN
+    UPDATE changes SET counter = counter + 1;
+    UPDATE zones SET version = (SELECT counter FROM changes) WHERE name = OLD.zone;
+END;
+";
+
+/// Zones served from an embedded SQLite database that other processes write
+/// to. `refresh` rebuilds only the zones whose rows changed since the last
+/// call; lookups are answered from the rebuilt in-memory zones.
+pub struct SqliteBackend {
+    conn: Mutex<Connection>,
+    store: ArcSwap<ZoneStore>,
+    state: Mutex<RefreshState>,
+}
+
+struct RefreshState {
+    validation: ValidationMode,
+    serial_policy: SerialPolicy,
+    /// Change counter seen by the last refresh
+    counter: Option<i64>,
+    /// Version of each zone as of the last refresh, including zones that
+    /// failed to load so they are not retried until they change again
+    versions: HashMap<String, i64>,
+}
+
+impl SqliteBackend {
+    /// Opens (creating if needed) the database at `path` and loads every zone
+    pub fn open(
+        path: &Path,
+        validation: ValidationMode,
+        serial_policy: SerialPolicy,
+    ) -> Result<Self> {
+        let conn = open_database(path)?;
+        let backend = SqliteBackend {
+            conn: Mutex::new(conn),
+            store: ArcSwap::from_pointee(ZoneStore::new()),
+            state: Mutex::new(RefreshState {
+                validation,
+                serial_policy,
+                counter: None,
+                versions: HashMap::new(),
+            }),
+        };
+        backend.refresh()?;
+        Ok(backend)
+    }
+
+    /// Applies to zones loaded from now on
+    pub fn reconfigure(&self, validation: ValidationMode, serial_policy: SerialPolicy) {
+        let mut state = self.state.lock().unwrap();
+        state.validation = validation;
+        state.serial_policy = serial_policy;
+    }
+
+    /// Picks up changes made to the database since the last refresh. A zone
+    /// that fails to load keeps serving its previous contents. Returns the
+    /// origins of zones that were loaded or removed.
+    pub fn refresh(&self) -> Result<Vec<String>> {
+        let mut conn = self.conn.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        // One read transaction, so every zone is read from the same snapshot
+        let tx = conn.transaction()?;
+
+        let counter: i64 = tx.query_row("SELECT counter FROM changes", [], |row| row.get(0))?;
+        if state.counter == Some(counter) {
+            return Ok(Vec::new());
+        }
+
+        let current: Vec<(String, i64)> = tx
+            .prepare("SELECT name, version FROM zones")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        // Changes are made to a copy of the zone map and published together
+        let mut store = ZoneStore::clone(&self.store.load());
+        let mut changed = Vec::new();
+        for (name, version) in &current {
+            if state.versions.get(name) == Some(version) {
+                continue;
+            }
+            state.versions.insert(name.clone(), *version);
+            let previous = Name::from_str(name).ok().and_then(|origin| {
+                store
+                    .find_zone(&origin)
+                    .filter(|zone| zone.origin == origin)
+            });
+            let loaded = load_zone(&tx, name, state.validation).and_then(|mut zone| {
+                let now = SystemTime::now();
+                serial::apply(
+                    state.serial_policy,
+                    state.validation,
+                    previous.map(AsRef::as_ref),
+                    &mut zone,
+                    now,
+                )?;
+                Ok(zone)
+            });
+            match loaded {
+                Ok(zone) => {
+                    tracing::info!("Zone {} loaded from database (version {})", name, version);
+                    store.add_zone(zone);
+                    changed.push(name.clone());
+                }
+                Err(e) => {
+                    tracing::error!("Failed to load zone {} from database: {:#}", name, e);
+                }
+            }
+        }
+
+        let present: HashSet<&String> = current.iter().map(|(name, _)| name).collect();
+        let removed: Vec<String> = state
+            .versions
+            .keys()
+            .filter(|name| !present.contains(name))
+            .cloned()
+            .collect();
+        for name in removed {
+            state.versions.remove(&name);
+            if let Ok(origin) = Name::from_str(&name) {
+                store.remove_zone(&origin);
+            }
+            tracing::info!("Zone {} removed from database", name);
+            changed.push(name);
+        }
+
+        if !changed.is_empty() {
+            self.store.store(Arc::new(store));
+        }
+        state.counter = Some(counter);
+        Ok(changed)
+    }
+}
+
+impl ZoneBackend for SqliteBackend {
+    fn snapshot(&self) -> Arc<ZoneStore> {
+        self.store.load_full()
+    }
+}
+
+fn load_zone(tx: &Transaction, name: &str, validation: ValidationMode) -> Result<Zone> {
+    let doc = read_document(tx, name)?;
+    let zone = zone::zone_from_document(&doc, name)?;
+
+    let violations = lint::lint_zone(&zone);
+    if !violations.is_empty() && validation == ValidationMode::Fatal {
+        let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        anyhow::bail!("Zone failed validation:\n{}", violations.join("\n"));
+    }
+    for violation in &violations {
+        tracing::warn!("Zone {}: {}", name, violation);
+    }
+    Ok(zone)
+}
+
+fn open_database(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)
+        .context(format!("Failed to open zone database {}", path.display()))?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    conn.execute_batch(SCHEMA)
+        .context("Failed to create zone database schema")?;
+    Ok(conn)
+}
+
+fn read_document(tx: &Transaction, name: &str) -> Result<ZoneDocument> {
+    let soa = tx
+        .query_row(
+            "SELECT mname, rname, serial, refresh, retry, expire, minimum, soa_ttl
+             FROM zones WHERE name = ?1",
+            [name],
+            |row| {
+                Ok(SoaDocument {
+                    mname: row.get(0)?,
+                    rname: row.get(1)?,
+                    serial: row.get(2)?,
+                    refresh: row.get(3)?,
+                    retry: row.get(4)?,
+                    expire: row.get(5)?,
+                    minimum: row.get(6)?,
+                    ttl: row.get(7)?,
+                })
+            },
+        )
+        .optional()?
+        .ok_or_else(|| anyhow::anyhow!("Zone {} not found", name))?;
+
+    let rrsets = tx
+        .prepare("SELECT name, type, ttl, rdata FROM records WHERE zone = ?1 ORDER BY id")?
+        .query_map([name], |row| {
+            Ok(RrsetDocument {
+                name: row.get(0)?,
+                rtype: row.get(1)?,
+                ttl: row.get(2)?,
+                rdata: vec![row.get(3)?],
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(ZoneDocument {
+        origin: name.to_string(),
+        soa,
+        rrsets,
+    })
+}
+
+/// Writes a zone into the database at `path`, replacing any zone with the
+/// same origin in a single transaction
+pub fn import_zone(path: &Path, doc: &ZoneDocument) -> Result<()> {
+    let mut conn = open_database(path)?;
+    let tx = conn.transaction()?;
+    let soa = &doc.soa;
+
+    tx.execute(
+        "INSERT INTO zones (name, mname, rname, serial, refresh, retry, expire, minimum, soa_ttl)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT (name) DO UPDATE SET
+             mname = excluded.mname, rname = excluded.rname, serial = excluded.serial,
+             refresh = excluded.refresh, retry = excluded.retry, expire = excluded.expire,
+             minimum = excluded.minimum, soa_ttl = excluded.soa_ttl",
+        params![
+            doc.origin,
+            soa.mname,
+            soa.rname,
+            soa.serial,
+            soa.refresh,
+            soa.retry,
+            soa.expire,
+            soa.minimum,
+            soa.ttl
+        ],
+    )?;
+    tx.execute("DELETE FROM records WHERE zone = ?1", [&doc.origin])?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO records (zone, name, type, ttl, rdata) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for rrset in &doc.rrsets {
+            for rdata in &rrset.rdata {
+                insert.execute(params![
+                    doc.origin,
+                    rrset.name,
+                    rrset.rtype,
+                    rrset.ttl,
+                    rdata
+                ])?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::rr::RecordType;
+
+    fn doc(origin: &str, serial: u32, rrsets: Vec<RrsetDocument>) -> ZoneDocument {
+        ZoneDocument {
+            origin: origin.to_string(),
+            soa: SoaDocument {
+                ttl: Some(3600),
+                mname: "ns1".to_string(),
+                rname: "admin".to_string(),
+                serial,
+                refresh: 7200,
+                retry: 3600,
+                expire: 1209600,
+                minimum: 300,
+            },
+            rrsets,
+        }
+    }
+
+    fn rrset(name: &str, rtype: &str, rdata: &[&str]) -> RrsetDocument {
+        RrsetDocument {
+            name: name.to_string(),
+            rtype: rtype.to_string(),
+            ttl: 300,
+            rdata: rdata.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_serves_zones_from_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zones.db");
+        import_zone(
+            &path,
+            &doc(
+                "example.com.",
+                1,
+                vec![
+                    rrset("@", "NS", &["ns1"]),
+                    rrset("ns1", "A", &["192.0.2.53"]),
+                    rrset("www", "A", &["192.0.2.1", "192.0.2.2"]),
+                ],
+            ),
+        )
+        .unwrap();
+
+        let backend = SqliteBackend::open(&path, ValidationMode::Warn, SerialPolicy::Keep).unwrap();
+        let zone = backend.find_zone(&name("www.example.com.")).unwrap();
+        assert_eq!(zone.soa.serial, 1);
+        assert_eq!(
+            zone.lookup(&name("www.example.com."), RecordType::A)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(backend.find_zone(&name("example.net.")).is_none());
+    }
+
+    #[test]
+    fn test_refresh_picks_up_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zones.db");
+        import_zone(
+            &path,
+            &doc(
+                "example.com.",
+                1,
+                vec![rrset("@", "NS", &["ns.example.net."])],
+            ),
+        )
+        .unwrap();
+        import_zone(
+            &path,
+            &doc(
+                "example.org.",
+                1,
+                vec![rrset("@", "NS", &["ns.example.net."])],
+            ),
+        )
+        .unwrap();
+        let backend = SqliteBackend::open(&path, ValidationMode::Warn, SerialPolicy::Keep).unwrap();
+        assert!(backend.refresh().unwrap().is_empty());
+
+        // A control plane writing rows directly
+        let writer = Connection::open(&path).unwrap();
+        writer
+            .execute(
+                "INSERT INTO records (zone, name, type, ttl, rdata)
+                 VALUES ('example.com.', 'www', 'A', 300, '192.0.2.1')",
+                [],
+            )
+            .unwrap();
+        assert_eq!(backend.refresh().unwrap(), vec!["example.com."]);
+        assert!(
+            backend
+                .find_zone(&name("example.com."))
+                .unwrap()
+                .lookup(&name("www.example.com."), RecordType::A)
+                .is_some()
+        );
+
+        writer
+            .execute(
+                "UPDATE zones SET serial = 2 WHERE name = 'example.org.'",
+                [],
+            )
+            .unwrap();
+        assert_eq!(backend.refresh().unwrap(), vec!["example.org."]);
+        assert_eq!(
+            backend.find_zone(&name("example.org.")).unwrap().soa.serial,
+            2
+        );
+
+        writer
+            .execute("DELETE FROM zones WHERE name = 'example.org.'", [])
+            .unwrap();
+        assert_eq!(backend.refresh().unwrap(), vec!["example.org."]);
+        assert!(backend.find_zone(&name("example.org.")).is_none());
+        assert!(backend.find_zone(&name("example.com.")).is_some());
+    }
+
+    #[test]
+    fn test_invalid_change_keeps_previous_zone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zones.db");
+        import_zone(
+            &path,
+            &doc("example.com.", 1, vec![rrset("www", "A", &["192.0.2.1"])]),
+        )
+        .unwrap();
+        let backend = SqliteBackend::open(&path, ValidationMode::Warn, SerialPolicy::Keep).unwrap();
+
+        let writer = Connection::open(&path).unwrap();
+        writer
+            .execute(
+                "INSERT INTO records (zone, name, type, ttl, rdata)
+                 VALUES ('example.com.', 'bad', 'A', 300, '192.0.2.300')",
+                [],
+            )
+            .unwrap();
+        assert!(backend.refresh().unwrap().is_empty());
+        let zone = backend.find_zone(&name("example.com.")).unwrap();
+        assert!(
+            zone.lookup(&name("www.example.com."), RecordType::A)
+                .is_some()
+        );
+        assert!(
+            zone.lookup(&name("bad.example.com."), RecordType::A)
+                .is_none()
+        );
+
+        // Fixing the row loads the zone again
+        writer
+            .execute(
+                "UPDATE records SET rdata = '192.0.2.3' WHERE name = 'bad'",
+                [],
+            )
+            .unwrap();
+        assert_eq!(backend.refresh().unwrap(), vec!["example.com."]);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::config::WatchConfig;
+use crate::files::{self, FileBackend, ReloadResult};
+use crate::metrics::Metrics;
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, mpsc};
+use tokio::task::JoinHandle;
+
+/// Watches the files zones were loaded from, including `$INCLUDE`d files,
+/// and reloads a zone once writes to its files have stopped for the
+/// debounce interval. Directories are watched rather than files, so files
+/// replaced by a rename (as rsync does) are still noticed. Zone files
+/// added to or removed from a zone directory are picked up the same way.
+/// Watching stops when the returned handle is dropped.
+pub fn spawn(
+    backend: Arc<FileBackend>,
+    config: &WatchConfig,
+    metrics: Arc<Metrics>,
+) -> Result<ZoneWatch> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })
+    .context("Failed to start zone file watcher")?;
+
+    let mut zone_watcher = ZoneWatcher {
+        backend,
+        watcher,
+        files: HashMap::new(),
+        zone_dirs: Vec::new(),
+        dirs: HashSet::new(),
+    };
+    let sources = Sources::read(&zone_watcher.backend);
+    zone_watcher.update(sources)?;
+
+    let debounce = Duration::from_millis(config.debounce_ms);
+    let refresh = Arc::new(Notify::new());
+    let task = tokio::spawn(zone_watcher.run(rx, refresh.clone(), debounce, metrics));
+    Ok(ZoneWatch { task, refresh })
+}
+
+/// Handle to a running zone file watcher
+pub struct ZoneWatch {
+    task: JoinHandle<()>,
+    refresh: Arc<Notify>,
+}
+
+impl ZoneWatch {
+    /// Picks up zones added to or removed from the backend
+    pub fn refresh(&self) {
+        self.refresh.notify_one();
+    }
+}
+
+impl Drop for ZoneWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct ZoneWatcher {
+    backend: Arc<FileBackend>,
+    watcher: notify::RecommendedWatcher,
+    /// Zones to reload when a file changes, by the file's watched path
+    files: HashMap<PathBuf, Vec<String>>,
+    /// Zone directories, resolved to absolute paths, with their patterns
+    zone_dirs: Vec<(PathBuf, String)>,
+    /// Directories being watched
+    dirs: HashSet<PathBuf>,
+}
+
+/// The files and zone directories the backend's zones come from
+struct Sources {
+    /// Zones by the watched path of each of their files
+    files: HashMap<PathBuf, Vec<String>>,
+    /// Zone directories, resolved to absolute paths, with their patterns
+    zone_dirs: Vec<(PathBuf, String)>,
+}
+
+impl Sources {
+    /// Reads the current sources. This waits for any reload in progress, so
+    /// the watcher task calls it through `spawn_blocking`.
+    fn read(backend: &FileBackend) -> Sources {
+        let mut files: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        for (zone, paths) in backend.zone_files() {
+            for path in paths {
+                let zones = files.entry(watched_path(&path)).or_default();
+                if !zones.contains(&zone) {
+                    zones.push(zone.clone());
+                }
+            }
+        }
+
+        let zone_dirs = backend
+            .zone_dirs()
+            .into_iter()
+            .map(|dir| {
+                let path = dir.path.canonicalize().unwrap_or(dir.path);
+                (path, dir.pattern)
+            })
+            .collect();
+
+        Sources { files, zone_dirs }
+    }
+}
+
+/// Changes seen in one burst of file system events
+#[derive(Default)]
+struct Pending {
+    /// Zones whose files changed
+    zones: HashSet<String>,
+    /// Whether zone files were added to or removed from a zone directory
+    rescan: bool,
+}
+
+impl ZoneWatcher {
+    async fn run(
+        mut self,
+        mut events: mpsc::UnboundedReceiver<notify::Result<notify::Event>>,
+        refresh: Arc<Notify>,
+        debounce: Duration,
+        metrics: Arc<Metrics>,
+    ) {
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => match event {
+                    Some(event) => event,
+                    None => return,
+                },
+                _ = refresh.notified() => {
+                    let backend = self.backend.clone();
+                    match tokio::task::spawn_blocking(move || Sources::read(&backend)).await {
+                        Ok(sources) => {
+                            if let Err(e) = self.update(sources) {
+                                tracing::error!("Failed to update watched zone files: {:#}", e);
+                            }
+                        }
+                        Err(e) => tracing::error!("Zone file listing task failed: {}", e),
+                    }
+                    continue;
+                }
+            };
+            let mut pending = Pending::default();
+            self.collect(event, &mut pending);
+            if pending.zones.is_empty() && !pending.rescan {
+                continue;
+            }
+
+            // Wait for the burst of writes to end
+            loop {
+                match tokio::time::timeout(debounce, events.recv()).await {
+                    Ok(Some(event)) => self.collect(event, &mut pending),
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+
+            let names: Vec<String> = pending.zones.into_iter().collect();
+            if !names.is_empty() {
+                tracing::info!("Zone files changed, reloading {}", names.join(", "));
+            }
+            let rescan = pending.rescan;
+            if rescan {
+                tracing::info!("Zone directory changed, looking for added and removed zones");
+            }
+            let backend = self.backend.clone();
+            let reload = move || {
+                let mut statuses = match rescan {
+                    true => backend.rediscover(),
+                    false => Vec::new(),
+                };
+                statuses.extend(backend.reload_zones(&names));
+                // A reload can add or drop zones and `$INCLUDE`d files
+                (statuses, Sources::read(&backend))
+            };
+            let (statuses, sources) = match tokio::task::spawn_blocking(reload).await {
+                Ok(reloaded) => reloaded,
+                Err(e) => {
+                    tracing::error!("Zone reload task failed: {}", e);
+                    continue;
+                }
+            };
+            for status in &statuses {
+                match status.result {
+                    ReloadResult::Loaded => metrics.record_auto_reload(true),
+                    ReloadResult::Failed => metrics.record_auto_reload(false),
+                    ReloadResult::Unchanged | ReloadResult::RolledBack => {}
+                }
+            }
+
+            if let Err(e) = self.update(sources) {
+                tracing::error!("Failed to update watched zone files: {:#}", e);
+            }
+        }
+    }
+
+    /// Adds the changes `event` describes to `pending`
+    fn collect(&self, event: notify::Result<notify::Event>, pending: &mut Pending) {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!("Zone file watcher error: {}", e);
+                return;
+            }
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        for path in &event.paths {
+            if let Some(zones) = self.files.get(path) {
+                pending.zones.extend(zones.iter().cloned());
+            }
+            if let (Some(dir), Some(name)) = (path.parent(), path.file_name())
+                && self.zone_dirs.iter().any(|(zone_dir, pattern)| {
+                    zone_dir == dir
+                        && name
+                            .to_str()
+                            .is_some_and(|name| files::matches_pattern(pattern, name))
+                })
+            {
+                pending.rescan = true;
+            }
+        }
+    }
+
+    /// Watches the zone directories and the directories of every zone's
+    /// current files
+    fn update(&mut self, sources: Sources) -> Result<()> {
+        self.files = sources.files;
+        self.zone_dirs = sources.zone_dirs;
+
+        let dirs: HashSet<PathBuf> = self
+            .files
+            .keys()
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .chain(self.zone_dirs.iter().map(|(dir, _)| dir.clone()))
+            .collect();
+        for dir in self.dirs.difference(&dirs) {
+            let _ = self.watcher.unwatch(dir);
+        }
+        for dir in dirs.difference(&self.dirs) {
+            self.watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .context(format!("Failed to watch {}", dir.display()))?;
+            tracing::debug!("Watching {} for zone file changes", dir.display());
+        }
+        self.dirs = dirs;
+        Ok(())
+    }
+}
+
+/// The path the watcher reports for `path`: its directory resolved to an
+/// absolute path, with the file name kept as is, since the file itself may
+/// be replaced at any moment
+fn watched_path(path: &Path) -> PathBuf {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    match (dir.canonicalize(), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ZoneBackend;
+    use crate::config::Config;
+    use hickory_proto::rr::{Name, RecordType};
+    use std::str::FromStr;
+    use std::sync::atomic::Ordering;
+    use tempfile::TempDir;
+
+    fn host_count(backend: &FileBackend) -> usize {
+        let origin = Name::from_str("example.com.").unwrap();
+        let zone = backend.find_zone(&origin).unwrap();
+        let host = Name::from_str("www.example.com.").unwrap();
+        zone.lookup(&host, RecordType::A).map_or(0, Vec::len)
+    }
+
+    /// Polls until `done` holds, failing the test after a few seconds
+    async fn wait_for(mut done: impl FnMut() -> bool) {
+        for _ in 0..100 {
+            if done() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("Timed out waiting for zone reload");
+    }
+
+    #[tokio::test]
+    async fn test_reload_on_included_file_change() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("example.com.zone"),
+            "$ORIGIN example.com.\n$TTL 3600\n\
+             @ IN SOA ns1 admin 1 7200 3600 1209600 3600\n\
+             @ IN NS ns1\nns1 IN A 192.0.2.1\n\
+             $INCLUDE hosts.zone\n",
+        )
+        .unwrap();
+        let hosts = dir.path().join("hosts.zone");
+        std::fs::write(&hosts, "www IN A 192.0.2.10\n").unwrap();
+
+        let config: Config = serde_yaml::from_str(&format!(
+            "server: {{}}\nzones:\n\
+             - name: example.com.\n  file: {}/example.com.zone\n",
+            dir.path().display()
+        ))
+        .unwrap();
+        let backend = Arc::new(FileBackend::open(&config).unwrap());
+        let metrics = Arc::new(Metrics::new());
+        let _watch = spawn(
+            backend.clone(),
+            &WatchConfig { debounce_ms: 50 },
+            metrics.clone(),
+        )
+        .unwrap();
+        assert_eq!(host_count(&backend), 1);
+
+        // Edited in place
+        std::fs::write(&hosts, "www IN A 192.0.2.10\nwww IN A 192.0.2.11\n").unwrap();
+        wait_for(|| host_count(&backend) == 2).await;
+        assert_eq!(metrics.auto_reloads.load(Ordering::Relaxed), 1);
+
+        // Replaced by a rename, as rsync does
+        let staged = dir.path().join(".hosts.zone.tmp");
+        std::fs::write(
+            &staged,
+            "www IN A 192.0.2.10\nwww IN A 192.0.2.11\nwww IN A 192.0.2.12\n",
+        )
+        .unwrap();
+        std::fs::rename(&staged, &hosts).unwrap();
+        wait_for(|| host_count(&backend) == 3).await;
+        assert_eq!(metrics.auto_reloads.load(Ordering::Relaxed), 2);
+
+        // A broken edit is counted and the old version keeps being served
+        std::fs::write(&hosts, "www IN A not-an-address\n").unwrap();
+        wait_for(|| metrics.auto_reload_failures.load(Ordering::Relaxed) == 1).await;
+        assert_eq!(host_count(&backend), 3);
+    }
+
+    #[tokio::test]
+    async fn test_zone_dir_changes() {
+        let dir = TempDir::new().unwrap();
+        let write = |origin: &str| {
+            std::fs::write(
+                dir.path().join(format!("{}zone", origin)),
+                format!(
+                    "$ORIGIN {}\n$TTL 3600\n\
+                     @ IN SOA ns1 admin 1 7200 3600 1209600 3600\n\
+                     @ IN NS ns1\nns1 IN A 192.0.2.1\n",
+                    origin
+                ),
+            )
+            .unwrap();
+        };
+        write("example.com.");
+
+        let config: Config = serde_yaml::from_str(&format!(
+            "server: {{}}\nzone_dirs:\n- path: {}\n",
+            dir.path().display()
+        ))
+        .unwrap();
+        let backend = Arc::new(FileBackend::open(&config).unwrap());
+        let _watch = spawn(
+            backend.clone(),
+            &WatchConfig { debounce_ms: 50 },
+            Arc::new(Metrics::new()),
+        )
+        .unwrap();
+        let served = |origin: &str| {
+            backend
+                .find_zone(&Name::from_str(origin).unwrap())
+                .is_some()
+        };
+
+        write("example.org.");
+        wait_for(|| served("example.org.")).await;
+        std::fs::remove_file(dir.path().join("example.com.zone")).unwrap();
+        wait_for(|| !served("example.com.")).await;
+    }
+}
@@ -1,18 +1,26 @@
+use crate::config::ZoneFormat;
 use anyhow::{Context, Result};
+use hickory_proto::rr::rdata::svcb::{self, SvcParamKey, SvcParamValue};
 use hickory_proto::rr::{Name, RData, Record, RecordType};
-use std::collections::HashMap;
+use hickory_proto::serialize::binary::{BinDecoder, BinEncodable, Restrict};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::net::{Ipv4Addr, Ipv6Addr};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct Zone {
     pub origin: Name,
     pub soa: SoaRecord,
-    pub records: HashMap<Name, HashMap<RecordType, Vec<Record>>>,
+    records: HashMap<Name, HashMap<RecordType, Vec<Record>>>,
+    /// Names below the origin that own no records but have descendants that
+    /// do, maintained by `add_record`
+    empty_non_terminals: HashSet<Name>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SoaRecord {
     pub mname: Name,
     pub rname: Name,
@@ -29,6 +37,7 @@ impl Zone {
             origin,
             soa,
             records: HashMap::new(),
+            empty_non_terminals: HashSet::new(),
         }
     }
 
@@ -36,6 +45,17 @@ impl Zone {
         let name = record.name().clone();
         let rtype = record.record_type();
 
+        // A name that owns records is no longer an empty non-terminal; its
+        // ancestors up to the origin become ones unless they own records
+        self.empty_non_terminals.remove(&name);
+        let mut ancestor = name.base_name();
+        while ancestor != self.origin && self.origin.zone_of(&ancestor) {
+            if !self.records.contains_key(&ancestor) {
+                self.empty_non_terminals.insert(ancestor.clone());
+            }
+            ancestor = ancestor.base_name();
+        }
+
         self.records
             .entry(name)
             .or_default()
@@ -44,36 +64,115 @@ impl Zone {
             .push(record);
     }
 
+    /// Drops the records whose owner is outside the origin. Master files may
+    /// contain them; `lint_zone` reports them before they are dropped.
+    pub fn remove_out_of_zone(&mut self) {
+        let origin = &self.origin;
+        self.records.retain(|name, _| origin.zone_of(name));
+    }
+
+    /// All RRsets by owner and type. Records are only added through
+    /// `add_record`, which keeps the empty non-terminals in step.
+    pub fn records(&self) -> &HashMap<Name, HashMap<RecordType, Vec<Record>>> {
+        &self.records
+    }
+
+    /// Lookup the RRset of `rtype` at `name`. Name matching is case-insensitive
+    /// (`Name` hashes and compares lowercased labels); the returned records keep
+    /// the owner case from the zone.
     pub fn lookup(&self, name: &Name, rtype: RecordType) -> Option<&Vec<Record>> {
         self.records.get(name)?.get(&rtype)
     }
 
+    /// All RRsets owned by `name`, ordered by record type
+    pub fn lookup_rrsets(&self, name: &Name) -> Vec<&Vec<Record>> {
+        let Some(type_map) = self.records.get(name) else {
+            return Vec::new();
+        };
+
+        let mut types: Vec<_> = type_map.keys().collect();
+        types.sort();
+        types.into_iter().map(|rtype| &type_map[rtype]).collect()
+    }
+
+    /// Check whether `name` owns any records (case-insensitive)
     pub fn contains_name(&self, name: &Name) -> bool {
         self.records.contains_key(name)
     }
 
-    /// Lookup a wildcard record by finding the best matching wildcard
-    /// Returns None if no wildcard matches
-    pub fn lookup_wildcard(&self, name: &Name, rtype: RecordType) -> Option<&Vec<Record>> {
-        // Try to find a wildcard match by constructing potential wildcard names
-        // For "foo.bar.example.com", try "*.bar.example.com", then "*.example.com"
-        let labels = name.iter().collect::<Vec<_>>();
+    /// Check whether `name` is an empty non-terminal: it owns no records but
+    /// has descendants that do (RFC 4592 section 2.2.2)
+    pub fn is_empty_non_terminal(&self, name: &Name) -> bool {
+        self.empty_non_terminals.contains(name)
+    }
 
-        // Start from the second label (skip the leftmost label)
-        for skip in 1..labels.len() {
-            let mut wildcard_labels = vec![b"*".as_ref()];
-            wildcard_labels.extend_from_slice(&labels[skip..]);
+    /// Check whether `name` exists in the zone, either as an owner of records,
+    /// as an empty non-terminal, or as the zone apex
+    pub fn name_exists(&self, name: &Name) -> bool {
+        *name == self.origin || self.contains_name(name) || self.is_empty_non_terminal(name)
+    }
 
-            if let Ok(wildcard_name) = Name::from_labels(wildcard_labels)
-                && let Some(records) = self.lookup(&wildcard_name, rtype)
-            {
-                return Some(records);
+    /// Find the closest encloser of `name` (RFC 4592 section 3.3.1): the
+    /// longest existing ancestor of `name` within the zone
+    pub fn closest_encloser(&self, name: &Name) -> Option<Name> {
+        if !self.origin.zone_of(name) {
+            return None;
+        }
+
+        let mut candidate = name.base_name();
+        while self.origin.zone_of(&candidate) {
+            if self.name_exists(&candidate) {
+                return Some(candidate);
             }
+            candidate = candidate.base_name();
         }
 
         None
     }
 
+    /// Find the source of synthesis for `name`: the wildcard owner directly
+    /// below its closest encloser. Returns None if `name` exists, or if no
+    /// wildcard exists at the closest encloser.
+    pub fn wildcard_source(&self, name: &Name) -> Option<Name> {
+        if self.name_exists(name) {
+            return None;
+        }
+
+        let closest_encloser = self.closest_encloser(name)?;
+        let source = Name::from_ascii("*")
+            .and_then(|wildcard| wildcard.append_name(&closest_encloser))
+            .ok()?;
+
+        self.contains_name(&source).then_some(source)
+    }
+
+    /// Lookup a wildcard match for `name` (RFC 4592) and synthesize the
+    /// answer with `name` as owner. Returns None if no wildcard applies or the
+    /// source of synthesis has no records of `rtype`.
+    pub fn lookup_wildcard(&self, name: &Name, rtype: RecordType) -> Option<Vec<Record>> {
+        let source = self.wildcard_source(name)?;
+        let records = self.lookup(&source, rtype)?;
+
+        Some(synthesize_owner(records, name))
+    }
+
+    /// Replaces the SOA serial, keeping the TTL of the zone's SOA record
+    pub fn set_serial(&mut self, serial: u32) {
+        self.soa.serial = serial;
+        let soa = self.get_soa_record();
+        if let Some(records) = self
+            .records
+            .get_mut(&self.origin)
+            .and_then(|rrsets| rrsets.get_mut(&RecordType::SOA))
+        {
+            for record in records {
+                let ttl = record.ttl();
+                *record = soa.clone();
+                record.set_ttl(ttl);
+            }
+        }
+    }
+
     pub fn get_soa_record(&self) -> Record {
         let rdata = RData::SOA(hickory_proto::rr::rdata::SOA::new(
             self.soa.mname.clone(),
@@ -128,9 +227,23 @@ impl Zone {
     }
 }
 
-#[derive(Debug)]
+/// Copy `records` with their owner name replaced by `owner`, as required when
+/// answering from a wildcard. RRSIGs keep their labels field, which lets
+/// validators reconstruct the wildcard owner that was signed.
+pub fn synthesize_owner(records: &[Record], owner: &Name) -> Vec<Record> {
+    records
+        .iter()
+        .map(|record| {
+            let mut synthesized = record.clone();
+            synthesized.set_name(owner.clone());
+            synthesized
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct ZoneStore {
-    zones: HashMap<Name, Zone>,
+    zones: HashMap<Name, Arc<Zone>>,
 }
 
 impl ZoneStore {
@@ -141,17 +254,30 @@ impl ZoneStore {
     }
 
     pub fn add_zone(&mut self, zone: Zone) {
+        self.insert(Arc::new(zone));
+    }
+
+    /// Adds a zone that is shared with something else, such as a zone
+    /// version history
+    pub fn insert(&mut self, zone: Arc<Zone>) {
         self.zones.insert(zone.origin.clone(), zone);
     }
 
-    pub fn find_zone(&self, name: &Name) -> Option<&Zone> {
+    pub fn remove_zone(&mut self, origin: &Name) -> Option<Arc<Zone>> {
+        self.zones.remove(origin)
+    }
+
+    /// Find the most specific zone containing `name`. Both the exact match and
+    /// the suffix match are case-insensitive, so 0x20-randomized queries resolve
+    /// to the same zone.
+    pub fn find_zone(&self, name: &Name) -> Option<&Arc<Zone>> {
         // Try exact match first
         if let Some(zone) = self.zones.get(name) {
             return Some(zone);
         }
 
         // Find the zone with the longest matching suffix
-        let mut best_match: Option<&Zone> = None;
+        let mut best_match: Option<&Arc<Zone>> = None;
         let mut best_match_labels = 0;
 
         for zone in self.zones.values() {
@@ -169,352 +295,1314 @@ impl ZoneStore {
     }
 }
 
-pub fn parse_zone_file<P: AsRef<Path>>(path: P, origin_name: &str) -> Result<Zone> {
-    let content = std::fs::read_to_string(path.as_ref()).context("Failed to read zone file")?;
+/// Type codes of records hickory has no RData model for
+const TYPE_RP: u16 = 17;
+const TYPE_LOC: u16 = 29;
+const TYPE_CERT: u16 = 37;
+const TYPE_SMIMEA: u16 = 53;
+const TYPE_ZONEMD: u16 = 63;
+const TYPE_URI: u16 = 256;
+
+/// Presentation mnemonics of the types above
+const OPAQUE_TYPE_NAMES: [(&str, u16); 6] = [
+    ("RP", TYPE_RP),
+    ("LOC", TYPE_LOC),
+    ("CERT", TYPE_CERT),
+    ("SMIMEA", TYPE_SMIMEA),
+    ("ZONEMD", TYPE_ZONEMD),
+    ("URI", TYPE_URI),
+];
+
+/// Maximum length of a single character-string (RFC 1035 section 3.3)
+const MAX_CHARACTER_STRING: usize = 255;
+
+/// Maximum nesting depth of `$INCLUDE` directives
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Maximum number of records a single `$GENERATE` directive may produce
+const MAX_GENERATE_COUNT: u64 = 1 << 20;
+
+/// State shared across a zone file and every file it `$INCLUDE`s
+struct ZoneParser {
+    origin: Name,
+    zone: Option<Zone>,
+    /// Records seen before the SOA, added once the zone exists
+    pending: Vec<(Record, PathBuf, Token)>,
+    defaults: RecordDefaults,
+    include_stack: Vec<PathBuf>,
+    /// Every file read, in the order they were read
+    sources: Vec<PathBuf>,
+    diagnostics: Vec<Diagnostic>,
+}
 
-    let origin = Name::from_str(origin_name).context("Invalid origin name")?;
+/// Values a resource record inherits from earlier entries when it omits them
+#[derive(Debug, Default)]
+struct RecordDefaults {
+    /// TTL set by the most recent `$TTL` directive
+    ttl: Option<u32>,
+    /// Most recent explicit TTL, used when no `$TTL` has been seen (RFC 1035)
+    last_ttl: Option<u32>,
+    /// Owner of the previous record, used by blank-owner lines
+    last_owner: Option<Name>,
+}
 
-    let mut zone: Option<Zone> = None;
-    let mut default_ttl: u32 = 3600;
-    let mut current_origin = origin.clone();
+impl RecordDefaults {
+    fn ttl(&self) -> u32 {
+        self.ttl.or(self.last_ttl).unwrap_or(3600)
+    }
+}
 
-    // Preprocess the content to handle multi-line records with parentheses
-    let processed_lines = preprocess_zone_content(&content);
+impl ZoneParser {
+    fn error(&mut self, file: &Path, located: Located) {
+        self.diagnostics
+            .push(Diagnostic::new(Severity::Error, file, located));
+    }
 
-    for (line_num, line) in processed_lines.iter().enumerate() {
-        let line = line.trim();
+    fn warning(&mut self, file: &Path, located: Located) {
+        self.diagnostics
+            .push(Diagnostic::new(Severity::Warning, file, located));
+    }
 
-        // Skip empty lines and comments
-        if line.is_empty() || line.starts_with(';') {
-            continue;
+    /// Adds a parsed record, reporting conflicts with what the zone already
+    /// holds. `at` is the first token of the entry the record came from.
+    /// Records outside the origin are kept for `lint_zone` to report.
+    fn add_record(&mut self, record: Record, file: &Path, at: &Token) {
+        if record.record_type() == RecordType::SOA {
+            if !self.origin.zone_of(record.name()) {
+                self.warning(
+                    file,
+                    at.located(format!("Ignoring out-of-zone SOA record {}", record.name())),
+                );
+                return;
+            }
+            if self.zone.is_some() {
+                self.error(file, at.located("Duplicate SOA record"));
+                return;
+            }
+            let Some(soa_data) = extract_soa_data(&record) else {
+                return;
+            };
+            let mut zone = Zone::new(self.origin.clone(), soa_data);
+            zone.add_record(record);
+            self.zone = Some(zone);
+            for (record, file, at) in std::mem::take(&mut self.pending) {
+                self.add_record(record, &file, &at);
+            }
+            return;
         }
 
-        // Handle directives
-        if line.starts_with('$') {
-            if line.starts_with("$ORIGIN") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    current_origin = Name::from_str(parts[1])
-                        .context(format!("Invalid $ORIGIN on line {}", line_num + 1))?;
-                }
-            } else if line.starts_with("$TTL") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    default_ttl = parts[1]
-                        .parse()
-                        .context(format!("Invalid $TTL on line {}", line_num + 1))?;
+        let Some(zone) = self.zone.as_mut() else {
+            self.pending.push((record, file.to_path_buf(), at.clone()));
+            return;
+        };
+
+        let mut record = record;
+        let mut warnings = Vec::new();
+        if let Some(type_map) = zone.records.get(record.name())
+            && let Some(rrset) = type_map.get(&record.record_type())
+            && let Some(first) = rrset.first()
+            && first.ttl() != record.ttl()
+            && !matches!(record.record_type(), RecordType::RRSIG | RecordType::SIG)
+        {
+            // RFC 2181 section 5.2: all records of an RRset share one TTL
+            warnings.push(format!(
+                "TTL {} differs from {} for {} {} RRset, using {}",
+                record.ttl(),
+                first.ttl(),
+                record.name(),
+                record.record_type(),
+                first.ttl()
+            ));
+            record.set_ttl(first.ttl());
+        }
+        zone.add_record(record);
+
+        for message in warnings {
+            self.warning(file, at.located(message));
+        }
+    }
+
+    /// Parses one file, recording problems in `diagnostics` and carrying on
+    /// with the next entry. `$ORIGIN` changes are scoped to the file, so an
+    /// included file never alters the origin of the file that included it.
+    /// Only failures to read the file at all are returned as errors.
+    fn parse_file(&mut self, path: &Path, mut current_origin: Name) -> Result<()> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.include_stack.contains(&canonical) {
+            anyhow::bail!("$INCLUDE cycle detected at {}", path.display());
+        }
+        if self.include_stack.len() >= MAX_INCLUDE_DEPTH {
+            anyhow::bail!(
+                "$INCLUDE nesting deeper than {} at {}",
+                MAX_INCLUDE_DEPTH,
+                path.display()
+            );
+        }
+
+        let content = std::fs::read_to_string(path)
+            .context(format!("Failed to read zone file {}", path.display()))?;
+        self.sources.push(path.to_path_buf());
+        let (entries, errors) = tokenize_zone_recovering(&content);
+        for located in errors {
+            self.error(path, located);
+        }
+        self.include_stack.push(canonical);
+
+        for entry in &entries {
+            let first = &entry.tokens[0];
+
+            // Handle directives
+            if !entry.blank_owner && first.text.starts_with('$') {
+                let parts: Vec<&str> = entry.tokens.iter().map(|t| t.text.as_str()).collect();
+                match parts[0].to_ascii_uppercase().as_str() {
+                    "$ORIGIN" => {
+                        if parts.len() >= 2 {
+                            match parse_domain_name(parts[1], &current_origin) {
+                                Ok(origin) => current_origin = origin,
+                                Err(e) => self.error(
+                                    path,
+                                    first.located(format!("Invalid $ORIGIN: {:#}", e)),
+                                ),
+                            }
+                        }
+                    }
+                    "$TTL" => {
+                        if parts.len() >= 2 {
+                            match parse_ttl(parts[1]) {
+                                Ok(ttl) => self.defaults.ttl = Some(ttl),
+                                Err(e) => self
+                                    .error(path, first.located(format!("Invalid $TTL: {:#}", e))),
+                            }
+                        }
+                    }
+                    "$INCLUDE" => {
+                        let Some(file) = parts.get(1) else {
+                            self.error(path, first.located("Missing file name in $INCLUDE"));
+                            continue;
+                        };
+                        let include_path = resolve_include_path(path, file);
+                        let include_origin = match parts.get(2) {
+                            Some(o) => match parse_domain_name(o, &current_origin) {
+                                Ok(origin) => origin,
+                                Err(e) => {
+                                    self.error(
+                                        path,
+                                        first.located(format!("Invalid $INCLUDE origin: {:#}", e)),
+                                    );
+                                    continue;
+                                }
+                            },
+                            None => current_origin.clone(),
+                        };
+                        if let Err(e) = self.parse_file(&include_path, include_origin) {
+                            self.error(
+                                path,
+                                first.located(format!("In $INCLUDE {}: {:#}", file, e)),
+                            );
+                        }
+                    }
+                    "$GENERATE" => {
+                        let generated = match expand_generate(&entry.tokens) {
+                            Ok(generated) => generated,
+                            Err(e) => {
+                                self.error(
+                                    path,
+                                    first.located(format!("Invalid $GENERATE: {:#}", e)),
+                                );
+                                continue;
+                            }
+                        };
+                        for generated_entry in &generated {
+                            self.parse_entry(path, generated_entry, &current_origin);
+                        }
+                    }
+                    other => {
+                        self.warning(
+                            path,
+                            first.located(format!("Ignoring unsupported directive {}", other)),
+                        );
+                    }
                 }
+                continue;
             }
-            continue;
+
+            self.parse_entry(path, entry, &current_origin);
         }
 
-        // Parse resource record
-        if let Some(record) = parse_resource_record(line, &current_origin, default_ttl, line_num)? {
-            // If this is SOA and we don't have a zone yet, create it
-            if record.record_type() == RecordType::SOA
-                && zone.is_none()
-                && let Some(soa_data) = extract_soa_data(&record)
-            {
-                zone = Some(Zone::new(origin.clone(), soa_data));
-            }
+        self.include_stack.pop();
+        Ok(())
+    }
 
-            if let Some(ref mut z) = zone {
-                z.add_record(record);
+    fn parse_entry(&mut self, path: &Path, entry: &Entry, origin: &Name) {
+        let mut warnings = Vec::new();
+        let result = parse_resource_record(entry, origin, &mut self.defaults, &mut warnings);
+        for located in warnings {
+            self.warning(path, located);
+        }
+        match result {
+            Ok(Some(record)) => self.add_record(record, path, &entry.tokens[0]),
+            Ok(None) => {}
+            Err(e) => {
+                self.diagnostics
+                    .push(Diagnostic::from_error(path, &entry.tokens[0], &e));
             }
         }
     }
+}
+
+/// Result of loading a zone file: the zone, if an SOA was found, and every
+/// error and warning reported along the way
+#[derive(Debug)]
+pub struct ZoneCheck {
+    pub zone: Option<Zone>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The zone file followed by every file it `$INCLUDE`d
+    pub sources: Vec<PathBuf>,
+}
+
+impl ZoneCheck {
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+    }
 
-    zone.ok_or_else(|| anyhow::anyhow!("Zone file must contain an SOA record"))
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Warning)
+    }
 }
 
-/// Preprocesses zone file content to handle multi-line records with parentheses
-/// and strip inline comments
-fn preprocess_zone_content(content: &str) -> Vec<String> {
-    let mut result = Vec::new();
-    let mut current_line = String::new();
-    let mut in_parens = false;
+/// Parses a zone file without stopping at the first problem, collecting
+/// all errors and warnings. Fails only if the file itself cannot be read.
+pub fn check_zone_file<P: AsRef<Path>>(path: P, origin_name: &str) -> Result<ZoneCheck> {
+    let origin = Name::from_str(origin_name).context("Invalid origin name")?;
 
-    for line in content.lines() {
-        // Strip inline comments (but preserve the rest of the line)
-        let line_without_comment = if let Some(pos) = line.find(';') {
-            // Check if the semicolon is inside quotes (for TXT records, etc.)
-            let before_semicolon = &line[..pos];
-            let quote_count = before_semicolon.matches('"').count();
+    let mut parser = ZoneParser {
+        origin: origin.clone(),
+        zone: None,
+        pending: Vec::new(),
+        defaults: RecordDefaults::default(),
+        include_stack: Vec::new(),
+        sources: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    parser.parse_file(path.as_ref(), origin)?;
 
-            // If odd number of quotes, semicolon is inside a string, keep it
-            if quote_count % 2 == 1 {
-                line
-            } else {
-                before_semicolon
+    Ok(ZoneCheck {
+        zone: parser.zone,
+        diagnostics: parser.diagnostics,
+        sources: parser.sources,
+    })
+}
+
+/// Parses a zone file, logging warnings and failing with every error found
+pub fn parse_zone_file<P: AsRef<Path>>(path: P, origin_name: &str) -> Result<Zone> {
+    Ok(parse_zone_file_with_sources(path, origin_name)?.0)
+}
+
+/// Like `parse_zone_file`, also returning the zone file and every file it
+/// `$INCLUDE`d
+pub fn parse_zone_file_with_sources<P: AsRef<Path>>(
+    path: P,
+    origin_name: &str,
+) -> Result<(Zone, Vec<PathBuf>)> {
+    let check = check_zone_file(path, origin_name)?;
+    for warning in check.warnings() {
+        tracing::warn!("{}", warning);
+    }
+
+    let errors: Vec<String> = check.errors().map(ToString::to_string).collect();
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "{} error(s) in zone file:\n{}",
+            errors.len(),
+            errors.join("\n")
+        );
+    }
+
+    let zone = check
+        .zone
+        .ok_or_else(|| anyhow::anyhow!("Zone file must contain an SOA record"))?;
+    Ok((zone, check.sources))
+}
+
+/// Resolves an `$INCLUDE` path; relative paths are taken relative to the
+/// directory of the including file.
+fn resolve_include_path(including: &Path, file: &str) -> PathBuf {
+    let file = Path::new(file);
+    if file.is_absolute() {
+        return file.to_path_buf();
+    }
+    match including.parent() {
+        Some(dir) => dir.join(file),
+        None => file.to_path_buf(),
+    }
+}
+
+/// Expands a BIND-style `$GENERATE start-stop[/step] lhs rhs...` directive
+/// into one entry per iteration. Everything after `lhs` (optional TTL and
+/// class, type, rdata) is treated as the right-hand template.
+fn expand_generate(tokens: &[Token]) -> Result<Vec<Entry>> {
+    if tokens.len() < 4 {
+        anyhow::bail!("Expected $GENERATE range lhs [ttl] [class] type rhs");
+    }
+
+    let (range, step) = match tokens[1].text.split_once('/') {
+        Some((range, step)) => (range, step.parse::<u64>().context("Invalid step")?),
+        None => (tokens[1].text.as_str(), 1),
+    };
+    let (start, stop) = range
+        .split_once('-')
+        .ok_or_else(|| anyhow::anyhow!("Range must be start-stop"))?;
+    let start: u64 = start.parse().context("Invalid range start")?;
+    let stop: u64 = stop.parse().context("Invalid range stop")?;
+
+    if step == 0 {
+        anyhow::bail!("Step must be at least 1");
+    }
+    if start > stop {
+        anyhow::bail!("Range start {} is greater than stop {}", start, stop);
+    }
+    if (stop - start) / step >= MAX_GENERATE_COUNT {
+        anyhow::bail!("Range produces more than {} records", MAX_GENERATE_COUNT);
+    }
+
+    let mut entries = Vec::new();
+    let mut i = start;
+    while i <= stop {
+        let tokens = tokens[2..]
+            .iter()
+            .map(|t| {
+                Ok(Token {
+                    text: generate_substitute(&t.text, i)?,
+                    ..t.clone()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        entries.push(Entry {
+            blank_owner: false,
+            tokens,
+        });
+        // A range ending near u64::MAX must not wrap around
+        match i.checked_add(step) {
+            Some(next) => i = next,
+            None => break,
+        }
+    }
+    Ok(entries)
+}
+
+/// Substitutes the `$GENERATE` iterator into a template. `$` is replaced by
+/// the iterator, `${offset[,width[,base]]}` applies a modifier and `$$` or
+/// `\$` produce a literal `$`. Bases are `d`, `o`, `x`, `X`, and `n`/`N` for
+/// reversed nibble labels (width counts nibbles).
+fn generate_substitute(template: &str, value: u64) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'$') => {
+                chars.next();
+                out.push('$');
             }
-        } else {
-            line
-        };
+            '$' if chars.peek() == Some(&'$') => {
+                chars.next();
+                out.push('$');
+            }
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let mut spec = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => spec.push(c),
+                        None => anyhow::bail!("Unterminated modifier in {}", template),
+                    }
+                }
+                out.push_str(&apply_generate_modifier(&spec, value)?);
+            }
+            '$' => out.push_str(&value.to_string()),
+            c => out.push(c),
+        }
+    }
+
+    Ok(out)
+}
 
-        let trimmed = line_without_comment.trim();
+fn apply_generate_modifier(spec: &str, value: u64) -> Result<String> {
+    let fields: Vec<&str> = spec.split(',').map(str::trim).collect();
+    if fields.len() > 3 {
+        anyhow::bail!("Too many fields in modifier {{{}}}", spec);
+    }
 
-        // Check for opening parenthesis
-        if trimmed.contains('(') {
-            in_parens = true;
-            // Add everything before and including the paren to current line
-            current_line.push_str(trimmed.replace('(', "").trim());
-            current_line.push(' ');
-            continue;
+    let offset: i64 = match fields.first() {
+        Some(f) if !f.is_empty() => f.parse().context("Invalid modifier offset")?,
+        _ => 0,
+    };
+    let width: usize = match fields.get(1) {
+        Some(f) if !f.is_empty() => f.parse().context("Invalid modifier width")?,
+        _ => 0,
+    };
+    let base = fields.get(2).copied().unwrap_or("d");
+
+    let value = i64::try_from(value)
+        .ok()
+        .and_then(|v| v.checked_add(offset))
+        .filter(|v| *v >= 0)
+        .ok_or_else(|| anyhow::anyhow!("Modifier offset {} out of range", offset))?;
+
+    Ok(match base {
+        "d" => format!("{:0width$}", value),
+        "o" => format!("{:0width$o}", value),
+        "x" => format!("{:0width$x}", value),
+        "X" => format!("{:0width$X}", value),
+        "n" | "N" => {
+            let hex = if base == "n" {
+                format!("{:0width$x}", value)
+            } else {
+                format!("{:0width$X}", value)
+            };
+            hex.chars()
+                .rev()
+                .map(String::from)
+                .collect::<Vec<_>>()
+                .join(".")
         }
+        other => anyhow::bail!("Unknown modifier base {}", other),
+    })
+}
 
-        // Check for closing parenthesis
-        if trimmed.contains(')') {
-            in_parens = false;
-            // Add everything before the closing paren and finalize the line
-            current_line.push_str(trimmed.replace(')', "").trim());
-            result.push(current_line.trim().to_string());
-            current_line = String::new();
-            continue;
+/// A zone file token and the 1-based position it starts at
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    col: usize,
+}
+
+impl Token {
+    fn located(&self, message: impl Into<String>) -> Located {
+        Located::new(self.line, self.col, message)
+    }
+}
+
+/// A parse error or warning tied to the zone file position it refers to
+#[derive(Debug, Clone)]
+struct Located {
+    line: usize,
+    col: usize,
+    message: String,
+}
+
+impl Located {
+    fn new(line: usize, col: usize, message: impl Into<String>) -> Self {
+        Located {
+            line,
+            col,
+            message: message.into(),
         }
+    }
+}
 
-        // If we're inside parentheses, accumulate
-        if in_parens {
-            current_line.push_str(trimmed);
-            current_line.push(' ');
-        } else {
-            // Normal line - add it directly (unless it's empty)
-            if !trimmed.is_empty() {
-                result.push(trimmed.to_string());
+impl std::fmt::Display for Located {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.line, self.col
+        )
+    }
+}
+
+impl std::error::Error for Located {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// A problem found while loading a zone file, displayed as
+/// `file:line:col: severity: message`
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: PathBuf,
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, file: &Path, located: Located) -> Self {
+        Diagnostic {
+            severity,
+            file: file.to_path_buf(),
+            line: located.line,
+            col: located.col,
+            message: located.message,
+        }
+    }
+
+    /// Builds an error diagnostic from a failed entry, using the position
+    /// attached by the parser or else the start of the entry
+    fn from_error(file: &Path, fallback: &Token, err: &anyhow::Error) -> Self {
+        let located = match err.downcast_ref::<Located>() {
+            Some(located) => Located::new(located.line, located.col, error_message(err)),
+            None => fallback.located(error_message(err)),
+        };
+        Diagnostic::new(Severity::Error, file, located)
+    }
+}
+
+/// The full message of a parse error, without the position of the
+/// outermost `Located` error
+fn error_message(err: &anyhow::Error) -> String {
+    match err.downcast_ref::<Located>() {
+        Some(located) => {
+            let mut message = located.message.clone();
+            for cause in err.chain().skip(1) {
+                message.push_str(&format!(": {}", cause));
+            }
+            message
+        }
+        None => format!("{:#}", err),
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.file.display(),
+            self.line,
+            self.col,
+            self.severity,
+            self.message
+        )
+    }
+}
+
+/// One logical zone file entry (a directive or resource record), which may
+/// span several physical lines inside parentheses
+#[derive(Debug)]
+struct Entry {
+    /// The entry started with whitespace, so the owner is inherited from
+    /// the previous record
+    blank_owner: bool,
+    tokens: Vec<Token>,
+}
+
+/// Splits zone file content into entries following the RFC 1035 master file
+/// grammar: `;` comments, parentheses joining lines, quoted strings kept as
+/// single tokens (quotes included), and `\` escapes kept verbatim. Fails on
+/// the first syntax error; see `tokenize_zone_recovering`.
+fn tokenize_zone(content: &str) -> Result<Vec<Entry>> {
+    let (entries, errors) = tokenize_zone_recovering(content);
+    match errors.into_iter().next() {
+        Some(error) => Err(error.into()),
+        None => Ok(entries),
+    }
+}
+
+/// Like `tokenize_zone`, but an entry with a syntax error is dropped and
+/// tokenizing resumes at the next line, so every error in the file is
+/// reported. An unmatched `(` is only noticed at the end of the content, so
+/// tokenizing then resumes at the line after it.
+fn tokenize_zone_recovering(content: &str) -> (Vec<Entry>, Vec<Located>) {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut blank_owner = false;
+    // Line, column and byte offset of the open '('
+    let mut open_paren: Option<(usize, usize, usize)> = None;
+    let mut in_quotes = false;
+    let mut in_comment = false;
+    let (mut line, mut col) = (1, 0);
+    let mut offset = 0;
+
+    loop {
+        let mut chars = content[offset..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            col += 1;
+
+            if c == '\n' {
+                if in_quotes {
+                    let start = current.as_ref().map_or((line, col), |t| (t.line, t.col));
+                    errors.push(Located::new(start.0, start.1, "Unterminated quoted string"));
+                    in_quotes = false;
+                    open_paren = None;
+                    tokens.clear();
+                    current = None;
+                }
+                in_comment = false;
+                tokens.extend(current.take());
+                if open_paren.is_none() {
+                    if !tokens.is_empty() {
+                        entries.push(Entry {
+                            blank_owner,
+                            tokens: std::mem::take(&mut tokens),
+                        });
+                    }
+                    blank_owner = false;
+                }
+                line += 1;
+                col = 0;
+                continue;
+            }
+            if in_comment {
+                continue;
+            }
+
+            if in_quotes {
+                let token = current.get_or_insert_with(|| Token {
+                    text: String::new(),
+                    line,
+                    col,
+                });
+                token.text.push(c);
+                match c {
+                    '\\' => {
+                        if let Some((_, next)) = chars.next() {
+                            col += 1;
+                            token.text.push(next);
+                        }
+                    }
+                    '"' => in_quotes = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            let error = match c {
+                ' ' | '\t' | '\r' => {
+                    if col == 1 && tokens.is_empty() && open_paren.is_none() {
+                        blank_owner = true;
+                    }
+                    tokens.extend(current.take());
+                    None
+                }
+                ';' => {
+                    tokens.extend(current.take());
+                    in_comment = true;
+                    None
+                }
+                '(' => {
+                    tokens.extend(current.take());
+                    if open_paren.is_some() {
+                        Some(Located::new(line, col, "Nested '('"))
+                    } else {
+                        open_paren = Some((line, col, offset + i));
+                        None
+                    }
+                }
+                ')' => {
+                    tokens.extend(current.take());
+                    open_paren
+                        .take()
+                        .is_none()
+                        .then(|| Located::new(line, col, "Unmatched ')'"))
+                }
+                _ => {
+                    let token = current.get_or_insert_with(|| Token {
+                        text: String::new(),
+                        line,
+                        col,
+                    });
+                    token.text.push(c);
+                    match c {
+                        '"' => in_quotes = true,
+                        '\\' => {
+                            if let Some((_, next)) = chars.next() {
+                                col += 1;
+                                token.text.push(next);
+                            }
+                        }
+                        _ => {}
+                    }
+                    None
+                }
+            };
+            // Drop the broken entry and skip the rest of the line
+            if let Some(error) = error {
+                errors.push(error);
+                open_paren = None;
+                tokens.clear();
+                current = None;
+                in_comment = true;
+            }
+        }
+
+        if in_quotes {
+            let start = current.as_ref().map_or((line, col), |t| (t.line, t.col));
+            errors.push(Located::new(start.0, start.1, "Unterminated quoted string"));
+            return (entries, errors);
+        }
+        let Some((paren_line, paren_col, paren_offset)) = open_paren.take() else {
+            break;
+        };
+        errors.push(Located::new(paren_line, paren_col, "Unmatched '('"));
+        tokens.clear();
+        current = None;
+        blank_owner = false;
+        in_comment = false;
+        match content[paren_offset..].find('\n') {
+            Some(end) => {
+                offset = paren_offset + end + 1;
+                (line, col) = (paren_line + 1, 0);
             }
+            None => return (entries, errors),
         }
     }
 
-    // Handle case where parentheses weren't closed (add remaining content)
-    if !current_line.is_empty() {
-        result.push(current_line.trim().to_string());
+    tokens.extend(current.take());
+    if !tokens.is_empty() {
+        entries.push(Entry {
+            blank_owner,
+            tokens,
+        });
+    }
+
+    (entries, errors)
+}
+
+/// Parses a TTL, either plain seconds or BIND-style units such as `1h30m`
+/// or `1w` (`s`, `m`, `h`, `d`, `w`, case-insensitive)
+fn parse_ttl(s: &str) -> Result<u32> {
+    if let Ok(ttl) = s.parse::<u32>() {
+        return Ok(ttl);
+    }
+    if !s.starts_with(|c: char| c.is_ascii_digit()) {
+        anyhow::bail!("Invalid TTL {}", s);
+    }
+
+    let mut total: u64 = 0;
+    let mut digits = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let multiplier = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 604800,
+            _ => anyhow::bail!("Invalid TTL unit '{}' in {}", c, s),
+        };
+        if digits.is_empty() {
+            anyhow::bail!("Missing value before '{}' in TTL {}", c, s);
+        }
+        let value: u64 = digits.parse().context(format!("Invalid TTL {}", s))?;
+        total = total.saturating_add(value.saturating_mul(multiplier));
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        // A trailing bare number counts as seconds, e.g. `1m30`
+        let value: u64 = digits.parse().context(format!("Invalid TTL {}", s))?;
+        total = total.saturating_add(value);
     }
 
-    result
+    u32::try_from(total).map_err(|_| anyhow::anyhow!("TTL {} out of range", s))
 }
 
 fn parse_resource_record(
-    line: &str,
+    entry: &Entry,
     origin: &Name,
-    default_ttl: u32,
-    line_num: usize,
+    defaults: &mut RecordDefaults,
+    warnings: &mut Vec<Located>,
 ) -> Result<Option<Record>> {
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    if parts.len() < 4 {
-        return Ok(None);
-    }
+    let tokens = &entry.tokens;
+    let parts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+    let at = |i: usize| &tokens[i.min(tokens.len() - 1)];
 
     let mut idx = 0;
 
-    // Parse name
-    let name = if parts[idx] == "@" {
-        origin.clone()
-    } else if parts[idx] == "*" {
-        // Wildcard at zone apex: *.example.com.
-        Name::from_str(&format!("*.{}", origin))
-            .context(format!("Invalid wildcard name on line {}", line_num + 1))?
-    } else if parts[idx].starts_with("*.") {
-        // Wildcard with subdomain: *.sub.example.com
-        if parts[idx].ends_with('.') {
-            Name::from_str(parts[idx])
-                .context(format!("Invalid wildcard name on line {}", line_num + 1))?
-        } else {
-            Name::from_str(&format!("{}.{}", parts[idx], origin))
-                .context(format!("Invalid wildcard name on line {}", line_num + 1))?
-        }
-    } else if parts[idx].ends_with('.') {
-        Name::from_str(parts[idx]).context(format!("Invalid name on line {}", line_num + 1))?
+    // Parse owner name, inheriting the previous owner on blank-owner lines
+    let name = if entry.blank_owner {
+        defaults.last_owner.clone().ok_or_else(|| {
+            anyhow::Error::from(at(0).located("Missing owner name for first record"))
+        })?
     } else {
-        Name::from_str(&format!("{}.{}", parts[idx], origin))
-            .context(format!("Invalid name on line {}", line_num + 1))?
+        idx += 1;
+        parse_domain_name(parts[0], origin).context(at(0).located("Invalid name"))?
     };
-    idx += 1;
 
-    // Parse optional TTL or class
-    let mut ttl = default_ttl;
-    if parts[idx].parse::<u32>().is_ok() {
-        ttl = parts[idx].parse().unwrap();
+    // Parse optional TTL and class, which may appear in either order
+    let mut ttl = None;
+    let mut class_seen = false;
+    while idx < parts.len() {
+        let part = parts[idx];
+        if !class_seen && is_class(part) {
+            let is_in = part.eq_ignore_ascii_case("IN")
+                || matches!(generic_code(part, "CLASS"), Some(Ok(1)));
+            if !is_in {
+                return Err(at(idx)
+                    .located(format!("Unsupported class {}", part))
+                    .into());
+            }
+            class_seen = true;
+        } else if ttl.is_none() && part.starts_with(|c: char| c.is_ascii_digit()) {
+            ttl = Some(parse_ttl(part).context(at(idx).located("Invalid TTL"))?);
+        } else {
+            break;
+        }
         idx += 1;
     }
 
-    // Skip class if present (we only support IN)
-    if parts[idx] == "IN" {
-        idx += 1;
+    if idx >= parts.len() {
+        return Err(at(idx).located("Missing record type").into());
     }
 
-    // Parse record type
-    let rtype = parts[idx];
+    defaults.last_owner = Some(name.clone());
+    if ttl.is_some() {
+        defaults.last_ttl = ttl;
+    }
+    let ttl = ttl.unwrap_or_else(|| defaults.ttl());
+
+    // Parse record type, mapping RFC 3597 `TYPE###` names of known types
+    // back to their mnemonic
+    let rtype_idx = idx;
+    let mut rtype = parts[idx].to_ascii_uppercase();
+    if let Some(code) = generic_code(&rtype, "TYPE") {
+        let code = code.context(at(idx).located(format!("Invalid type {}", parts[idx])))?;
+        if !matches!(RecordType::from(code), RecordType::Unknown(_)) {
+            rtype = RecordType::from(code).to_string();
+        }
+    }
     idx += 1;
 
+    let need = |n: usize| -> Result<()> {
+        if parts.len() < idx + n {
+            return Err(at(rtype_idx)
+                .located(format!("Incomplete {} record", rtype))
+                .into());
+        }
+        Ok(())
+    };
+
+    // RFC 3597 generic RDATA: `\# <length> <hex>`, accepted for any type
+    if parts.get(idx) == Some(&"\\#") {
+        let record_type = match generic_code(&rtype, "TYPE") {
+            Some(code) => RecordType::from(code?),
+            None => RecordType::from_str(&rtype).context(
+                at(rtype_idx).located(format!("Unknown type {}, use the TYPE### form", rtype)),
+            )?,
+        };
+        if matches!(
+            record_type,
+            RecordType::ZERO
+                | RecordType::OPT
+                | RecordType::ANY
+                | RecordType::AXFR
+                | RecordType::IXFR
+                | RecordType::TSIG
+        ) {
+            return Err(at(rtype_idx)
+                .located(format!("Type {} cannot appear in a zone file", rtype))
+                .into());
+        }
+
+        need(2)?;
+        let length = parts[idx + 1]
+            .parse::<u16>()
+            .context(at(idx + 1).located("Invalid RDATA length"))?;
+        let wire = decode_hex("generic RDATA", &parts[idx + 2..], at(idx + 2))?;
+        if wire.len() != length as usize {
+            return Err(at(idx + 1)
+                .located(format!(
+                    "RDATA length {} does not match {} bytes of data",
+                    length,
+                    wire.len()
+                ))
+                .into());
+        }
+
+        let rdata = match rdata_from_wire(record_type, &wire)
+            .context(at(idx + 2).located(format!("Invalid {} RDATA", rtype)))?
+        {
+            // Signatures are stored as SIG, as for the RRSIG presentation form
+            RData::DNSSEC(hickory_proto::rr::dnssec::rdata::DNSSECRData::RRSIG(rrsig)) => {
+                RData::DNSSEC(hickory_proto::rr::dnssec::rdata::DNSSECRData::SIG(
+                    (*rrsig).clone(),
+                ))
+            }
+            rdata => rdata,
+        };
+        return Ok(Some(Record::from_rdata(name, ttl, rdata)));
+    }
+
     // Parse RDATA
-    let rdata = match rtype {
+    let rdata = match rtype.as_str() {
         "A" => {
-            if parts.len() <= idx {
-                return Ok(None);
-            }
+            need(1)?;
             let addr = parts[idx]
                 .parse::<Ipv4Addr>()
-                .context(format!("Invalid A record on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid A record"))?;
             RData::A(hickory_proto::rr::rdata::A(addr))
         }
         "AAAA" => {
-            if parts.len() <= idx {
-                return Ok(None);
-            }
+            need(1)?;
             let addr = parts[idx]
                 .parse::<Ipv6Addr>()
-                .context(format!("Invalid AAAA record on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid AAAA record"))?;
             RData::AAAA(hickory_proto::rr::rdata::AAAA(addr))
         }
         "NS" => {
-            if parts.len() <= idx {
-                return Ok(None);
-            }
+            need(1)?;
             let nsdname = parse_domain_name(parts[idx], origin)
-                .context(format!("Invalid NS record on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid NS record"))?;
             RData::NS(hickory_proto::rr::rdata::NS(nsdname))
         }
         "SOA" => {
-            if parts.len() < idx + 7 {
-                return Ok(None);
-            }
-            let mname = parse_domain_name(parts[idx], origin)?;
-            let rname = parse_domain_name(parts[idx + 1], origin)?;
+            need(7)?;
+            let mname = parse_domain_name(parts[idx], origin)
+                .context(at(idx).located("Invalid SOA mname"))?;
+            let rname = parse_domain_name(parts[idx + 1], origin)
+                .context(at(idx + 1).located("Invalid SOA rname"))?;
             let serial = parts[idx + 2]
                 .parse()
-                .context(format!("Invalid SOA serial on line {}", line_num + 1))?;
-            let refresh = parts[idx + 3]
-                .parse()
-                .context(format!("Invalid SOA refresh on line {}", line_num + 1))?;
-            let retry = parts[idx + 4]
-                .parse()
-                .context(format!("Invalid SOA retry on line {}", line_num + 1))?;
-            let expire = parts[idx + 5]
-                .parse()
-                .context(format!("Invalid SOA expire on line {}", line_num + 1))?;
-            let minimum = parts[idx + 6]
-                .parse()
-                .context(format!("Invalid SOA minimum on line {}", line_num + 1))?;
+                .context(at(idx + 2).located("Invalid SOA serial"))?;
+            let refresh = parse_soa_timer(parts[idx + 3])
+                .context(at(idx + 3).located("Invalid SOA refresh"))?;
+            let retry = parse_soa_timer(parts[idx + 4])
+                .context(at(idx + 4).located("Invalid SOA retry"))?;
+            let expire = parse_soa_timer(parts[idx + 5])
+                .context(at(idx + 5).located("Invalid SOA expire"))?;
+            let minimum =
+                parse_ttl(parts[idx + 6]).context(at(idx + 6).located("Invalid SOA minimum"))?;
 
             RData::SOA(hickory_proto::rr::rdata::SOA::new(
                 mname, rname, serial, refresh, retry, expire, minimum,
             ))
         }
         "CNAME" => {
-            if parts.len() <= idx {
-                return Ok(None);
-            }
+            need(1)?;
             let cname = parse_domain_name(parts[idx], origin)
-                .context(format!("Invalid CNAME record on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid CNAME record"))?;
             RData::CNAME(hickory_proto::rr::rdata::CNAME(cname))
         }
+        "ALIAS" | "ANAME" => {
+            // Apex-safe alias, flattened into A/AAAA answers at query time
+            need(1)?;
+            let target = parse_domain_name(parts[idx], origin)
+                .context(at(idx).located("Invalid ALIAS record"))?;
+            RData::ANAME(hickory_proto::rr::rdata::ANAME(target))
+        }
         "MX" => {
-            if parts.len() < idx + 2 {
-                return Ok(None);
-            }
+            need(2)?;
             let preference = parts[idx]
                 .parse::<u16>()
-                .context(format!("Invalid MX preference on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid MX preference"))?;
             let exchange = parse_domain_name(parts[idx + 1], origin)
-                .context(format!("Invalid MX exchange on line {}", line_num + 1))?;
+                .context(at(idx + 1).located("Invalid MX exchange"))?;
             RData::MX(hickory_proto::rr::rdata::MX::new(preference, exchange))
         }
         "TXT" => {
-            if parts.len() <= idx {
-                return Ok(None);
+            need(1)?;
+            // Each token is one character-string; long strings are split
+            // into 255-byte chunks
+            let mut strings = Vec::new();
+            for (i, part) in parts[idx..].iter().enumerate() {
+                let bytes = parse_character_string(part)
+                    .context(at(idx + i).located("Invalid TXT string"))?;
+                if bytes.is_empty() {
+                    strings.push(bytes);
+                } else {
+                    strings.extend(bytes.chunks(MAX_CHARACTER_STRING).map(<[u8]>::to_vec));
+                }
             }
-            // Join all remaining parts as the TXT data (handles quoted strings)
-            let txt_data = parts[idx..].join(" ");
-            // Remove quotes if present
-            let txt_data = txt_data.trim_matches('"');
-            RData::TXT(hickory_proto::rr::rdata::TXT::new(vec![
-                txt_data.to_string(),
-            ]))
+            if strings.iter().map(|s| s.len() + 1).sum::<usize>() > u16::MAX as usize {
+                return Err(at(idx).located("TXT data too long").into());
+            }
+            RData::TXT(hickory_proto::rr::rdata::TXT::from_bytes(
+                strings.iter().map(Vec::as_slice).collect(),
+            ))
         }
         "PTR" => {
-            if parts.len() <= idx {
-                return Ok(None);
-            }
+            need(1)?;
             let ptrdname = parse_domain_name(parts[idx], origin)
-                .context(format!("Invalid PTR record on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid PTR record"))?;
             RData::PTR(hickory_proto::rr::rdata::PTR(ptrdname))
         }
         "SRV" => {
-            if parts.len() < idx + 4 {
-                return Ok(None);
-            }
+            need(4)?;
             let priority = parts[idx]
                 .parse::<u16>()
-                .context(format!("Invalid SRV priority on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid SRV priority"))?;
             let weight = parts[idx + 1]
                 .parse::<u16>()
-                .context(format!("Invalid SRV weight on line {}", line_num + 1))?;
+                .context(at(idx + 1).located("Invalid SRV weight"))?;
             let port = parts[idx + 2]
                 .parse::<u16>()
-                .context(format!("Invalid SRV port on line {}", line_num + 1))?;
+                .context(at(idx + 2).located("Invalid SRV port"))?;
             let target = parse_domain_name(parts[idx + 3], origin)
-                .context(format!("Invalid SRV target on line {}", line_num + 1))?;
+                .context(at(idx + 3).located("Invalid SRV target"))?;
             RData::SRV(hickory_proto::rr::rdata::SRV::new(
                 priority, weight, port, target,
             ))
         }
         "CAA" => {
-            if parts.len() < idx + 3 {
-                return Ok(None);
+            need(3)?;
+            let flags = parts[idx]
+                .parse::<u8>()
+                .context(at(idx).located("Invalid CAA flags"))?;
+            let tag = parts[idx + 1];
+            if tag.is_empty() || tag.len() > 15 || !tag.bytes().all(|b| b.is_ascii_alphanumeric()) {
+                return Err(at(idx + 1).located("Invalid CAA tag").into());
+            }
+            if parts.len() > idx + 3 {
+                return Err(at(idx + 3)
+                    .located("CAA value must be a single string")
+                    .into());
+            }
+            let value = parse_character_string(parts[idx + 2])
+                .context(at(idx + 2).located("Invalid CAA value"))?;
+
+            // hickory only builds arbitrary CAA properties from wire format
+            let mut wire = vec![flags, tag.len() as u8];
+            wire.extend_from_slice(tag.as_bytes());
+            wire.extend_from_slice(&value);
+            rdata_from_wire(RecordType::CAA, &wire)
+                .context(at(idx + 2).located("Invalid CAA value"))?
+        }
+        "SVCB" | "HTTPS" => {
+            need(2)?;
+            let priority = parts[idx]
+                .parse::<u16>()
+                .context(at(idx).located(format!("Invalid {} priority", rtype)))?;
+            let target = parse_domain_name(parts[idx + 1], origin)
+                .context(at(idx + 1).located(format!("Invalid {} target", rtype)))?;
+            let params = parse_svc_params(&tokens[idx + 2..])?;
+            if priority == 0 && !params.is_empty() {
+                warnings.push(at(idx).located(format!(
+                    "AliasMode {} record has SvcParams, which clients ignore",
+                    rtype
+                )));
+            }
+
+            let svcb = hickory_proto::rr::rdata::SVCB::new(priority, target, params);
+            if rtype == "HTTPS" {
+                RData::HTTPS(hickory_proto::rr::rdata::HTTPS(svcb))
+            } else {
+                RData::SVCB(svcb)
+            }
+        }
+        "HINFO" => {
+            need(2)?;
+            let cpu =
+                parse_character_string(parts[idx]).context(at(idx).located("Invalid HINFO cpu"))?;
+            let os = parse_character_string(parts[idx + 1])
+                .context(at(idx + 1).located("Invalid HINFO os"))?;
+            RData::HINFO(hickory_proto::rr::rdata::HINFO::from_bytes(
+                cpu.into_boxed_slice(),
+                os.into_boxed_slice(),
+            ))
+        }
+        "RP" => {
+            need(2)?;
+            let mbox = parse_domain_name(parts[idx], origin)
+                .context(at(idx).located("Invalid RP mbox"))?;
+            let txt = parse_domain_name(parts[idx + 1], origin)
+                .context(at(idx + 1).located("Invalid RP txt"))?;
+            // Names in RDATA of types without a hickory model are never compressed
+            let mut wire = mbox.to_bytes()?;
+            wire.extend(txt.to_bytes()?);
+            opaque_rdata(RecordType::from(TYPE_RP), wire)
+        }
+        "LOC" => {
+            need(7)?;
+            let wire = parse_loc(&tokens[idx..]).context(at(idx).located("Invalid LOC record"))?;
+            opaque_rdata(RecordType::from(TYPE_LOC), wire)
+        }
+        "URI" => {
+            need(3)?;
+            let priority = parts[idx]
+                .parse::<u16>()
+                .context(at(idx).located("Invalid URI priority"))?;
+            let weight = parts[idx + 1]
+                .parse::<u16>()
+                .context(at(idx + 1).located("Invalid URI weight"))?;
+            let target = parse_character_string(parts[idx + 2])
+                .context(at(idx + 2).located("Invalid URI target"))?;
+            if target.is_empty() {
+                return Err(at(idx + 2).located("Empty URI target").into());
             }
+
+            let mut wire = Vec::with_capacity(4 + target.len());
+            wire.extend_from_slice(&priority.to_be_bytes());
+            wire.extend_from_slice(&weight.to_be_bytes());
+            wire.extend(target);
+            opaque_rdata(RecordType::from(TYPE_URI), wire)
+        }
+        "CERT" => {
+            need(4)?;
+            let cert_type =
+                parse_cert_type(parts[idx]).context(at(idx).located("Invalid CERT type"))?;
+            let key_tag = parts[idx + 1]
+                .parse::<u16>()
+                .context(at(idx + 1).located("Invalid CERT key_tag"))?;
+            let algorithm = parts[idx + 2]
+                .parse::<u8>()
+                .context(at(idx + 2).located("Invalid CERT algorithm"))?;
+
+            let cert = decode_base64("CERT", &parts[idx + 3..], at(idx + 3))?;
+
+            let mut wire = Vec::with_capacity(5 + cert.len());
+            wire.extend_from_slice(&cert_type.to_be_bytes());
+            wire.extend_from_slice(&key_tag.to_be_bytes());
+            wire.push(algorithm);
+            wire.extend(cert);
+            opaque_rdata(RecordType::from(TYPE_CERT), wire)
+        }
+        "OPENPGPKEY" => {
+            need(1)?;
+            let public_key = decode_base64("OPENPGPKEY", &parts[idx..], at(idx))?;
+            RData::OPENPGPKEY(hickory_proto::rr::rdata::OPENPGPKEY::new(public_key))
+        }
+        "SMIMEA" => {
+            // Same RDATA as TLSA: usage selector matching_type data
+            need(4)?;
+            let cert_usage = parts[idx]
+                .parse::<u8>()
+                .context(at(idx).located("Invalid SMIMEA usage"))?;
+            let selector = parts[idx + 1]
+                .parse::<u8>()
+                .context(at(idx + 1).located("Invalid SMIMEA selector"))?;
+            let matching_type = parts[idx + 2]
+                .parse::<u8>()
+                .context(at(idx + 2).located("Invalid SMIMEA matching_type"))?;
+
+            let cert_data = decode_hex("SMIMEA", &parts[idx + 3..], at(idx + 3))?;
+
+            let mut wire = vec![cert_usage, selector, matching_type];
+            wire.extend(cert_data);
+            opaque_rdata(RecordType::from(TYPE_SMIMEA), wire)
+        }
+        "CDS" => {
+            // Same RDATA as DS: key_tag algorithm digest_type digest
+            need(4)?;
+            let key_tag = parts[idx]
+                .parse::<u16>()
+                .context(at(idx).located("Invalid CDS key_tag"))?;
+            let algorithm = parts[idx + 1]
+                .parse::<u8>()
+                .context(at(idx + 1).located("Invalid CDS algorithm"))?;
+            let digest_type = parts[idx + 2]
+                .parse::<u8>()
+                .context(at(idx + 2).located("Invalid CDS digest_type"))?;
+
+            let digest = decode_hex("CDS", &parts[idx + 3..], at(idx + 3))?;
+
+            let mut wire = key_tag.to_be_bytes().to_vec();
+            wire.extend([algorithm, digest_type]);
+            wire.extend(digest);
+            dnssec_rdata_from_wire(RecordType::CDS, wire)
+        }
+        "CDNSKEY" => {
+            // Same RDATA as DNSKEY: flags protocol algorithm public_key
+            need(4)?;
             let flags = parts[idx]
+                .parse::<u16>()
+                .context(at(idx).located("Invalid CDNSKEY flags"))?;
+            let protocol = parts[idx + 1]
                 .parse::<u8>()
-                .context(format!("Invalid CAA flags on line {}", line_num + 1))?;
-            let tag = parts[idx + 1].to_string();
-            // Join remaining parts and remove quotes
-            let value = parts[idx + 2..].join(" ");
-            let value = value.trim_matches('"');
-
-            // Create CAA record - for now, only support "issue" tag properly
-            let caa = if tag == "issue" || tag == "issuewild" {
-                if value.is_empty() || value == ";" {
-                    hickory_proto::rr::rdata::CAA::new_issue(flags & 0x80 != 0, None, vec![])
-                } else {
-                    hickory_proto::rr::rdata::CAA::new_issue(
-                        flags & 0x80 != 0,
-                        Some(
-                            hickory_proto::rr::Name::from_str(value)
-                                .unwrap_or_else(|_| hickory_proto::rr::Name::root()),
-                        ),
-                        vec![],
-                    )
-                }
+                .context(at(idx + 1).located("Invalid CDNSKEY protocol"))?;
+            let algorithm = parts[idx + 2]
+                .parse::<u8>()
+                .context(at(idx + 2).located("Invalid CDNSKEY algorithm"))?;
+
+            let public_key = decode_base64("CDNSKEY", &parts[idx + 3..], at(idx + 3))?;
+
+            let mut wire = flags.to_be_bytes().to_vec();
+            wire.extend([protocol, algorithm]);
+            wire.extend(public_key);
+            dnssec_rdata_from_wire(RecordType::CDNSKEY, wire)
+        }
+        "ZONEMD" => {
+            // ZONEMD: serial scheme hash_algorithm digest
+            need(4)?;
+            let serial = parts[idx]
+                .parse::<u32>()
+                .context(at(idx).located("Invalid ZONEMD serial"))?;
+            let scheme = parts[idx + 1]
+                .parse::<u8>()
+                .context(at(idx + 1).located("Invalid ZONEMD scheme"))?;
+            let hash_algorithm = parts[idx + 2]
+                .parse::<u8>()
+                .context(at(idx + 2).located("Invalid ZONEMD hash_algorithm"))?;
+
+            let digest = decode_hex("ZONEMD", &parts[idx + 3..], at(idx + 3))?;
+            if !zonemd_digest_len_valid(hash_algorithm, digest.len()) {
+                return Err(at(idx + 3).located("Invalid ZONEMD digest length").into());
+            }
+
+            let mut wire = serial.to_be_bytes().to_vec();
+            wire.extend([scheme, hash_algorithm]);
+            wire.extend(digest);
+            opaque_rdata(RecordType::from(TYPE_ZONEMD), wire)
+        }
+        "CSYNC" => {
+            // CSYNC: soa_serial flags type_bit_maps
+            need(2)?;
+            let soa_serial = parts[idx]
+                .parse::<u32>()
+                .context(at(idx).located("Invalid CSYNC serial"))?;
+            let flags = parts[idx + 1]
+                .parse::<u16>()
+                .context(at(idx + 1).located("Invalid CSYNC flags"))?;
+
+            let mut type_bit_maps = Vec::new();
+            for (i, part) in parts[idx + 2..].iter().enumerate() {
+                let rtype = parse_record_type(part)
+                    .context(at(idx + 2 + i).located("Invalid CSYNC type"))?;
+                type_bit_maps.push(rtype);
+            }
+
+            let csync = RData::CSYNC(hickory_proto::rr::rdata::CSYNC::new(
+                soa_serial,
+                flags & 0x0001 != 0, // immediate
+                flags & 0x0002 != 0, // soaminimum
+                type_bit_maps,
+            ));
+            if flags & !0x0003 == 0 {
+                csync
             } else {
-                // For other tags, use a simple issue record
-                hickory_proto::rr::rdata::CAA::new_issue(flags & 0x80 != 0, None, vec![])
-            };
-            RData::CAA(caa)
+                // hickory only models the two defined flags; keep the others
+                // by storing the wire form with the raw flags field
+                let mut wire = rdata_to_wire(&csync)?;
+                wire[4..6].copy_from_slice(&flags.to_be_bytes());
+                opaque_rdata(RecordType::CSYNC, wire)
+            }
         }
         "DNSKEY" => {
-            if parts.len() < idx + 4 {
-                return Ok(None);
-            }
+            need(4)?;
             let flags = parts[idx]
                 .parse::<u16>()
-                .context(format!("Invalid DNSKEY flags on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid DNSKEY flags"))?;
             let _protocol = parts[idx + 1]
                 .parse::<u8>()
-                .context(format!("Invalid DNSKEY protocol on line {}", line_num + 1))?;
+                .context(at(idx + 1).located("Invalid DNSKEY protocol"))?;
             let algorithm = parts[idx + 2]
                 .parse::<u8>()
-                .context(format!("Invalid DNSKEY algorithm on line {}", line_num + 1))?;
+                .context(at(idx + 2).located("Invalid DNSKEY algorithm"))?;
 
-            // Public key is base64 encoded, join remaining parts
-            let public_key_b64 = parts[idx + 3..].join("");
-            let public_key = match base64::Engine::decode(
-                &base64::engine::general_purpose::STANDARD,
-                &public_key_b64,
-            ) {
-                Ok(key) => key,
-                Err(_) => {
-                    tracing::warn!("Invalid base64 in DNSKEY on line {}", line_num + 1);
-                    return Ok(None);
-                }
-            };
+            let public_key = decode_base64("DNSKEY", &parts[idx + 3..], at(idx + 3))?;
 
             RData::DNSSEC(hickory_proto::rr::dnssec::rdata::DNSSECRData::DNSKEY(
                 hickory_proto::rr::dnssec::rdata::DNSKEY::new(
@@ -528,52 +1616,32 @@ fn parse_resource_record(
         }
         "RRSIG" => {
             // RRSIG: type_covered algorithm labels original_ttl sig_expiration sig_inception key_tag signer_name signature
-            if parts.len() < idx + 9 {
-                return Ok(None);
-            }
+            need(9)?;
 
-            let type_covered = RecordType::from_str(parts[idx]).context(format!(
-                "Invalid RRSIG type_covered on line {}",
-                line_num + 1
-            ))?;
+            let type_covered = parse_record_type(parts[idx])
+                .context(at(idx).located("Invalid RRSIG type_covered"))?;
             let algorithm = parts[idx + 1]
                 .parse::<u8>()
-                .context(format!("Invalid RRSIG algorithm on line {}", line_num + 1))?;
+                .context(at(idx + 1).located("Invalid RRSIG algorithm"))?;
             let labels = parts[idx + 2]
                 .parse::<u8>()
-                .context(format!("Invalid RRSIG labels on line {}", line_num + 1))?;
-            let original_ttl = parts[idx + 3].parse::<u32>().context(format!(
-                "Invalid RRSIG original_ttl on line {}",
-                line_num + 1
-            ))?;
-            let sig_expiration = parts[idx + 4].parse::<u32>().context(format!(
-                "Invalid RRSIG sig_expiration on line {}",
-                line_num + 1
-            ))?;
-            let sig_inception = parts[idx + 5].parse::<u32>().context(format!(
-                "Invalid RRSIG sig_inception on line {}",
-                line_num + 1
-            ))?;
+                .context(at(idx + 2).located("Invalid RRSIG labels"))?;
+            let original_ttl = parts[idx + 3]
+                .parse::<u32>()
+                .context(at(idx + 3).located("Invalid RRSIG original_ttl"))?;
+            let sig_expiration = parts[idx + 4]
+                .parse::<u32>()
+                .context(at(idx + 4).located("Invalid RRSIG sig_expiration"))?;
+            let sig_inception = parts[idx + 5]
+                .parse::<u32>()
+                .context(at(idx + 5).located("Invalid RRSIG sig_inception"))?;
             let key_tag = parts[idx + 6]
                 .parse::<u16>()
-                .context(format!("Invalid RRSIG key_tag on line {}", line_num + 1))?;
-            let signer_name = parse_domain_name(parts[idx + 7], origin).context(format!(
-                "Invalid RRSIG signer_name on line {}",
-                line_num + 1
-            ))?;
-
-            // Signature is base64 encoded, join remaining parts
-            let signature_b64 = parts[idx + 8..].join("");
-            let signature = match base64::Engine::decode(
-                &base64::engine::general_purpose::STANDARD,
-                &signature_b64,
-            ) {
-                Ok(sig) => sig,
-                Err(_) => {
-                    tracing::warn!("Invalid base64 in RRSIG on line {}", line_num + 1);
-                    return Ok(None);
-                }
-            };
+                .context(at(idx + 6).located("Invalid RRSIG key_tag"))?;
+            let signer_name = parse_domain_name(parts[idx + 7], origin)
+                .context(at(idx + 7).located("Invalid RRSIG signer_name"))?;
+
+            let signature = decode_base64("RRSIG", &parts[idx + 8..], at(idx + 8))?;
 
             RData::DNSSEC(hickory_proto::rr::dnssec::rdata::DNSSECRData::SIG(
                 hickory_proto::rr::dnssec::rdata::SIG::new(
@@ -591,19 +1659,15 @@ fn parse_resource_record(
         }
         "NSEC" => {
             // NSEC: next_domain_name type_bit_maps
-            if parts.len() < idx + 2 {
-                return Ok(None);
-            }
+            need(2)?;
 
-            let next_domain_name = parse_domain_name(parts[idx], origin).context(format!(
-                "Invalid NSEC next_domain_name on line {}",
-                line_num + 1
-            ))?;
+            let next_domain_name = parse_domain_name(parts[idx], origin)
+                .context(at(idx).located("Invalid NSEC next_domain_name"))?;
 
             // Parse type bit maps - simplified version, just parse the record types
             let mut type_bit_maps = Vec::new();
             for part in &parts[idx + 1..] {
-                if let Ok(rtype) = RecordType::from_str(part) {
+                if let Ok(rtype) = parse_record_type(part) {
                     type_bit_maps.push(rtype);
                 }
             }
@@ -614,29 +1678,19 @@ fn parse_resource_record(
         }
         "DS" => {
             // DS: key_tag algorithm digest_type digest
-            if parts.len() < idx + 4 {
-                return Ok(None);
-            }
+            need(4)?;
 
             let key_tag = parts[idx]
                 .parse::<u16>()
-                .context(format!("Invalid DS key_tag on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid DS key_tag"))?;
             let algorithm = parts[idx + 1]
                 .parse::<u8>()
-                .context(format!("Invalid DS algorithm on line {}", line_num + 1))?;
+                .context(at(idx + 1).located("Invalid DS algorithm"))?;
             let digest_type = parts[idx + 2]
                 .parse::<u8>()
-                .context(format!("Invalid DS digest_type on line {}", line_num + 1))?;
-
-            // Digest is hex encoded
-            let digest_hex = parts[idx + 3..].join("");
-            let digest = match hex::decode(&digest_hex) {
-                Ok(d) => d,
-                Err(_) => {
-                    tracing::warn!("Invalid hex in DS on line {}", line_num + 1);
-                    return Ok(None);
-                }
-            };
+                .context(at(idx + 2).located("Invalid DS digest_type"))?;
+
+            let digest = decode_hex("DS", &parts[idx + 3..], at(idx + 3))?;
 
             RData::DNSSEC(hickory_proto::rr::dnssec::rdata::DNSSECRData::DS(
                 hickory_proto::rr::dnssec::rdata::DS::new(
@@ -648,68 +1702,59 @@ fn parse_resource_record(
             ))
         }
         "NAPTR" => {
-            if parts.len() < idx + 6 {
-                return Ok(None);
-            }
+            need(6)?;
             let order = parts[idx]
                 .parse::<u16>()
-                .context(format!("Invalid NAPTR order on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid NAPTR order"))?;
             let preference = parts[idx + 1]
                 .parse::<u16>()
-                .context(format!("Invalid NAPTR preference on line {}", line_num + 1))?;
-            let flags = parts[idx + 2]
-                .trim_matches('"')
-                .as_bytes()
-                .to_vec()
-                .into_boxed_slice();
-            let services = parts[idx + 3]
-                .trim_matches('"')
-                .as_bytes()
-                .to_vec()
-                .into_boxed_slice();
-            let regexp = parts[idx + 4]
-                .trim_matches('"')
-                .as_bytes()
-                .to_vec()
-                .into_boxed_slice();
-            let replacement = parse_domain_name(parts[idx + 5], origin).context(format!(
-                "Invalid NAPTR replacement on line {}",
-                line_num + 1
-            ))?;
+                .context(at(idx + 1).located("Invalid NAPTR preference"))?;
+            let flags = parse_character_string(parts[idx + 2])
+                .context(at(idx + 2).located("Invalid NAPTR flags"))?;
+            if flags.len() > MAX_CHARACTER_STRING {
+                return Err(at(idx + 2)
+                    .located("NAPTR flags longer than 255 bytes")
+                    .into());
+            }
+            let services = parse_character_string(parts[idx + 3])
+                .context(at(idx + 3).located("Invalid NAPTR services"))?;
+            if services.len() > MAX_CHARACTER_STRING {
+                return Err(at(idx + 3)
+                    .located("NAPTR services longer than 255 bytes")
+                    .into());
+            }
+            let regexp = parse_character_string(parts[idx + 4])
+                .context(at(idx + 4).located("Invalid NAPTR regexp"))?;
+            if regexp.len() > MAX_CHARACTER_STRING {
+                return Err(at(idx + 4)
+                    .located("NAPTR regexp longer than 255 bytes")
+                    .into());
+            }
+            let replacement = parse_domain_name(parts[idx + 5], origin)
+                .context(at(idx + 5).located("Invalid NAPTR replacement"))?;
 
             RData::NAPTR(hickory_proto::rr::rdata::NAPTR::new(
                 order,
                 preference,
-                flags,
-                services,
-                regexp,
+                flags.into_boxed_slice(),
+                services.into_boxed_slice(),
+                regexp.into_boxed_slice(),
                 replacement,
             ))
         }
         "TLSA" => {
-            if parts.len() < idx + 4 {
-                return Ok(None);
-            }
+            need(4)?;
             let cert_usage = parts[idx]
                 .parse::<u8>()
-                .context(format!("Invalid TLSA usage on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid TLSA usage"))?;
             let selector = parts[idx + 1]
                 .parse::<u8>()
-                .context(format!("Invalid TLSA selector on line {}", line_num + 1))?;
-            let matching_type = parts[idx + 2].parse::<u8>().context(format!(
-                "Invalid TLSA matching_type on line {}",
-                line_num + 1
-            ))?;
-
-            // Certificate data is hex encoded, join remaining parts
-            let cert_hex = parts[idx + 3..].join("");
-            let cert_data = match hex::decode(&cert_hex) {
-                Ok(d) => d,
-                Err(_) => {
-                    tracing::warn!("Invalid hex in TLSA on line {}", line_num + 1);
-                    return Ok(None);
-                }
-            };
+                .context(at(idx + 1).located("Invalid TLSA selector"))?;
+            let matching_type = parts[idx + 2]
+                .parse::<u8>()
+                .context(at(idx + 2).located("Invalid TLSA matching_type"))?;
+
+            let cert_data = decode_hex("TLSA", &parts[idx + 3..], at(idx + 3))?;
 
             RData::TLSA(hickory_proto::rr::rdata::TLSA::new(
                 cert_usage.into(),
@@ -719,25 +1764,15 @@ fn parse_resource_record(
             ))
         }
         "SSHFP" => {
-            if parts.len() < idx + 3 {
-                return Ok(None);
-            }
+            need(3)?;
             let algorithm = parts[idx]
                 .parse::<u8>()
-                .context(format!("Invalid SSHFP algorithm on line {}", line_num + 1))?;
+                .context(at(idx).located("Invalid SSHFP algorithm"))?;
             let fp_type = parts[idx + 1]
                 .parse::<u8>()
-                .context(format!("Invalid SSHFP fp_type on line {}", line_num + 1))?;
-
-            // Fingerprint is hex encoded
-            let fp_hex = parts[idx + 2..].join("");
-            let fingerprint = match hex::decode(&fp_hex) {
-                Ok(fp) => fp,
-                Err(_) => {
-                    tracing::warn!("Invalid hex in SSHFP on line {}", line_num + 1);
-                    return Ok(None);
-                }
-            };
+                .context(at(idx + 1).located("Invalid SSHFP fp_type"))?;
+
+            let fingerprint = decode_hex("SSHFP", &parts[idx + 2..], at(idx + 2))?;
 
             RData::SSHFP(hickory_proto::rr::rdata::SSHFP::new(
                 algorithm.into(),
@@ -746,7 +1781,7 @@ fn parse_resource_record(
             ))
         }
         _ => {
-            tracing::warn!("Unsupported record type {} on line {}", rtype, line_num + 1);
+            warnings.push(at(rtype_idx).located(format!("Unsupported record type {}", rtype)));
             return Ok(None);
         }
     };
@@ -754,8 +1789,426 @@ fn parse_resource_record(
     Ok(Some(Record::from_rdata(name, ttl, rdata)))
 }
 
+/// Parses SVCB/HTTPS `key=value` parameters (RFC 9460 section 2.1) into
+/// wire order. Generic `keyNNNNN` parameters are rejected because hickory
+/// cannot encode their values faithfully.
+fn parse_svc_params(tokens: &[Token]) -> Result<Vec<(SvcParamKey, SvcParamValue)>> {
+    let mut params: Vec<(SvcParamKey, SvcParamValue)> = Vec::new();
+
+    for token in tokens {
+        let (key, value) = match token.text.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (token.text.as_str(), None),
+        };
+        let key = parse_svc_param_key(key).context(token.located("Invalid SvcParam"))?;
+        let value = value.map(svc_value_list).transpose()?;
+
+        let param = match (key, value) {
+            (SvcParamKey::NoDefaultAlpn, None) => SvcParamValue::NoDefaultAlpn,
+            (SvcParamKey::NoDefaultAlpn, Some(_)) => {
+                return Err(token.located("no-default-alpn takes no value").into());
+            }
+            (key, None) => return Err(token.located(format!("Missing value for {}", key)).into()),
+            (key, Some(values)) => parse_svc_param_value(key, values)
+                .context(token.located(format!("Invalid {} value", key)))?,
+        };
+
+        if params.iter().any(|(existing, _)| *existing == key) {
+            return Err(token.located(format!("Duplicate SvcParam {}", key)).into());
+        }
+        params.push((key, param));
+    }
+
+    // Keys appear in increasing numeric order on the wire
+    params.sort_by_key(|(key, _)| u16::from(*key));
+
+    let has = |key: SvcParamKey| params.iter().any(|(existing, _)| *existing == key);
+    if has(SvcParamKey::NoDefaultAlpn) && !has(SvcParamKey::Alpn) {
+        anyhow::bail!("no-default-alpn requires alpn");
+    }
+    if let Some((_, SvcParamValue::Mandatory(mandatory))) = params.first() {
+        for key in &mandatory.0 {
+            if *key == SvcParamKey::Mandatory {
+                anyhow::bail!("mandatory must not list itself");
+            }
+            if !has(*key) {
+                anyhow::bail!("Mandatory SvcParam {} is missing", key);
+            }
+        }
+    }
+
+    Ok(params)
+}
+
+fn parse_svc_param_key(key: &str) -> Result<SvcParamKey> {
+    Ok(match key.to_ascii_lowercase().as_str() {
+        "mandatory" => SvcParamKey::Mandatory,
+        "alpn" => SvcParamKey::Alpn,
+        "no-default-alpn" => SvcParamKey::NoDefaultAlpn,
+        "port" => SvcParamKey::Port,
+        "ipv4hint" => SvcParamKey::Ipv4Hint,
+        "ech" => SvcParamKey::EchConfig,
+        "ipv6hint" => SvcParamKey::Ipv6Hint,
+        other => anyhow::bail!("Unsupported SvcParam {}", other),
+    })
+}
+
+/// Splits a SvcParam value on unescaped commas and decodes each item as a
+/// character-string. The whole value may be quoted.
+fn svc_value_list(value: &str) -> Result<Vec<Vec<u8>>> {
+    let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    };
+
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                item.push(c);
+                item.extend(chars.next());
+            }
+            ',' => items.push(parse_character_string(&std::mem::take(&mut item))?),
+            c => item.push(c),
+        }
+    }
+    items.push(parse_character_string(&item)?);
+    Ok(items)
+}
+
+fn parse_svc_param_value(key: SvcParamKey, values: Vec<Vec<u8>>) -> Result<SvcParamValue> {
+    let single = |values: &[Vec<u8>]| -> Result<String> {
+        match values {
+            [value] => Ok(String::from_utf8(value.clone())?),
+            _ => anyhow::bail!("Expected a single value"),
+        }
+    };
+    let strings = |values: Vec<Vec<u8>>| -> Result<Vec<String>> {
+        values
+            .into_iter()
+            .map(|v| Ok(String::from_utf8(v)?))
+            .collect()
+    };
+
+    Ok(match key {
+        SvcParamKey::Mandatory => {
+            let mut keys = strings(values)?
+                .iter()
+                .map(|k| parse_svc_param_key(k))
+                .collect::<Result<Vec<_>>>()?;
+            // Listed keys are in increasing numeric order on the wire
+            keys.sort_by_key(|key| u16::from(*key));
+            if keys.windows(2).any(|pair| pair[0] == pair[1]) {
+                anyhow::bail!("Duplicate key in mandatory list");
+            }
+            SvcParamValue::Mandatory(svcb::Mandatory(keys))
+        }
+        SvcParamKey::Alpn => {
+            if values
+                .iter()
+                .any(|v| v.is_empty() || v.len() > MAX_CHARACTER_STRING)
+            {
+                anyhow::bail!("ALPN identifiers must be 1 to 255 bytes");
+            }
+            SvcParamValue::Alpn(svcb::Alpn(strings(values)?))
+        }
+        SvcParamKey::Port => SvcParamValue::Port(single(&values)?.parse()?),
+        SvcParamKey::Ipv4Hint => SvcParamValue::Ipv4Hint(svcb::IpHint(
+            strings(values)?
+                .iter()
+                .map(|a| Ok(a.parse::<Ipv4Addr>()?.into()))
+                .collect::<Result<_>>()?,
+        )),
+        SvcParamKey::Ipv6Hint => SvcParamValue::Ipv6Hint(svcb::IpHint(
+            strings(values)?
+                .iter()
+                .map(|a| Ok(a.parse::<Ipv6Addr>()?.into()))
+                .collect::<Result<_>>()?,
+        )),
+        SvcParamKey::EchConfig => {
+            // The value is a base64 ECHConfigList, whose 2-byte length
+            // prefix hickory adds back itself when encoding
+            let list = base64::Engine::decode(
+                &base64::engine::general_purpose::STANDARD,
+                single(&values)?,
+            )?;
+            match list.split_first_chunk::<2>() {
+                Some((len, configs)) if u16::from_be_bytes(*len) as usize == configs.len() => {
+                    SvcParamValue::EchConfig(svcb::EchConfig(configs.to_vec()))
+                }
+                _ => anyhow::bail!("ECHConfigList length prefix does not match its data"),
+            }
+        }
+        other => anyhow::bail!("Unsupported SvcParam {}", other),
+    })
+}
+
+/// Decodes a base64 field, which may be split across several tokens
+fn decode_base64(field: &str, parts: &[&str], at: &Token) -> Result<Vec<u8>> {
+    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, parts.join(""))
+        .context(at.located(format!("Invalid base64 in {}", field)))
+}
+
+/// Decodes a hex field, which may be split across several tokens
+fn decode_hex(field: &str, parts: &[&str], at: &Token) -> Result<Vec<u8>> {
+    hex::decode(parts.join("")).context(at.located(format!("Invalid hex in {}", field)))
+}
+
+/// Wraps wire-format RDATA for a type hickory has no model for. It is
+/// stored and served as-is, like RFC 3597 generic RDATA.
+fn opaque_rdata(rtype: RecordType, wire: Vec<u8>) -> RData {
+    RData::Unknown {
+        code: rtype,
+        rdata: hickory_proto::rr::rdata::NULL::with(wire),
+    }
+}
+
+/// Decodes CDS/CDNSKEY wire data into hickory's model, keeping it opaque
+/// when hickory rejects it, e.g. the RFC 8078 "delete" form with algorithm 0
+fn dnssec_rdata_from_wire(rtype: RecordType, wire: Vec<u8>) -> RData {
+    rdata_from_wire(rtype, &wire).unwrap_or_else(|_| opaque_rdata(rtype, wire))
+}
+
+/// Parses a CERT type mnemonic (RFC 4398 section 2.1) or number
+fn parse_cert_type(s: &str) -> Result<u16> {
+    Ok(match s.to_ascii_uppercase().as_str() {
+        "PKIX" => 1,
+        "SPKI" => 2,
+        "PGP" => 3,
+        "IPKIX" => 4,
+        "ISPKI" => 5,
+        "IPGP" => 6,
+        "ACPKIX" => 7,
+        "IACPKIX" => 8,
+        "URI" => 253,
+        "OID" => 254,
+        _ => s.parse()?,
+    })
+}
+
+/// Checks a ZONEMD digest length (RFC 8976 section 2.2.4): SHA-384 and
+/// SHA-512 digests have fixed sizes, and no digest is shorter than 12 octets
+fn zonemd_digest_len_valid(hash_algorithm: u8, len: usize) -> bool {
+    let expected_len = match hash_algorithm {
+        1 => Some(48),
+        2 => Some(64),
+        _ => None,
+    };
+    len >= 12 && expected_len.is_none_or(|expected| len == expected)
+}
+
+/// Parses a decimal with at most `scale` fractional digits into an integer
+/// scaled by `10^scale`, e.g. `("1.5", 2)` gives 150
+fn parse_scaled_decimal(s: &str, scale: u32) -> Result<i64> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if whole.is_empty()
+        || fraction.len() > scale as usize
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        anyhow::bail!("Invalid number {}", s);
+    }
+
+    let padded = format!("{:0<width$}", fraction, width = scale as usize);
+    let fraction: i64 = if padded.is_empty() {
+        0
+    } else {
+        padded.parse()?
+    };
+    let value = whole
+        .parse::<i64>()?
+        .checked_mul(10_i64.pow(scale))
+        .and_then(|v| v.checked_add(fraction))
+        .ok_or_else(|| anyhow::anyhow!("Number {} out of range", s))?;
+    Ok(if negative { -value } else { value })
+}
+
+/// Encodes a LOC size or precision in centimetres as the RFC 1876
+/// mantissa/exponent byte
+fn loc_precision(s: &str) -> Result<u8> {
+    let mut cm = parse_scaled_decimal(s.trim_end_matches(['m', 'M']), 2)?;
+    if !(0..=9_000_000_000).contains(&cm) {
+        anyhow::bail!("LOC size/precision {} out of range", s);
+    }
+    let mut exponent = 0;
+    while cm >= 10 {
+        cm /= 10;
+        exponent += 1;
+    }
+    Ok(((cm as u8) << 4) | exponent)
+}
+
+/// Parses LOC presentation format (RFC 1876 section 3) into wire format:
+/// `d1 [m1 [s1]] N|S d2 [m2 [s2]] E|W alt[m] [size[m] [hp[m] [vp[m]]]]`
+fn parse_loc(tokens: &[Token]) -> Result<Vec<u8>> {
+    let mut rest = tokens.iter().map(|t| t.text.as_str());
+
+    // Thousandths of an arc second north/east of the equator/meridian
+    let mut coordinate = |positive: &str, negative: &str, max_degrees: i64| -> Result<u32> {
+        let mut fields = Vec::new();
+        let hemisphere = loop {
+            let Some(part) = rest.next() else {
+                anyhow::bail!("Missing {}/{} hemisphere", positive, negative);
+            };
+            if part.eq_ignore_ascii_case(positive) || part.eq_ignore_ascii_case(negative) {
+                break part.to_ascii_uppercase();
+            }
+            if fields.len() == 3 {
+                anyhow::bail!("Expected {} or {}, found {}", positive, negative, part);
+            }
+            fields.push(part);
+        };
+
+        let degrees: i64 = fields.first().context("Missing degrees")?.parse()?;
+        let minutes: i64 = fields.get(1).map_or(Ok(0), |m| m.parse())?;
+        let millis = fields
+            .get(2)
+            .map_or(Ok(0), |s| parse_scaled_decimal(s, 3))?;
+        if !(0..60).contains(&minutes) || !(0..60_000).contains(&millis) {
+            anyhow::bail!("Minutes and seconds must be below 60");
+        }
+        let offset = (degrees * 3600 + minutes * 60) * 1000 + millis;
+        if !(0..=max_degrees).contains(&degrees) || offset > max_degrees * 3_600_000 {
+            anyhow::bail!("Coordinate exceeds {} degrees", max_degrees);
+        }
+
+        let offset = if hemisphere == positive {
+            offset
+        } else {
+            -offset
+        };
+        Ok(((1_i64 << 31) + offset) as u32)
+    };
+
+    let latitude = coordinate("N", "S", 90)?;
+    let longitude = coordinate("E", "W", 180)?;
+
+    let altitude = rest.next().context("Missing altitude")?;
+    // Centimetres above a base 100,000m below the WGS 84 reference spheroid
+    let altitude = parse_scaled_decimal(altitude.trim_end_matches(['m', 'M']), 2)? + 10_000_000;
+    let altitude = u32::try_from(altitude).map_err(|_| anyhow::anyhow!("Altitude out of range"))?;
+
+    // Defaults: 1m size, 10,000m horizontal and 10m vertical precision
+    let mut precisions = [0x12, 0x16, 0x13];
+    for precision in precisions.iter_mut() {
+        match rest.next() {
+            Some(part) => *precision = loc_precision(part)?,
+            None => break,
+        }
+    }
+    if let Some(extra) = rest.next() {
+        anyhow::bail!("Unexpected {} after LOC precision", extra);
+    }
+
+    let mut wire = vec![0, precisions[0], precisions[1], precisions[2]];
+    wire.extend_from_slice(&latitude.to_be_bytes());
+    wire.extend_from_slice(&longitude.to_be_bytes());
+    wire.extend_from_slice(&altitude.to_be_bytes());
+    Ok(wire)
+}
+
+/// Decodes a character-string (RFC 1035 section 5.1): surrounding quotes are
+/// removed and `\X` and `\DDD` escapes are resolved to raw bytes
+fn parse_character_string(token: &str) -> Result<Vec<u8>> {
+    let inner = if token.len() >= 2 && token.starts_with('"') && token.ends_with('"') {
+        &token[1..token.len() - 1]
+    } else {
+        token
+    };
+
+    let bytes = inner.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        match bytes.get(i..i + 3) {
+            Some(digits) if digits.iter().all(u8::is_ascii_digit) => {
+                let value: u16 = std::str::from_utf8(digits)?.parse()?;
+                let byte = u8::try_from(value)
+                    .map_err(|_| anyhow::anyhow!("Escape \\{} out of range", value))?;
+                out.push(byte);
+                i += 3;
+            }
+            _ => match bytes.get(i) {
+                Some(&b) => {
+                    out.push(b);
+                    i += 1;
+                }
+                None => anyhow::bail!("Dangling escape in {}", token),
+            },
+        }
+    }
+
+    Ok(out)
+}
+
+/// Decodes wire-format RDATA, for types hickory only builds from the wire
+fn rdata_from_wire(rtype: RecordType, wire: &[u8]) -> Result<RData> {
+    let length = u16::try_from(wire.len()).context("RDATA longer than 65535 bytes")?;
+    let mut decoder = BinDecoder::new(wire);
+    let rdata = RData::read(&mut decoder, rtype, Restrict::new(length))?;
+    if !decoder.is_empty() {
+        anyhow::bail!("Trailing bytes after {} RDATA", rtype);
+    }
+    Ok(rdata)
+}
+
+/// Parses an SOA refresh/retry/expire field, which hickory stores as `i32`
+fn parse_soa_timer(s: &str) -> Result<i32> {
+    Ok(i32::try_from(parse_ttl(s)?)?)
+}
+
+/// Extracts the number from an RFC 3597 `TYPE###` or `CLASS###` name.
+/// Returns `None` when `s` is not of that form.
+fn generic_code(s: &str, prefix: &str) -> Option<Result<u16>> {
+    let digits = s
+        .get(..prefix.len())
+        .filter(|p| p.eq_ignore_ascii_case(prefix))
+        .map(|_| &s[prefix.len()..])?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(digits.parse().context(format!("{} out of range", s)))
+}
+
+/// Parses a type mnemonic (case-insensitive) or RFC 3597 `TYPE###` name, as
+/// used in RRSIG, NSEC and CSYNC RDATA
+fn parse_record_type(s: &str) -> Result<RecordType> {
+    if let Some(code) = generic_code(s, "TYPE") {
+        return Ok(RecordType::from(code?));
+    }
+    let upper = s.to_ascii_uppercase();
+    match OPAQUE_TYPE_NAMES.iter().find(|(name, _)| *name == upper) {
+        Some((_, code)) => Ok(RecordType::from(*code)),
+        None => Ok(RecordType::from_str(&upper)?),
+    }
+}
+
+fn is_class(s: &str) -> bool {
+    ["IN", "CH", "CS", "HS"]
+        .iter()
+        .any(|class| s.eq_ignore_ascii_case(class))
+        || generic_code(s, "CLASS").is_some()
+}
+
 fn parse_domain_name(s: &str, origin: &Name) -> Result<Name> {
-    if s.ends_with('.') {
+    if s == "@" {
+        Ok(origin.clone())
+    } else if s.ends_with('.') {
         Ok(Name::from_str(s)?)
     } else {
         Ok(Name::from_str(&format!("{}.{}", s, origin))?)
@@ -778,38 +2231,855 @@ fn extract_soa_data(record: &Record) -> Option<SoaRecord> {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_zone_store_find_zone() {
-        let mut store = ZoneStore::new();
-
-        let origin = Name::from_str("example.com.").unwrap();
-        let soa = SoaRecord {
-            mname: Name::from_str("ns1.example.com.").unwrap(),
-            rname: Name::from_str("admin.example.com.").unwrap(),
-            serial: 1,
-            refresh: 7200,
-            retry: 3600,
-            expire: 1209600,
-            minimum: 86400,
-        };
+/// Structured form of a zone, shared by the master file writer and the
+/// YAML/JSON zone format. Names and RDATA use master file presentation
+/// syntax, with names relative to the origin where possible, so a zone
+/// converts between the formats without loss.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZoneDocument {
+    pub origin: String,
+    pub soa: SoaDocument,
+    #[serde(default)]
+    pub rrsets: Vec<RrsetDocument>,
+}
 
-        let zone = Zone::new(origin, soa);
-        store.add_zone(zone);
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoaDocument {
+    /// TTL of the SOA record itself (default: `minimum`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    pub mname: String,
+    pub rname: String,
+    pub serial: u32,
+    pub refresh: i32,
+    pub retry: i32,
+    pub expire: i32,
+    pub minimum: u32,
+}
 
-        let query = Name::from_str("www.example.com.").unwrap();
-        assert!(store.find_zone(&query).is_some());
+/// Records sharing an owner, type and TTL, one RDATA string per record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RrsetDocument {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub rtype: String,
+    pub ttl: u32,
+    pub rdata: Vec<String>,
+}
 
-        let query = Name::from_str("example.org.").unwrap();
-        assert!(store.find_zone(&query).is_none());
-    }
+/// Builds the structured form of a zone in canonical order: owners in
+/// DNSSEC canonical order, then types by number, then records by their
+/// wire-format RDATA. The SOA is carried in `soa` rather than `rrsets`.
+pub fn zone_to_document(zone: &Zone) -> Result<ZoneDocument> {
+    let origin = &zone.origin;
+
+    // The SOA keeps the TTL it was loaded with; its RDATA comes from `zone.soa`
+    let soa_ttl = zone
+        .lookup(origin, RecordType::SOA)
+        .and_then(|records| records.first())
+        .map_or(zone.soa.minimum, Record::ttl);
+    let soa = SoaDocument {
+        ttl: Some(soa_ttl),
+        mname: format_name(&zone.soa.mname, origin),
+        rname: format_name(&zone.soa.rname, origin),
+        serial: zone.soa.serial,
+        refresh: zone.soa.refresh,
+        retry: zone.soa.retry,
+        expire: zone.soa.expire,
+        minimum: zone.soa.minimum,
+    };
 
-    #[test]
-    fn test_wildcard_lookup() {
-        let origin = Name::from_str("example.com.").unwrap();
+    let mut rrsets: Vec<RrsetDocument> = Vec::new();
+    let mut names: Vec<&Name> = zone.records.keys().collect();
+    names.sort();
+    for name in names {
+        let mut types: Vec<_> = zone.records[name]
+            .iter()
+            .filter(|(rtype, _)| **rtype != RecordType::SOA)
+            .collect();
+        types.sort_by_key(|(rtype, _)| u16::from(**rtype));
+
+        for (_, records) in types {
+            // Records within an RRset are ordered by their wire-format RDATA
+            let mut sorted = Vec::with_capacity(records.len());
+            for record in records {
+                let Some(rdata) = record.data() else {
+                    continue;
+                };
+                sorted.push((rdata_to_wire(rdata)?, record));
+            }
+            sorted.sort_by(|a, b| a.0.cmp(&b.0));
+
+            let owner = format_name(name, origin);
+            for (_, record) in sorted {
+                let (rtype, data) = format_record_data(record, origin)?;
+                // Signatures covering different types may have different
+                // TTLs, so a change of TTL starts a new entry
+                match rrsets.last_mut() {
+                    Some(last)
+                        if last.name == owner
+                            && last.rtype == rtype
+                            && last.ttl == record.ttl() =>
+                    {
+                        last.rdata.push(data);
+                    }
+                    _ => rrsets.push(RrsetDocument {
+                        name: owner.clone(),
+                        rtype,
+                        ttl: record.ttl(),
+                        rdata: vec![data],
+                    }),
+                }
+            }
+        }
+    }
+
+    Ok(ZoneDocument {
+        origin: format_absolute_name(origin),
+        soa,
+        rrsets,
+    })
+}
+
+/// Writes a zone as a master file in canonical form: `$ORIGIN` first, then
+/// the SOA, then every other record in the order of `zone_to_document`, one
+/// record per line with an explicit TTL and class.
+pub fn write_zone(zone: &Zone) -> Result<String> {
+    let doc = zone_to_document(zone)?;
+    let soa = &doc.soa;
+    let mut out = format!("$ORIGIN {}\n", doc.origin);
+    out.push_str(&format!(
+        "@\t{}\tIN\tSOA\t{} {} {} {} {} {} {}\n",
+        soa.ttl.unwrap_or(soa.minimum),
+        soa.mname,
+        soa.rname,
+        soa.serial,
+        soa.refresh,
+        soa.retry,
+        soa.expire,
+        soa.minimum
+    ));
+    for rrset in &doc.rrsets {
+        for data in &rrset.rdata {
+            out.push_str(&format!(
+                "{}\t{}\tIN\t{}\t{}\n",
+                rrset.name, rrset.ttl, rrset.rtype, data
+            ));
+        }
+    }
+    Ok(out)
+}
+
+/// Writes a zone in the given format
+pub fn write_zone_as(zone: &Zone, format: ZoneFormat) -> Result<String> {
+    match format {
+        ZoneFormat::Master => write_zone(zone),
+        ZoneFormat::Yaml => Ok(serde_yaml::to_string(&zone_to_document(zone)?)?),
+        ZoneFormat::Json => {
+            let mut out = serde_json::to_string_pretty(&zone_to_document(zone)?)?;
+            out.push('\n');
+            Ok(out)
+        }
+    }
+}
+
+/// Loads a zone file in the given format
+pub fn load_zone_file<P: AsRef<Path>>(
+    path: P,
+    origin_name: &str,
+    format: ZoneFormat,
+) -> Result<Zone> {
+    let path = path.as_ref();
+    let doc: ZoneDocument = match format {
+        ZoneFormat::Master => return parse_zone_file(path, origin_name),
+        ZoneFormat::Yaml => {
+            let content = std::fs::read_to_string(path).context("Failed to read zone file")?;
+            serde_yaml::from_str(&content)
+                .context(format!("Invalid YAML zone document {}", path.display()))?
+        }
+        ZoneFormat::Json => {
+            let content = std::fs::read_to_string(path).context("Failed to read zone file")?;
+            serde_json::from_str(&content)
+                .context(format!("Invalid JSON zone document {}", path.display()))?
+        }
+    };
+    zone_from_document(&doc, origin_name)
+}
+
+/// Like `load_zone_file`, also returning every file the zone was read from
+pub fn load_zone_file_with_sources<P: AsRef<Path>>(
+    path: P,
+    origin_name: &str,
+    format: ZoneFormat,
+) -> Result<(Zone, Vec<PathBuf>)> {
+    let path = path.as_ref();
+    match format {
+        ZoneFormat::Master => parse_zone_file_with_sources(path, origin_name),
+        ZoneFormat::Yaml | ZoneFormat::Json => Ok((
+            load_zone_file(path, origin_name, format)?,
+            vec![path.to_path_buf()],
+        )),
+    }
+}
+
+/// Builds a zone from its structured form. RDATA is parsed with the master
+/// file grammar; every invalid RRset is reported, not just the first.
+pub fn zone_from_document(doc: &ZoneDocument, origin_name: &str) -> Result<Zone> {
+    let origin = Name::from_str(origin_name).context("Invalid origin name")?;
+    let doc_origin = Name::from_str(&doc.origin).context("Invalid document origin")?;
+    if doc_origin != origin {
+        anyhow::bail!(
+            "Document origin {} does not match zone {}",
+            doc_origin,
+            origin
+        );
+    }
+
+    let soa = SoaRecord {
+        mname: parse_domain_name(&doc.soa.mname, &origin).context("Invalid SOA mname")?,
+        rname: parse_domain_name(&doc.soa.rname, &origin).context("Invalid SOA rname")?,
+        serial: doc.soa.serial,
+        refresh: doc.soa.refresh,
+        retry: doc.soa.retry,
+        expire: doc.soa.expire,
+        minimum: doc.soa.minimum,
+    };
+    let mut zone = Zone::new(origin.clone(), soa);
+    let mut soa_record = zone.get_soa_record();
+    if let Some(ttl) = doc.soa.ttl {
+        soa_record.set_ttl(ttl);
+    }
+    zone.add_record(soa_record);
+
+    let mut errors = Vec::new();
+    for (i, rrset) in doc.rrsets.iter().enumerate() {
+        for data in &rrset.rdata {
+            match parse_document_record(rrset, data, &origin) {
+                Ok(record) => zone.add_record(record),
+                Err(e) => errors.push(format!(
+                    "rrsets[{}] {} {} {:?}: {}",
+                    i, rrset.name, rrset.rtype, data, e
+                )),
+            }
+        }
+    }
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "{} error(s) in zone document:\n{}",
+            errors.len(),
+            errors.join("\n")
+        );
+    }
+
+    Ok(zone)
+}
+
+/// Parses one RDATA string of a structured RRset as if it were a master
+/// file line. Errors leave out the position within that synthesized line.
+fn parse_document_record(rrset: &RrsetDocument, data: &str, origin: &Name) -> Result<Record> {
+    if rrset.rtype.eq_ignore_ascii_case("SOA") {
+        anyhow::bail!("The SOA belongs in the soa section");
+    }
+
+    let line = format!("{} {} IN {} {}", rrset.name, rrset.ttl, rrset.rtype, data);
+    let entries = tokenize_zone(&line).map_err(|e| anyhow::anyhow!(error_message(&e)))?;
+    let [entry] = entries.as_slice() else {
+        anyhow::bail!("RDATA must be a single record");
+    };
+
+    let mut warnings = Vec::new();
+    let record =
+        parse_resource_record(entry, origin, &mut RecordDefaults::default(), &mut warnings)
+            .map_err(|e| anyhow::anyhow!(error_message(&e)))?;
+    let record = match record {
+        Some(record) => record,
+        None => match warnings.pop() {
+            Some(warning) => anyhow::bail!(warning.message),
+            None => anyhow::bail!("Unsupported record type {}", rrset.rtype),
+        },
+    };
+    if !origin.zone_of(record.name()) {
+        anyhow::bail!("{} is outside the zone", record.name());
+    }
+    Ok(record)
+}
+
+/// Formats a record's type mnemonic and RDATA. RDATA without a presentation
+/// form this parser accepts is written in the RFC 3597 generic syntax.
+fn format_record_data(record: &Record, origin: &Name) -> Result<(String, String)> {
+    let Some(rdata) = record.data() else {
+        anyhow::bail!("Record {} has no RDATA", record.name());
+    };
+
+    Ok(match format_rdata(rdata, origin) {
+        Some(data) => (format_rdata_type(rdata), data),
+        None => {
+            let wire = rdata_to_wire(rdata)?;
+            let rtype = match u16::from(rdata.record_type()) {
+                // Signatures are stored as SIG but written as RRSIG
+                24 => u16::from(RecordType::RRSIG),
+                code => code,
+            };
+            let data = if wire.is_empty() {
+                "\\# 0".to_string()
+            } else {
+                format!("\\# {} {}", wire.len(), hex::encode_upper(&wire))
+            };
+            (format!("TYPE{}", rtype), data)
+        }
+    })
+}
+
+/// The mnemonic a record is written under, matching what the parser accepts
+fn format_rdata_type(rdata: &RData) -> String {
+    match rdata {
+        RData::ANAME(_) => "ALIAS".to_string(),
+        RData::DNSSEC(hickory_proto::rr::dnssec::rdata::DNSSECRData::SIG(_)) => "RRSIG".to_string(),
+        _ => format_record_type(rdata.record_type()),
+    }
+}
+
+/// Formats a type as its mnemonic, or `TYPE###` when it has none
+fn format_record_type(rtype: RecordType) -> String {
+    let code = u16::from(rtype);
+    if let Some((name, _)) = OPAQUE_TYPE_NAMES.iter().find(|(_, c)| *c == code) {
+        return name.to_string();
+    }
+    match rtype {
+        RecordType::Unknown(_) => format!("TYPE{}", code),
+        known => known.to_string(),
+    }
+}
+
+/// Formats RDATA in presentation format, or returns None when the parser
+/// has no presentation form that reproduces it exactly
+fn format_rdata(rdata: &RData, origin: &Name) -> Option<String> {
+    use hickory_proto::rr::dnssec::rdata::DNSSECRData;
+
+    let name = |n: &Name| format_name(n, origin);
+    Some(match rdata {
+        RData::A(a) => a.0.to_string(),
+        RData::AAAA(aaaa) => aaaa.0.to_string(),
+        RData::NS(ns) => name(&ns.0),
+        RData::CNAME(cname) => name(&cname.0),
+        RData::PTR(ptr) => name(&ptr.0),
+        RData::ANAME(aname) => name(&aname.0),
+        RData::SOA(soa) => format!(
+            "{} {} {} {} {} {} {}",
+            name(soa.mname()),
+            name(soa.rname()),
+            soa.serial(),
+            soa.refresh(),
+            soa.retry(),
+            soa.expire(),
+            soa.minimum()
+        ),
+        RData::MX(mx) => format!("{} {}", mx.preference(), name(mx.exchange())),
+        RData::TXT(txt) => {
+            if txt.txt_data().is_empty() {
+                return None;
+            }
+            txt.txt_data()
+                .iter()
+                .map(|s| format_character_string(s))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        RData::SRV(srv) => format!(
+            "{} {} {} {}",
+            srv.priority(),
+            srv.weight(),
+            srv.port(),
+            name(srv.target())
+        ),
+        RData::CAA(_) => {
+            // The tag and value are written exactly as they appear on the wire
+            let wire = rdata_to_wire(rdata).ok()?;
+            let (&[flags, tag_len], rest) = wire.split_first_chunk::<2>()?;
+            let (tag, value) = rest.split_at_checked(tag_len as usize)?;
+            if tag.is_empty() || tag.len() > 15 || !tag.iter().all(u8::is_ascii_alphanumeric) {
+                return None;
+            }
+            format!(
+                "{} {} {}",
+                flags,
+                String::from_utf8_lossy(tag),
+                format_character_string(value)
+            )
+        }
+        RData::SVCB(svcb) => format_svcb(svcb, origin)?,
+        RData::HTTPS(https) => format_svcb(&https.0, origin)?,
+        RData::HINFO(hinfo) => format!(
+            "{} {}",
+            format_character_string(hinfo.cpu()),
+            format_character_string(hinfo.os())
+        ),
+        RData::NAPTR(naptr) => format!(
+            "{} {} {} {} {} {}",
+            naptr.order(),
+            naptr.preference(),
+            format_character_string(naptr.flags()),
+            format_character_string(naptr.services()),
+            format_character_string(naptr.regexp()),
+            name(naptr.replacement())
+        ),
+        RData::TLSA(tlsa) => format!(
+            "{} {} {} {}",
+            u8::from(tlsa.cert_usage()),
+            u8::from(tlsa.selector()),
+            u8::from(tlsa.matching()),
+            format_hex(tlsa.cert_data())?
+        ),
+        RData::SSHFP(sshfp) => format!(
+            "{} {} {}",
+            u8::from(sshfp.algorithm()),
+            u8::from(sshfp.fingerprint_type()),
+            format_hex(sshfp.fingerprint())?
+        ),
+        RData::OPENPGPKEY(key) => format_base64(key.public_key())?,
+        RData::CSYNC(csync) => {
+            // hickory exposes no accessor for the serial
+            let wire = rdata_to_wire(rdata).ok()?;
+            let serial = u32::from_be_bytes(*wire.first_chunk::<4>()?);
+            format_csync(serial, csync.flags(), csync.type_bit_maps())
+        }
+        RData::DNSSEC(DNSSECRData::DNSKEY(key)) => format_dnskey(key)?,
+        RData::DNSSEC(DNSSECRData::CDNSKEY(key)) => format_dnskey(key)?,
+        RData::DNSSEC(DNSSECRData::DS(ds)) => format_ds(ds)?,
+        RData::DNSSEC(DNSSECRData::CDS(ds)) => format_ds(ds)?,
+        RData::DNSSEC(DNSSECRData::SIG(sig)) => format_sig(sig, origin)?,
+        RData::DNSSEC(DNSSECRData::RRSIG(sig)) => format_sig(sig, origin)?,
+        RData::DNSSEC(DNSSECRData::NSEC(nsec)) => {
+            let mut out = name(nsec.next_domain_name());
+            for rtype in nsec.type_bit_maps() {
+                out.push(' ');
+                out.push_str(&format_record_type(*rtype));
+            }
+            out
+        }
+        RData::Unknown { code, rdata } => {
+            format_opaque_rdata(u16::from(*code), rdata.anything(), origin)?
+        }
+        _ => return None,
+    })
+}
+
+/// Formats RDATA stored opaquely by `opaque_rdata`
+fn format_opaque_rdata(code: u16, wire: &[u8], origin: &Name) -> Option<String> {
+    use hickory_proto::serialize::binary::BinDecodable;
+
+    Some(match code {
+        TYPE_RP => {
+            let mut decoder = BinDecoder::new(wire);
+            let mbox = Name::read(&mut decoder).ok()?;
+            let txt = Name::read(&mut decoder).ok()?;
+            if !decoder.is_empty() {
+                return None;
+            }
+            format!(
+                "{} {}",
+                format_name(&mbox, origin),
+                format_name(&txt, origin)
+            )
+        }
+        TYPE_LOC => format_loc(wire)?,
+        TYPE_URI => {
+            let (&[p0, p1, w0, w1], target) = wire.split_first_chunk::<4>()?;
+            if target.is_empty() {
+                return None;
+            }
+            format!(
+                "{} {} {}",
+                u16::from_be_bytes([p0, p1]),
+                u16::from_be_bytes([w0, w1]),
+                format_character_string(target)
+            )
+        }
+        TYPE_CERT => {
+            let (&[t0, t1, k0, k1, algorithm], cert) = wire.split_first_chunk::<5>()?;
+            format!(
+                "{} {} {} {}",
+                format_cert_type(u16::from_be_bytes([t0, t1])),
+                u16::from_be_bytes([k0, k1]),
+                algorithm,
+                format_base64(cert)?
+            )
+        }
+        TYPE_SMIMEA => {
+            let (&[usage, selector, matching_type], data) = wire.split_first_chunk::<3>()?;
+            format!(
+                "{} {} {} {}",
+                usage,
+                selector,
+                matching_type,
+                format_hex(data)?
+            )
+        }
+        TYPE_ZONEMD => {
+            let (&[s0, s1, s2, s3, scheme, hash_algorithm], digest) =
+                wire.split_first_chunk::<6>()?;
+            if !zonemd_digest_len_valid(hash_algorithm, digest.len()) {
+                return None;
+            }
+            format!(
+                "{} {} {} {}",
+                u32::from_be_bytes([s0, s1, s2, s3]),
+                scheme,
+                hash_algorithm,
+                format_hex(digest)?
+            )
+        }
+        // CDS/CDNSKEY forms hickory rejects, such as the RFC 8078 delete form
+        59 => {
+            let (&[k0, k1, algorithm, digest_type], digest) = wire.split_first_chunk::<4>()?;
+            format!(
+                "{} {} {} {}",
+                u16::from_be_bytes([k0, k1]),
+                algorithm,
+                digest_type,
+                format_hex(digest)?
+            )
+        }
+        60 => {
+            let (&[f0, f1, protocol, algorithm], key) = wire.split_first_chunk::<4>()?;
+            format!(
+                "{} {} {} {}",
+                u16::from_be_bytes([f0, f1]),
+                protocol,
+                algorithm,
+                format_base64(key)?
+            )
+        }
+        // CSYNC with flags hickory does not model
+        62 => {
+            let RData::CSYNC(csync) = rdata_from_wire(RecordType::CSYNC, wire).ok()? else {
+                return None;
+            };
+            let (&[s0, s1, s2, s3, f0, f1], _) = wire.split_first_chunk::<6>()?;
+            format_csync(
+                u32::from_be_bytes([s0, s1, s2, s3]),
+                u16::from_be_bytes([f0, f1]),
+                csync.type_bit_maps(),
+            )
+        }
+        _ => return None,
+    })
+}
+
+fn format_csync(serial: u32, flags: u16, types: &[RecordType]) -> String {
+    let mut out = format!("{} {}", serial, flags);
+    for rtype in types {
+        out.push(' ');
+        out.push_str(&format_record_type(*rtype));
+    }
+    out
+}
+
+fn format_dnskey(key: &hickory_proto::rr::dnssec::rdata::DNSKEY) -> Option<String> {
+    // The protocol field is always 3 (RFC 4034 section 2.1.2)
+    Some(format!(
+        "{} 3 {} {}",
+        key.flags(),
+        u8::from(key.algorithm()),
+        format_base64(key.public_key())?
+    ))
+}
+
+fn format_ds(ds: &hickory_proto::rr::dnssec::rdata::DS) -> Option<String> {
+    Some(format!(
+        "{} {} {} {}",
+        ds.key_tag(),
+        u8::from(ds.algorithm()),
+        u8::from(ds.digest_type()),
+        format_hex(ds.digest())?
+    ))
+}
+
+fn format_sig(sig: &hickory_proto::rr::dnssec::rdata::SIG, origin: &Name) -> Option<String> {
+    Some(format!(
+        "{} {} {} {} {} {} {} {} {}",
+        format_record_type(sig.type_covered()),
+        u8::from(sig.algorithm()),
+        sig.num_labels(),
+        sig.original_ttl(),
+        sig.sig_expiration(),
+        sig.sig_inception(),
+        sig.key_tag(),
+        format_name(sig.signer_name(), origin),
+        format_base64(sig.sig())?
+    ))
+}
+
+/// Formats SVCB/HTTPS RDATA (RFC 9460 section 2.1). Returns None for
+/// parameters the parser does not accept.
+fn format_svcb(svcb: &hickory_proto::rr::rdata::SVCB, origin: &Name) -> Option<String> {
+    let mut out = format!(
+        "{} {}",
+        svcb.svc_priority(),
+        format_name(svcb.target_name(), origin)
+    );
+
+    for (key, value) in svcb.svc_params() {
+        out.push(' ');
+        out.push_str(svc_param_key_name(*key)?);
+
+        let items: Vec<Vec<u8>> = match value {
+            SvcParamValue::NoDefaultAlpn => continue,
+            SvcParamValue::Mandatory(mandatory) => mandatory
+                .0
+                .iter()
+                .map(|key| svc_param_key_name(*key).map(|name| name.as_bytes().to_vec()))
+                .collect::<Option<_>>()?,
+            SvcParamValue::Alpn(alpn) => alpn.0.iter().map(|id| id.as_bytes().to_vec()).collect(),
+            SvcParamValue::Port(port) => vec![port.to_string().into_bytes()],
+            SvcParamValue::Ipv4Hint(hint) => hint
+                .0
+                .iter()
+                .map(|a| a.0.to_string().into_bytes())
+                .collect(),
+            SvcParamValue::Ipv6Hint(hint) => hint
+                .0
+                .iter()
+                .map(|aaaa| aaaa.0.to_string().into_bytes())
+                .collect(),
+            SvcParamValue::EchConfig(ech) => {
+                // Restore the ECHConfigList length prefix the parser strips
+                let mut list = u16::try_from(ech.0.len()).ok()?.to_be_bytes().to_vec();
+                list.extend_from_slice(&ech.0);
+                vec![format_base64(&list)?.into_bytes()]
+            }
+            SvcParamValue::Unknown(_) => return None,
+        };
+        if items.is_empty() {
+            return None;
+        }
+
+        let mut value = String::new();
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                value.push(',');
+            }
+            for &b in item {
+                push_escaped(&mut value, b, b"\"\\,");
+            }
+        }
+        out.push('=');
+        if value.contains([' ', ';', '(', ')']) {
+            out.push('"');
+            out.push_str(&value);
+            out.push('"');
+        } else {
+            out.push_str(&value);
+        }
+    }
+
+    Some(out)
+}
+
+/// The presentation name of a SvcParamKey, the inverse of `parse_svc_param_key`
+fn svc_param_key_name(key: SvcParamKey) -> Option<&'static str> {
+    Some(match key {
+        SvcParamKey::Mandatory => "mandatory",
+        SvcParamKey::Alpn => "alpn",
+        SvcParamKey::NoDefaultAlpn => "no-default-alpn",
+        SvcParamKey::Port => "port",
+        SvcParamKey::Ipv4Hint => "ipv4hint",
+        SvcParamKey::EchConfig => "ech",
+        SvcParamKey::Ipv6Hint => "ipv6hint",
+        _ => return None,
+    })
+}
+
+/// Formats a CERT type as its RFC 4398 mnemonic, the inverse of `parse_cert_type`
+fn format_cert_type(cert_type: u16) -> String {
+    match cert_type {
+        1 => "PKIX".to_string(),
+        2 => "SPKI".to_string(),
+        3 => "PGP".to_string(),
+        4 => "IPKIX".to_string(),
+        5 => "ISPKI".to_string(),
+        6 => "IPGP".to_string(),
+        7 => "ACPKIX".to_string(),
+        8 => "IACPKIX".to_string(),
+        253 => "URI".to_string(),
+        254 => "OID".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Formats LOC wire data in RFC 1876 presentation format, the inverse of
+/// `parse_loc`
+fn format_loc(wire: &[u8]) -> Option<String> {
+    let (&[version, size, horizontal, vertical], rest) = wire.split_first_chunk::<4>()?;
+    let fields: &[u8; 12] = rest.try_into().ok()?;
+    if version != 0 {
+        return None;
+    }
+
+    let coordinate = |raw: [u8; 4], positive: &str, negative: &str, max_degrees: i64| {
+        let offset = i64::from(u32::from_be_bytes(raw)) - (1_i64 << 31);
+        let millis = offset.abs();
+        if millis > max_degrees * 3_600_000 {
+            return None;
+        }
+        Some(format!(
+            "{} {} {}.{:03} {}",
+            millis / 3_600_000,
+            millis / 60_000 % 60,
+            millis / 1000 % 60,
+            millis % 1000,
+            if offset < 0 { negative } else { positive }
+        ))
+    };
+    let precision = |byte: u8| {
+        let (mantissa, exponent) = (byte >> 4, byte & 0x0f);
+        if mantissa > 9 || exponent > 9 || (mantissa == 0 && exponent != 0) {
+            return None;
+        }
+        Some(format_metres(
+            i64::from(mantissa) * 10_i64.pow(u32::from(exponent)),
+        ))
+    };
+
+    let altitude = i64::from(u32::from_be_bytes(fields[8..12].try_into().ok()?)) - 10_000_000;
+    Some(format!(
+        "{} {} {} {} {} {}",
+        coordinate(fields[0..4].try_into().ok()?, "N", "S", 90)?,
+        coordinate(fields[4..8].try_into().ok()?, "E", "W", 180)?,
+        format_metres(altitude),
+        precision(size)?,
+        precision(horizontal)?,
+        precision(vertical)?
+    ))
+}
+
+/// Formats a length in centimetres as metres, e.g. `-2450` as `-24.50m`
+fn format_metres(cm: i64) -> String {
+    let sign = if cm < 0 { "-" } else { "" };
+    let cm = cm.abs();
+    if cm % 100 == 0 {
+        format!("{}{}m", sign, cm / 100)
+    } else {
+        format!("{}{}.{:02}m", sign, cm / 100, cm % 100)
+    }
+}
+
+/// Formats bytes as a quoted character-string, escaping `"` and `\` and
+/// writing other non-printable bytes as `\DDD`
+fn format_character_string(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() + 2);
+    out.push('"');
+    for &b in bytes {
+        push_escaped(&mut out, b, b"\"\\");
+    }
+    out.push('"');
+    out
+}
+
+/// Appends `b`, backslash-escaping it if it is in `special` and writing it
+/// as a decimal `\DDD` escape if it is not printable ASCII
+fn push_escaped(out: &mut String, b: u8, special: &[u8]) {
+    if special.contains(&b) {
+        out.push('\\');
+        out.push(b as char);
+    } else if b.is_ascii_graphic() || b == b' ' {
+        out.push(b as char);
+    } else {
+        out.push_str(&format!("\\{:03}", b));
+    }
+}
+
+/// Formats a binary field as hex; empty fields have no presentation form
+fn format_hex(data: &[u8]) -> Option<String> {
+    (!data.is_empty()).then(|| hex::encode_upper(data))
+}
+
+/// Formats a binary field as base64; empty fields have no presentation form
+fn format_base64(data: &[u8]) -> Option<String> {
+    (!data.is_empty())
+        .then(|| base64::Engine::encode(&base64::engine::general_purpose::STANDARD, data))
+}
+
+/// Formats `name` relative to `origin` when it lies inside it (`@` for the
+/// origin itself), and as an absolute name otherwise
+fn format_name(name: &Name, origin: &Name) -> String {
+    if origin.is_root() || !origin.zone_of(name) {
+        return format_absolute_name(name);
+    }
+    if name == origin {
+        return "@".to_string();
+    }
+
+    // `num_labels` leaves out a leading wildcard, so count the labels directly
+    let relative = name.iter().count() - origin.iter().count();
+    format_labels(name.iter().take(relative))
+}
+
+fn format_absolute_name(name: &Name) -> String {
+    if name.is_root() {
+        return ".".to_string();
+    }
+    let mut out = format_labels(name.iter());
+    out.push('.');
+    out
+}
+
+/// Joins labels with dots, escaping characters that are special in names or
+/// in the master file grammar
+fn format_labels<'a>(labels: impl Iterator<Item = &'a [u8]>) -> String {
+    let mut out = String::new();
+    for (i, label) in labels.enumerate() {
+        if i > 0 {
+            out.push('.');
+        }
+        for &b in label {
+            push_escaped(&mut out, b, b".\\\"()@$; ");
+        }
+    }
+    out
+}
+
+/// Encodes RDATA to wire format without name compression
+fn rdata_to_wire(rdata: &RData) -> Result<Vec<u8>> {
+    let mut wire = Vec::new();
+    let mut encoder = hickory_proto::serialize::binary::BinEncoder::new(&mut wire);
+    encoder.set_canonical_names(true);
+    rdata.emit(&mut encoder)?;
+    Ok(wire)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_store_find_zone() {
+        let mut store = ZoneStore::new();
+
+        let origin = Name::from_str("example.com.").unwrap();
+        let soa = SoaRecord {
+            mname: Name::from_str("ns1.example.com.").unwrap(),
+            rname: Name::from_str("admin.example.com.").unwrap(),
+            serial: 1,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 86400,
+        };
+
+        let zone = Zone::new(origin, soa);
+        store.add_zone(zone);
+
+        let query = Name::from_str("www.example.com.").unwrap();
+        assert!(store.find_zone(&query).is_some());
+
+        let query = Name::from_str("example.org.").unwrap();
+        assert!(store.find_zone(&query).is_none());
+    }
+
+    #[test]
+    fn test_wildcard_lookup() {
+        let origin = Name::from_str("example.com.").unwrap();
         let soa = SoaRecord {
             mname: Name::from_str("ns1.example.com.").unwrap(),
             rname: Name::from_str("admin.example.com.").unwrap(),
@@ -840,230 +3110,1046 @@ mod tests {
         );
         zone.add_record(www_record);
 
-        // Test direct wildcard lookup
-        let wildcard_result = zone.lookup(&wildcard_name, RecordType::A);
-        assert!(wildcard_result.is_some());
-        assert_eq!(wildcard_result.unwrap().len(), 1);
+        // Test direct wildcard lookup
+        let wildcard_result = zone.lookup(&wildcard_name, RecordType::A);
+        assert!(wildcard_result.is_some());
+        assert_eq!(wildcard_result.unwrap().len(), 1);
+
+        // Test wildcard match for non-existent name
+        let random_name = Name::from_str("random.example.com.").unwrap();
+        let wildcard_match = zone.lookup_wildcard(&random_name, RecordType::A);
+        assert!(wildcard_match.is_some());
+        assert_eq!(wildcard_match.unwrap().len(), 1);
+
+        // Test that specific record exists (should NOT use wildcard)
+        let www_result = zone.lookup(&www_name, RecordType::A);
+        assert!(www_result.is_some());
+        assert_eq!(www_result.unwrap().len(), 1);
+
+        // Verify www returns different IP than wildcard
+        if let Some(RData::A(a)) = www_result.unwrap()[0].data() {
+            assert_eq!(a.0, Ipv4Addr::new(192, 0, 2, 10));
+        }
+    }
+
+    #[test]
+    fn test_wildcard_synthesizes_query_name_as_owner() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let soa = SoaRecord {
+            mname: Name::from_str("ns1.example.com.").unwrap(),
+            rname: Name::from_str("admin.example.com.").unwrap(),
+            serial: 1,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 86400,
+        };
+
+        let mut zone = Zone::new(origin, soa);
+        zone.add_record(Record::from_rdata(
+            Name::from_str("*.example.com.").unwrap(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 100))),
+        ));
+
+        let qname = Name::from_str("a.b.example.com.").unwrap();
+        let records = zone.lookup_wildcard(&qname, RecordType::A).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name(), &qname);
+    }
+
+    #[test]
+    fn test_wildcard_blocked_by_closest_encloser() {
+        // RFC 4592 section 2.2.1 example zone (abridged)
+        let origin = Name::from_str("example.").unwrap();
+        let soa = SoaRecord {
+            mname: Name::from_str("ns.example.com.").unwrap(),
+            rname: Name::from_str("hostmaster.example.com.").unwrap(),
+            serial: 1,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 86400,
+        };
+
+        let mut zone = Zone::new(origin, soa);
+        for (owner, rdata) in [
+            (
+                "*.example.",
+                RData::TXT(hickory_proto::rr::rdata::TXT::new(vec![
+                    "this is a wildcard".to_string(),
+                ])),
+            ),
+            (
+                "host1.example.",
+                RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 1))),
+            ),
+            (
+                "_ssh._tcp.host1.example.",
+                RData::SRV(hickory_proto::rr::rdata::SRV::new(
+                    0,
+                    0,
+                    22,
+                    Name::from_str("host1.example.").unwrap(),
+                )),
+            ),
+            (
+                "subdel.example.",
+                RData::NS(hickory_proto::rr::rdata::NS(
+                    Name::from_str("ns.example.com.").unwrap(),
+                )),
+            ),
+        ] {
+            zone.add_record(Record::from_rdata(
+                Name::from_str(owner).unwrap(),
+                3600,
+                rdata,
+            ));
+        }
+
+        // Matched by the wildcard
+        let matched = Name::from_str("host3.example.").unwrap();
+        assert!(zone.lookup_wildcard(&matched, RecordType::TXT).is_some());
+        let matched = Name::from_str("foo.bar.example.").unwrap();
+        assert!(zone.lookup_wildcard(&matched, RecordType::TXT).is_some());
+
+        // _tcp.host1.example. is an empty non-terminal, so it exists
+        let ent = Name::from_str("_tcp.host1.example.").unwrap();
+        assert!(zone.is_empty_non_terminal(&ent));
+        assert!(zone.name_exists(&ent));
+        assert!(zone.wildcard_source(&ent).is_none());
+
+        // The closest encloser of these names is host1.example. or
+        // _tcp.host1.example., neither of which owns a wildcard
+        for blocked in [
+            "_telnet._tcp.host1.example.",
+            "host1.example.",
+            "ghost.host1.example.",
+        ] {
+            let name = Name::from_str(blocked).unwrap();
+            assert!(
+                zone.lookup_wildcard(&name, RecordType::TXT).is_none(),
+                "{} must not match *.example.",
+                blocked
+            );
+        }
+        assert_eq!(
+            zone.closest_encloser(&Name::from_str("_telnet._tcp.host1.example.").unwrap()),
+            Some(ent)
+        );
+    }
+
+    #[test]
+    fn test_empty_non_terminal_index() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let soa = SoaRecord {
+            mname: Name::from_str("ns1.example.com.").unwrap(),
+            rname: Name::from_str("admin.example.com.").unwrap(),
+            serial: 1,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 86400,
+        };
+
+        let mut zone = Zone::new(origin.clone(), soa);
+        zone.add_record(Record::from_rdata(
+            Name::from_str("a.b.c.example.com.").unwrap(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 1))),
+        ));
+
+        let b = Name::from_str("b.c.example.com.").unwrap();
+        let c = Name::from_str("c.example.com.").unwrap();
+        assert!(zone.is_empty_non_terminal(&b));
+        assert!(zone.is_empty_non_terminal(&c));
+        assert!(!zone.is_empty_non_terminal(&origin), "Apex is never an ENT");
+        assert!(!zone.is_empty_non_terminal(&Name::from_str("a.b.c.example.com.").unwrap()));
+        assert!(!zone.is_empty_non_terminal(&Name::from_str("d.example.com.").unwrap()));
+
+        // Adding data at an ENT turns it into a regular name
+        zone.add_record(Record::from_rdata(
+            c.clone(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 2))),
+        ));
+        assert!(!zone.is_empty_non_terminal(&c));
+        assert!(zone.is_empty_non_terminal(&b));
+
+        // Adding data below an existing name does not make it an ENT
+        zone.add_record(Record::from_rdata(
+            Name::from_str("x.c.example.com.").unwrap(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 3))),
+        ));
+        assert!(!zone.is_empty_non_terminal(&c));
+
+        // Out-of-zone owners do not create ENTs
+        zone.add_record(Record::from_rdata(
+            Name::from_str("host.sub.example.org.").unwrap(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 4))),
+        ));
+        assert!(!zone.is_empty_non_terminal(&Name::from_str("sub.example.org.").unwrap()));
+    }
+
+    #[test]
+    fn test_dnskey_parsing() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        // Create a temporary zone file with DNSKEY record
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "ns1 IN A 192.0.2.1").unwrap();
+        // DNSKEY with simple base64 key for testing
+        writeln!(
+            temp_file,
+            "@ IN DNSKEY 256 3 8 AwEAAaetidLzsKWUt4swWR8yu0wPHPiUi8LU"
+        )
+        .unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+
+        // Verify DNSKEY was parsed
+        let dnskey_records =
+            zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::DNSKEY);
+        assert!(dnskey_records.is_some(), "DNSKEY record should be parsed");
+        assert_eq!(
+            dnskey_records.unwrap().len(),
+            1,
+            "Should have one DNSKEY record"
+        );
+
+        // Verify it's actually a DNSSEC record
+        if let Some(rdata) = dnskey_records.unwrap()[0].data() {
+            assert!(matches!(rdata, RData::DNSSEC(_)), "Should be DNSSEC RData");
+        }
+    }
+
+    #[test]
+    fn test_rrsig_parsing() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        // Create a temporary zone file with RRSIG record
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "ns1 IN A 192.0.2.1").unwrap();
+        // RRSIG: type_covered algorithm labels original_ttl sig_expiration sig_inception key_tag signer_name signature
+        // sig_expiration and sig_inception are Unix timestamps (u32)
+        writeln!(
+            temp_file,
+            "@ IN RRSIG A 8 2 3600 1767139200 1764547200 12345 example.com. AwEAAaetidLzsKWU"
+        )
+        .unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+
+        // Verify RRSIG was parsed (stored as SIG in hickory-proto)
+        let rrsig_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::SIG);
+        assert!(rrsig_records.is_some(), "RRSIG record should be parsed");
+        assert_eq!(
+            rrsig_records.unwrap().len(),
+            1,
+            "Should have one RRSIG record"
+        );
+
+        // Verify it's actually a DNSSEC record
+        if let Some(rdata) = rrsig_records.unwrap()[0].data() {
+            assert!(matches!(rdata, RData::DNSSEC(_)), "Should be DNSSEC RData");
+        }
+    }
+
+    #[test]
+    fn test_nsec_parsing() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        // Create a temporary zone file with NSEC record
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "ns1 IN A 192.0.2.1").unwrap();
+        // NSEC: next_domain_name type_bit_maps...
+        writeln!(
+            temp_file,
+            "@ IN NSEC www.example.com. A NS SOA RRSIG NSEC DNSKEY"
+        )
+        .unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+
+        // Verify NSEC was parsed
+        let nsec_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::NSEC);
+        assert!(nsec_records.is_some(), "NSEC record should be parsed");
+        assert_eq!(
+            nsec_records.unwrap().len(),
+            1,
+            "Should have one NSEC record"
+        );
+
+        // Verify it's actually a DNSSEC record
+        if let Some(rdata) = nsec_records.unwrap()[0].data() {
+            assert!(matches!(rdata, RData::DNSSEC(_)), "Should be DNSSEC RData");
+        }
+    }
+
+    #[test]
+    fn test_ds_parsing() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        // Create a temporary zone file with DS record
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "ns1 IN A 192.0.2.1").unwrap();
+        // DS: key_tag algorithm digest_type digest_hex
+        writeln!(
+            temp_file,
+            "@ IN DS 12345 8 2 A8B1C2D3E4F506172839405A6B7C8D9E0F1A2B3C4D5E6F70"
+        )
+        .unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+
+        // Verify DS was parsed
+        let ds_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::DS);
+        assert!(ds_records.is_some(), "DS record should be parsed");
+        assert_eq!(ds_records.unwrap().len(), 1, "Should have one DS record");
+
+        // Verify it's actually a DNSSEC record
+        if let Some(rdata) = ds_records.unwrap()[0].data() {
+            assert!(matches!(rdata, RData::DNSSEC(_)), "Should be DNSSEC RData");
+        }
+    }
+
+    #[test]
+    fn test_malformed_zone_file() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        // Missing SOA record
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        temp_file.flush().unwrap();
+
+        let result = parse_zone_file(temp_file.path(), "example.com.");
+        assert!(result.is_err(), "Should fail without SOA record");
+    }
+
+    #[test]
+    fn test_invalid_ttl() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL invalid").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        temp_file.flush().unwrap();
+
+        let result = parse_zone_file(temp_file.path(), "example.com.");
+        assert!(result.is_err(), "Should fail with invalid TTL");
+    }
+
+    #[test]
+    fn test_invalid_record_data() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        writeln!(temp_file, "@ IN A invalid.ip.address").unwrap();
+        temp_file.flush().unwrap();
+
+        let result = parse_zone_file(temp_file.path(), "example.com.");
+        // Invalid A record causes parse error
+        assert!(result.is_err(), "Should fail with invalid A record");
+    }
+
+    #[test]
+    fn test_malformed_soa() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        // SOA with insufficient fields
+        writeln!(temp_file, "@ IN SOA ns1.example.com.").unwrap();
+        temp_file.flush().unwrap();
+
+        let result = parse_zone_file(temp_file.path(), "example.com.");
+        assert!(result.is_err(), "Should fail with malformed SOA");
+    }
+
+    #[test]
+    fn test_empty_zone_file() {
+        use tempfile::NamedTempFile;
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let result = parse_zone_file(temp_file.path(), "example.com.");
+        assert!(result.is_err(), "Should fail with empty zone file");
+    }
+
+    #[test]
+    fn test_invalid_base64_in_dnskey() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        // Invalid base64 in DNSKEY
+        writeln!(temp_file, "@ IN DNSKEY 256 3 8 !!!INVALID_BASE64!!!").unwrap();
+        temp_file.flush().unwrap();
+
+        let err = parse_zone_file(temp_file.path(), "example.com.").unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid base64 in DNSKEY"));
+    }
+
+    #[test]
+    fn test_invalid_hex_in_ds() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        // Invalid hex in DS digest
+        writeln!(temp_file, "@ IN DS 12345 8 2 ZZZZZZ").unwrap();
+        temp_file.flush().unwrap();
+
+        let err = parse_zone_file(temp_file.path(), "example.com.").unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid hex in DS"));
+    }
+
+    #[test]
+    fn test_invalid_encoded_rdata_is_an_error() {
+        let parse = |content: &str| {
+            let entry = &tokenize_zone(content).unwrap()[0];
+            parse_resource_record(
+                entry,
+                &Name::from_str("example.com.").unwrap(),
+                &mut RecordDefaults::default(),
+                &mut Vec::new(),
+            )
+        };
+
+        for (content, message) in [
+            (
+                "@ IN CDS 12345 8 2 ZZZZ",
+                "Invalid hex in CDS at line 1, column 20",
+            ),
+            (
+                "@ IN CDNSKEY 257 3 8 !!!",
+                "Invalid base64 in CDNSKEY at line 1, column 22",
+            ),
+            (
+                "@ IN CERT PKIX 0 0 AB !!",
+                "Invalid base64 in CERT at line 1, column 20",
+            ),
+        ] {
+            assert_eq!(parse(content).unwrap_err().to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_very_long_domain_name() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        // Very long label (>63 characters, which violates DNS spec)
+        let long_label = "a".repeat(70);
+        writeln!(temp_file, "{} IN A 192.0.2.1", long_label).unwrap();
+        temp_file.flush().unwrap();
+
+        let result = parse_zone_file(temp_file.path(), "example.com.");
+        // Very long labels cause parse errors
+        assert!(result.is_err(), "Should fail with very long domain label");
+    }
+
+    #[test]
+    fn test_wildcard_with_specific_override() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let soa = SoaRecord {
+            mname: Name::from_str("ns1.example.com.").unwrap(),
+            rname: Name::from_str("admin.example.com.").unwrap(),
+            serial: 1,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 86400,
+        };
+
+        let mut zone = Zone::new(origin.clone(), soa);
+
+        // Add wildcard
+        let wildcard_name = Name::from_str("*.example.com.").unwrap();
+        let wildcard_record = Record::from_rdata(
+            wildcard_name.clone(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 100))),
+        );
+        zone.add_record(wildcard_record);
+
+        // Add specific record
+        let specific_name = Name::from_str("www.example.com.").unwrap();
+        let specific_record = Record::from_rdata(
+            specific_name.clone(),
+            3600,
+            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 10))),
+        );
+        zone.add_record(specific_record);
+
+        // Test that specific overrides wildcard
+        let specific_result = zone.lookup(&specific_name, RecordType::A);
+        assert!(specific_result.is_some());
+        if let Some(RData::A(a)) = specific_result.unwrap()[0].data() {
+            assert_eq!(
+                a.0,
+                Ipv4Addr::new(192, 0, 2, 10),
+                "Specific record should override wildcard"
+            );
+        }
+
+        // Test wildcard match
+        let random_name = Name::from_str("random.example.com.").unwrap();
+        let wildcard_result = zone.lookup_wildcard(&random_name, RecordType::A);
+        assert!(wildcard_result.is_some());
+        if let Some(RData::A(a)) = wildcard_result.unwrap()[0].data() {
+            assert_eq!(a.0, Ipv4Addr::new(192, 0, 2, 100), "Should match wildcard");
+        }
+    }
+
+    #[test]
+    fn test_nonexistent_record_type() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let soa = SoaRecord {
+            mname: Name::from_str("ns1.example.com.").unwrap(),
+            rname: Name::from_str("admin.example.com.").unwrap(),
+            serial: 1,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 86400,
+        };
+
+        let zone = Zone::new(origin.clone(), soa);
+
+        // Query for record type that doesn't exist
+        let result = zone.lookup(&origin, RecordType::MX);
+        assert!(
+            result.is_none(),
+            "Should return None for non-existent record type"
+        );
+    }
+
+    #[test]
+    fn test_caa_record_parsing() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        writeln!(temp_file, "@ IN CAA 0 issue \"letsencrypt.org\"").unwrap();
+        writeln!(temp_file, "@ IN CAA 0 issuewild \"letsencrypt.org\"").unwrap();
+        writeln!(temp_file, "@ IN CAA 0 iodef \"mailto:admin@example.com\"").unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        let caa_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::CAA);
+        assert!(caa_records.is_some());
+        assert_eq!(caa_records.unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_ptr_record_parsing() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN 2.0.192.in-addr.arpa.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        writeln!(temp_file, "1 IN PTR www.example.com.").unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "2.0.192.in-addr.arpa.").unwrap();
+        let ptr_records = zone.lookup(
+            &Name::from_str("1.2.0.192.in-addr.arpa.").unwrap(),
+            RecordType::PTR,
+        );
+        assert!(ptr_records.is_some());
+    }
+
+    #[test]
+    fn test_srv_record_parsing() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        writeln!(temp_file, "_http._tcp IN SRV 10 60 80 www.example.com.").unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        let srv_records = zone.lookup(
+            &Name::from_str("_http._tcp.example.com.").unwrap(),
+            RecordType::SRV,
+        );
+        assert!(srv_records.is_some());
+    }
+
+    #[test]
+    fn test_multiline_soa_with_parentheses() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
+        writeln!(temp_file, "    2024010101  ; Serial").unwrap();
+        writeln!(temp_file, "    7200        ; Refresh").unwrap();
+        writeln!(temp_file, "    3600        ; Retry").unwrap();
+        writeln!(temp_file, "    1209600     ; Expire").unwrap();
+        writeln!(temp_file, "    86400       ; Minimum TTL").unwrap();
+        writeln!(temp_file, ")").unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "ns1 IN A 192.0.2.1").unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+
+        // Verify SOA was parsed correctly
+        assert_eq!(zone.soa.serial, 2024010101);
+        assert_eq!(zone.soa.refresh, 7200);
+        assert_eq!(zone.soa.retry, 3600);
+        assert_eq!(zone.soa.expire, 1209600);
+        assert_eq!(zone.soa.minimum, 86400);
+
+        // Verify NS record was also parsed
+        let ns_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::NS);
+        assert!(ns_records.is_some());
+    }
+
+    #[test]
+    fn test_multiline_soa_without_inline_comments() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
+        writeln!(temp_file, "    2024010101").unwrap();
+        writeln!(temp_file, "    7200").unwrap();
+        writeln!(temp_file, "    3600").unwrap();
+        writeln!(temp_file, "    1209600").unwrap();
+        writeln!(temp_file, "    86400").unwrap();
+        writeln!(temp_file, ")").unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        assert_eq!(zone.soa.serial, 2024010101);
+    }
+
+    #[test]
+    fn test_inline_comments_stripped() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600 ; default TTL").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400 ; SOA record"
+        )
+        .unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com. ; nameserver").unwrap();
+        writeln!(temp_file, "ns1 IN A 192.0.2.1 ; nameserver IP").unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+
+        // Should parse successfully despite comments
+        let ns_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::NS);
+        assert!(ns_records.is_some());
+    }
+
+    #[test]
+    fn test_tokenize_zone_content() {
+        let content = r#"$ORIGIN example.com.
+$TTL 3600
+@ IN SOA ns1.example.com. admin.example.com. (
+    2024010101  ; Serial
+    7200        ; Refresh
+    3600        ; Retry
+    1209600     ; Expire
+    86400       ; Minimum TTL
+)
+@ IN NS ns1.example.com.
+"#;
+
+        let entries = tokenize_zone(content).unwrap();
+
+        // Should have 4 entries: $ORIGIN, $TTL, SOA (merged), NS
+        assert_eq!(entries.len(), 4);
+
+        // SOA entry should be merged
+        let soa: Vec<&str> = entries[2].tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(
+            soa,
+            [
+                "@",
+                "IN",
+                "SOA",
+                "ns1.example.com.",
+                "admin.example.com.",
+                "2024010101",
+                "7200",
+                "3600",
+                "1209600",
+                "86400"
+            ]
+        );
+
+        // Token positions come from the original text
+        assert_eq!(entries[2].tokens[5].line, 4);
+        assert_eq!(entries[2].tokens[5].col, 5);
+    }
+
+    #[test]
+    fn test_soa_single_line_format() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 2024010101 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        assert_eq!(zone.soa.serial, 2024010101);
+        assert_eq!(zone.soa.refresh, 7200);
+        assert_eq!(zone.soa.retry, 3600);
+        assert_eq!(zone.soa.expire, 1209600);
+        assert_eq!(zone.soa.minimum, 86400);
+    }
+
+    #[test]
+    fn test_soa_multiline_compact() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        // All values on one line within parentheses
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
+        writeln!(temp_file, "    2024010101 7200 3600 1209600 86400").unwrap();
+        writeln!(temp_file, ")").unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        assert_eq!(zone.soa.serial, 2024010101);
+        assert_eq!(zone.soa.refresh, 7200);
+    }
+
+    #[test]
+    fn test_soa_multiline_each_field_separate_line() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        // Each field on its own line
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
+        writeln!(temp_file, "    2024010101").unwrap();
+        writeln!(temp_file, "    7200").unwrap();
+        writeln!(temp_file, "    3600").unwrap();
+        writeln!(temp_file, "    1209600").unwrap();
+        writeln!(temp_file, "    86400").unwrap();
+        writeln!(temp_file, ")").unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        temp_file.flush().unwrap();
+
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        assert_eq!(zone.soa.serial, 2024010101);
+        assert_eq!(zone.soa.minimum, 86400);
+    }
 
-        // Test wildcard match for non-existent name
-        let random_name = Name::from_str("random.example.com.").unwrap();
-        let wildcard_match = zone.lookup_wildcard(&random_name, RecordType::A);
-        assert!(wildcard_match.is_some());
-        assert_eq!(wildcard_match.unwrap().len(), 1);
+    #[test]
+    fn test_soa_multiline_mixed_grouping() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
 
-        // Test that specific record exists (should NOT use wildcard)
-        let www_result = zone.lookup(&www_name, RecordType::A);
-        assert!(www_result.is_some());
-        assert_eq!(www_result.unwrap().len(), 1);
+        // Some fields grouped, some separate
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
+        writeln!(temp_file, "    2024010101 7200").unwrap();
+        writeln!(temp_file, "    3600").unwrap();
+        writeln!(temp_file, "    1209600 86400").unwrap();
+        writeln!(temp_file, ")").unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        temp_file.flush().unwrap();
 
-        // Verify www returns different IP than wildcard
-        if let Some(RData::A(a)) = www_result.unwrap()[0].data() {
-            assert_eq!(a.0, Ipv4Addr::new(192, 0, 2, 10));
-        }
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        assert_eq!(zone.soa.serial, 2024010101);
+        assert_eq!(zone.soa.refresh, 7200);
+        assert_eq!(zone.soa.retry, 3600);
+        assert_eq!(zone.soa.expire, 1209600);
+        assert_eq!(zone.soa.minimum, 86400);
     }
 
     #[test]
-    fn test_dnskey_parsing() {
+    fn test_soa_with_inline_comments() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
-        // Create a temporary zone file with DNSKEY record
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
         writeln!(temp_file, "$TTL 3600").unwrap();
         writeln!(
             temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
-        )
-        .unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
-        writeln!(temp_file, "ns1 IN A 192.0.2.1").unwrap();
-        // DNSKEY with simple base64 key for testing
-        writeln!(
-            temp_file,
-            "@ IN DNSKEY 256 3 8 AwEAAaetidLzsKWUt4swWR8yu0wPHPiUi8LU"
+            "@ IN SOA ns1.example.com. admin.example.com. ( ; Start SOA"
         )
         .unwrap();
+        writeln!(temp_file, "    2024010101  ; Serial number").unwrap();
+        writeln!(temp_file, "    7200        ; Refresh - 2 hours").unwrap();
+        writeln!(temp_file, "    3600        ; Retry - 1 hour").unwrap();
+        writeln!(temp_file, "    1209600     ; Expire - 2 weeks").unwrap();
+        writeln!(temp_file, "    86400       ; Minimum TTL - 1 day").unwrap();
+        writeln!(temp_file, ") ; End SOA").unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com. ; Primary NS").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-
-        // Verify DNSKEY was parsed
-        let dnskey_records =
-            zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::DNSKEY);
-        assert!(dnskey_records.is_some(), "DNSKEY record should be parsed");
-        assert_eq!(
-            dnskey_records.unwrap().len(),
-            1,
-            "Should have one DNSKEY record"
-        );
-
-        // Verify it's actually a DNSSEC record
-        if let Some(rdata) = dnskey_records.unwrap()[0].data() {
-            assert!(matches!(rdata, RData::DNSSEC(_)), "Should be DNSSEC RData");
-        }
+        assert_eq!(zone.soa.serial, 2024010101);
+        assert_eq!(zone.soa.refresh, 7200);
+        assert_eq!(zone.soa.retry, 3600);
+        assert_eq!(zone.soa.expire, 1209600);
+        assert_eq!(zone.soa.minimum, 86400);
     }
 
     #[test]
-    fn test_rrsig_parsing() {
+    fn test_soa_single_line_with_comment() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
-        // Create a temporary zone file with RRSIG record
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
         writeln!(temp_file, "$TTL 3600").unwrap();
         writeln!(
             temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+            "@ IN SOA ns1.example.com. admin.example.com. 2024010101 7200 3600 1209600 86400 ; Main SOA"
         )
         .unwrap();
         writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
-        writeln!(temp_file, "ns1 IN A 192.0.2.1").unwrap();
-        // RRSIG: type_covered algorithm labels original_ttl sig_expiration sig_inception key_tag signer_name signature
-        // sig_expiration and sig_inception are Unix timestamps (u32)
-        writeln!(
-            temp_file,
-            "@ IN RRSIG A 8 2 3600 1767139200 1764547200 12345 example.com. AwEAAaetidLzsKWU"
-        )
-        .unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-
-        // Verify RRSIG was parsed (stored as SIG in hickory-proto)
-        let rrsig_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::SIG);
-        assert!(rrsig_records.is_some(), "RRSIG record should be parsed");
-        assert_eq!(
-            rrsig_records.unwrap().len(),
-            1,
-            "Should have one RRSIG record"
-        );
-
-        // Verify it's actually a DNSSEC record
-        if let Some(rdata) = rrsig_records.unwrap()[0].data() {
-            assert!(matches!(rdata, RData::DNSSEC(_)), "Should be DNSSEC RData");
-        }
+        assert_eq!(zone.soa.serial, 2024010101);
     }
 
     #[test]
-    fn test_nsec_parsing() {
+    fn test_soa_with_tabs_and_spaces() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
-        // Create a temporary zone file with NSEC record
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
         writeln!(temp_file, "$TTL 3600").unwrap();
         writeln!(
             temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+            "@\tIN\tSOA\tns1.example.com.\tadmin.example.com.\t("
         )
         .unwrap();
+        writeln!(temp_file, "\t\t2024010101\t\t; Serial").unwrap();
+        writeln!(temp_file, "        7200          ; Refresh").unwrap();
+        writeln!(temp_file, "\t3600\t\t; Retry").unwrap();
+        writeln!(temp_file, "    1209600    ; Expire").unwrap();
+        writeln!(temp_file, "\t86400\t; Minimum").unwrap();
+        writeln!(temp_file, ")").unwrap();
         writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
-        writeln!(temp_file, "ns1 IN A 192.0.2.1").unwrap();
-        // NSEC: next_domain_name type_bit_maps...
-        writeln!(
-            temp_file,
-            "@ IN NSEC www.example.com. A NS SOA RRSIG NSEC DNSKEY"
-        )
-        .unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-
-        // Verify NSEC was parsed
-        let nsec_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::NSEC);
-        assert!(nsec_records.is_some(), "NSEC record should be parsed");
-        assert_eq!(
-            nsec_records.unwrap().len(),
-            1,
-            "Should have one NSEC record"
-        );
-
-        // Verify it's actually a DNSSEC record
-        if let Some(rdata) = nsec_records.unwrap()[0].data() {
-            assert!(matches!(rdata, RData::DNSSEC(_)), "Should be DNSSEC RData");
-        }
+        assert_eq!(zone.soa.serial, 2024010101);
+        assert_eq!(zone.soa.refresh, 7200);
+        assert_eq!(zone.soa.retry, 3600);
     }
 
     #[test]
-    fn test_ds_parsing() {
+    fn test_soa_opening_paren_on_same_line_as_name() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
-        // Create a temporary zone file with DS record
+        // Opening paren on same line as mname and rname
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
         writeln!(temp_file, "$TTL 3600").unwrap();
         writeln!(
             temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+            "@ IN SOA ns1.example.com. admin.example.com. ( 2024010101"
         )
         .unwrap();
+        writeln!(temp_file, "    7200 3600").unwrap();
+        writeln!(temp_file, "    1209600 86400 )").unwrap();
         writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
-        writeln!(temp_file, "ns1 IN A 192.0.2.1").unwrap();
-        // DS: key_tag algorithm digest_type digest_hex
-        writeln!(
-            temp_file,
-            "@ IN DS 12345 8 2 A8B1C2D3E4F506172839405A6B7C8D9E0F1A2B3C4D5E6F70"
-        )
-        .unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-
-        // Verify DS was parsed
-        let ds_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::DS);
-        assert!(ds_records.is_some(), "DS record should be parsed");
-        assert_eq!(ds_records.unwrap().len(), 1, "Should have one DS record");
-
-        // Verify it's actually a DNSSEC record
-        if let Some(rdata) = ds_records.unwrap()[0].data() {
-            assert!(matches!(rdata, RData::DNSSEC(_)), "Should be DNSSEC RData");
-        }
+        assert_eq!(zone.soa.serial, 2024010101);
+        assert_eq!(zone.soa.minimum, 86400);
     }
 
     #[test]
-    fn test_malformed_zone_file() {
+    fn test_soa_multiple_blank_lines_in_parens() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
-        // Missing SOA record
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
         writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
+        writeln!(temp_file).unwrap();
+        writeln!(temp_file, "    2024010101").unwrap();
+        writeln!(temp_file).unwrap();
+        writeln!(temp_file, "    7200 3600").unwrap();
+        writeln!(temp_file).unwrap();
+        writeln!(temp_file, "    1209600 86400").unwrap();
+        writeln!(temp_file).unwrap();
+        writeln!(temp_file, ")").unwrap();
         writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
         temp_file.flush().unwrap();
 
-        let result = parse_zone_file(temp_file.path(), "example.com.");
-        assert!(result.is_err(), "Should fail without SOA record");
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        assert_eq!(zone.soa.serial, 2024010101);
     }
 
     #[test]
-    fn test_invalid_ttl() {
+    fn test_soa_comment_only_lines_in_parens() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL invalid").unwrap();
-        writeln!(
-            temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
-        )
-        .unwrap();
+        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
+        writeln!(temp_file, "    ; This is the serial number").unwrap();
+        writeln!(temp_file, "    2024010101").unwrap();
+        writeln!(temp_file, "    ; Time values below").unwrap();
+        writeln!(temp_file, "    7200").unwrap();
+        writeln!(temp_file, "    3600").unwrap();
+        writeln!(temp_file, "    1209600").unwrap();
+        writeln!(temp_file, "    86400").unwrap();
+        writeln!(temp_file, ")").unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
         temp_file.flush().unwrap();
 
-        let result = parse_zone_file(temp_file.path(), "example.com.");
-        assert!(result.is_err(), "Should fail with invalid TTL");
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        assert_eq!(zone.soa.serial, 2024010101);
+        assert_eq!(zone.soa.refresh, 7200);
     }
 
     #[test]
-    fn test_invalid_record_data() {
+    fn test_multiline_mx_record() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
+        // Test that multiline format works for other record types too
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
         writeln!(temp_file, "$TTL 3600").unwrap();
@@ -1072,41 +4158,50 @@ mod tests {
             "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
         )
         .unwrap();
-        writeln!(temp_file, "@ IN A invalid.ip.address").unwrap();
+        writeln!(temp_file, "@ IN MX (").unwrap();
+        writeln!(temp_file, "    10").unwrap();
+        writeln!(temp_file, "    mail.example.com.").unwrap();
+        writeln!(temp_file, ")").unwrap();
+        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
         temp_file.flush().unwrap();
 
-        let result = parse_zone_file(temp_file.path(), "example.com.");
-        // Invalid A record causes parse error
-        assert!(result.is_err(), "Should fail with invalid A record");
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        let mx_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::MX);
+        assert!(mx_records.is_some());
+        assert_eq!(mx_records.unwrap().len(), 1);
     }
 
     #[test]
-    fn test_malformed_soa() {
+    fn test_naptr_record_parsing() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
         writeln!(temp_file, "$TTL 3600").unwrap();
-        // SOA with insufficient fields
-        writeln!(temp_file, "@ IN SOA ns1.example.com.").unwrap();
-        temp_file.flush().unwrap();
-
-        let result = parse_zone_file(temp_file.path(), "example.com.");
-        assert!(result.is_err(), "Should fail with malformed SOA");
-    }
-
-    #[test]
-    fn test_empty_zone_file() {
-        use tempfile::NamedTempFile;
+        writeln!(
+            temp_file,
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+        )
+        .unwrap();
+        writeln!(
+            temp_file,
+            "_sip._tcp IN NAPTR 100 10 \"u\" \"E2U+sip\" \"!^.*$!sip:info@example.com!\" ."
+        )
+        .unwrap();
+        temp_file.flush().unwrap();
 
-        let temp_file = NamedTempFile::new().unwrap();
-        let result = parse_zone_file(temp_file.path(), "example.com.");
-        assert!(result.is_err(), "Should fail with empty zone file");
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        let naptr_records = zone.lookup(
+            &Name::from_str("_sip._tcp.example.com.").unwrap(),
+            RecordType::NAPTR,
+        );
+        assert!(naptr_records.is_some());
+        assert_eq!(naptr_records.unwrap().len(), 1);
     }
 
     #[test]
-    fn test_invalid_base64_in_dnskey() {
+    fn test_tlsa_record_parsing() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
@@ -1118,19 +4213,20 @@ mod tests {
             "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
         )
         .unwrap();
-        // Invalid base64 in DNSKEY
-        writeln!(temp_file, "@ IN DNSKEY 256 3 8 !!!INVALID_BASE64!!!").unwrap();
+        writeln!(temp_file, "_443._tcp IN TLSA 3 1 1 D2ABDE240D7CD3EE6B4B28C54DF034B97983A1D16E8A410E4561CB106618E971").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        // Invalid DNSKEY should be skipped
-        let dnskey_records =
-            zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::DNSKEY);
-        assert!(dnskey_records.is_none(), "Invalid DNSKEY should be skipped");
+        let tlsa_records = zone.lookup(
+            &Name::from_str("_443._tcp.example.com.").unwrap(),
+            RecordType::TLSA,
+        );
+        assert!(tlsa_records.is_some());
+        assert_eq!(tlsa_records.unwrap().len(), 1);
     }
 
     #[test]
-    fn test_invalid_hex_in_ds() {
+    fn test_sshfp_record_parsing() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
@@ -1142,18 +4238,24 @@ mod tests {
             "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
         )
         .unwrap();
-        // Invalid hex in DS digest
-        writeln!(temp_file, "@ IN DS 12345 8 2 ZZZZZZ").unwrap();
+        writeln!(
+            temp_file,
+            "host IN SSHFP 1 2 123456789ABCDEF67890123456789ABCDEF67890123456789ABCDEF6789012"
+        )
+        .unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        // Invalid DS should be skipped
-        let ds_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::DS);
-        assert!(ds_records.is_none(), "Invalid DS should be skipped");
+        let sshfp_records = zone.lookup(
+            &Name::from_str("host.example.com.").unwrap(),
+            RecordType::SSHFP,
+        );
+        assert!(sshfp_records.is_some());
+        assert_eq!(sshfp_records.unwrap().len(), 1);
     }
 
     #[test]
-    fn test_very_long_domain_name() {
+    fn test_alias_record_parsing() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
@@ -1165,623 +4267,1502 @@ mod tests {
             "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
         )
         .unwrap();
-        // Very long label (>63 characters, which violates DNS spec)
-        let long_label = "a".repeat(70);
-        writeln!(temp_file, "{} IN A 192.0.2.1", long_label).unwrap();
+        writeln!(temp_file, "@ IN ALIAS cdn.example.net.").unwrap();
+        writeln!(temp_file, "www IN ANAME edge").unwrap();
         temp_file.flush().unwrap();
 
-        let result = parse_zone_file(temp_file.path(), "example.com.");
-        // Very long labels cause parse errors
-        assert!(result.is_err(), "Should fail with very long domain label");
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+
+        let apex = zone
+            .lookup(&Name::from_str("example.com.").unwrap(), RecordType::ANAME)
+            .unwrap();
+        assert!(matches!(
+            apex[0].data(),
+            Some(RData::ANAME(aname)) if aname.0 == Name::from_str("cdn.example.net.").unwrap()
+        ));
+
+        let www = zone
+            .lookup(
+                &Name::from_str("www.example.com.").unwrap(),
+                RecordType::ANAME,
+            )
+            .unwrap();
+        assert!(matches!(
+            www[0].data(),
+            Some(RData::ANAME(aname)) if aname.0 == Name::from_str("edge.example.com.").unwrap()
+        ));
     }
 
     #[test]
-    fn test_wildcard_with_specific_override() {
-        let origin = Name::from_str("example.com.").unwrap();
-        let soa = SoaRecord {
-            mname: Name::from_str("ns1.example.com.").unwrap(),
-            rname: Name::from_str("admin.example.com.").unwrap(),
-            serial: 1,
-            refresh: 7200,
-            retry: 3600,
-            expire: 1209600,
-            minimum: 86400,
-        };
-
-        let mut zone = Zone::new(origin.clone(), soa);
+    fn test_include_relative_path_with_origin() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(
+            dir.path().join("sub/hosts.inc"),
+            "www IN A 192.0.2.1\n$ORIGIN other.example.com.\nmail IN A 192.0.2.2\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("example.com.zone"),
+            "$ORIGIN example.com.\n\
+             $TTL 3600\n\
+             @ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400\n\
+             $INCLUDE sub/hosts.inc lab\n\
+             api IN A 192.0.2.3\n",
+        )
+        .unwrap();
 
-        // Add wildcard
-        let wildcard_name = Name::from_str("*.example.com.").unwrap();
-        let wildcard_record = Record::from_rdata(
-            wildcard_name.clone(),
-            3600,
-            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 100))),
+        let (zone, sources) =
+            parse_zone_file_with_sources(dir.path().join("example.com.zone"), "example.com.")
+                .unwrap();
+        assert_eq!(
+            sources,
+            vec![
+                dir.path().join("example.com.zone"),
+                dir.path().join("sub/hosts.inc")
+            ]
         );
-        zone.add_record(wildcard_record);
 
-        // Add specific record
-        let specific_name = Name::from_str("www.example.com.").unwrap();
-        let specific_record = Record::from_rdata(
-            specific_name.clone(),
-            3600,
-            RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 10))),
+        assert!(
+            zone.lookup(
+                &Name::from_str("www.lab.example.com.").unwrap(),
+                RecordType::A
+            )
+            .is_some()
+        );
+        assert!(
+            zone.lookup(
+                &Name::from_str("mail.other.example.com.").unwrap(),
+                RecordType::A
+            )
+            .is_some()
+        );
+        // $ORIGIN inside the included file does not leak back out
+        assert!(
+            zone.lookup(&Name::from_str("api.example.com.").unwrap(), RecordType::A)
+                .is_some()
         );
-        zone.add_record(specific_record);
-
-        // Test that specific overrides wildcard
-        let specific_result = zone.lookup(&specific_name, RecordType::A);
-        assert!(specific_result.is_some());
-        if let Some(RData::A(a)) = specific_result.unwrap()[0].data() {
-            assert_eq!(
-                a.0,
-                Ipv4Addr::new(192, 0, 2, 10),
-                "Specific record should override wildcard"
-            );
-        }
-
-        // Test wildcard match
-        let random_name = Name::from_str("random.example.com.").unwrap();
-        let wildcard_result = zone.lookup_wildcard(&random_name, RecordType::A);
-        assert!(wildcard_result.is_some());
-        if let Some(RData::A(a)) = wildcard_result.unwrap()[0].data() {
-            assert_eq!(a.0, Ipv4Addr::new(192, 0, 2, 100), "Should match wildcard");
-        }
     }
 
     #[test]
-    fn test_nonexistent_record_type() {
-        let origin = Name::from_str("example.com.").unwrap();
-        let soa = SoaRecord {
-            mname: Name::from_str("ns1.example.com.").unwrap(),
-            rname: Name::from_str("admin.example.com.").unwrap(),
-            serial: 1,
-            refresh: 7200,
-            retry: 3600,
-            expire: 1209600,
-            minimum: 86400,
-        };
-
-        let zone = Zone::new(origin.clone(), soa);
+    fn test_include_inherits_origin() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("soa.inc"),
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400\n\
+             @ IN NS ns1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("example.com.zone"),
+            format!(
+                "$ORIGIN example.com.\n$INCLUDE {}\nns1 IN A 192.0.2.53\n",
+                dir.path().join("soa.inc").display()
+            ),
+        )
+        .unwrap();
 
-        // Query for record type that doesn't exist
-        let result = zone.lookup(&origin, RecordType::MX);
+        let zone = parse_zone_file(dir.path().join("example.com.zone"), "example.com.").unwrap();
+        assert_eq!(zone.soa.serial, 1);
         assert!(
-            result.is_none(),
-            "Should return None for non-existent record type"
+            zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::NS)
+                .is_some()
+        );
+        assert!(
+            zone.lookup(&Name::from_str("ns1.example.com.").unwrap(), RecordType::A)
+                .is_some()
         );
     }
 
     #[test]
-    fn test_caa_record_parsing() {
+    fn test_include_cycle_detected() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("a.zone"),
+            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400\n\
+             $INCLUDE b.inc\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("b.inc"), "$INCLUDE a.zone\n").unwrap();
+
+        let err = parse_zone_file(dir.path().join("a.zone"), "example.com.").unwrap_err();
+        assert!(format!("{:#}", err).contains("cycle"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_include_missing_file() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
         writeln!(
             temp_file,
             "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
         )
         .unwrap();
-        writeln!(temp_file, "@ IN CAA 0 issue \"letsencrypt.org\"").unwrap();
-        writeln!(temp_file, "@ IN CAA 0 issuewild \"letsencrypt.org\"").unwrap();
-        writeln!(temp_file, "@ IN CAA 0 iodef \"mailto:admin@example.com\"").unwrap();
+        writeln!(temp_file, "$INCLUDE does-not-exist.inc").unwrap();
         temp_file.flush().unwrap();
 
-        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        let caa_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::CAA);
-        assert!(caa_records.is_some());
-        assert_eq!(caa_records.unwrap().len(), 3);
+        assert!(parse_zone_file(temp_file.path(), "example.com.").is_err());
     }
 
     #[test]
-    fn test_ptr_record_parsing() {
+    fn test_generate_records() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "$ORIGIN 2.0.192.in-addr.arpa.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
         writeln!(
             temp_file,
             "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
         )
         .unwrap();
-        writeln!(temp_file, "1 IN PTR www.example.com.").unwrap();
+        writeln!(temp_file, "$GENERATE 1-10 host-$ A 192.0.2.$").unwrap();
+        writeln!(
+            temp_file,
+            "$GENERATE 0-6/2 dhcp-${{10,3}} 300 IN CNAME pool$"
+        )
+        .unwrap();
         temp_file.flush().unwrap();
 
-        let zone = parse_zone_file(temp_file.path(), "2.0.192.in-addr.arpa.").unwrap();
-        let ptr_records = zone.lookup(
-            &Name::from_str("1.2.0.192.in-addr.arpa.").unwrap(),
-            RecordType::PTR,
+        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+
+        for i in 1..=10 {
+            let name = Name::from_str(&format!("host-{}.example.com.", i)).unwrap();
+            let records = zone.lookup(&name, RecordType::A).unwrap();
+            assert_eq!(
+                records[0].data(),
+                Some(&RData::A(Ipv4Addr::new(192, 0, 2, i as u8).into()))
+            );
+        }
+        assert!(
+            zone.lookup(
+                &Name::from_str("host-11.example.com.").unwrap(),
+                RecordType::A
+            )
+            .is_none()
+        );
+
+        for i in [0, 2, 4, 6] {
+            let name = Name::from_str(&format!("dhcp-{:03}.example.com.", i + 10)).unwrap();
+            let records = zone.lookup(&name, RecordType::CNAME).unwrap();
+            assert_eq!(records[0].ttl(), 300);
+        }
+        assert!(
+            zone.lookup(
+                &Name::from_str("dhcp-011.example.com.").unwrap(),
+                RecordType::CNAME
+            )
+            .is_none()
         );
-        assert!(ptr_records.is_some());
     }
 
     #[test]
-    fn test_srv_record_parsing() {
+    fn test_generate_modifiers() {
+        assert_eq!(generate_substitute("host$", 7).unwrap(), "host7");
+        assert_eq!(generate_substitute("${0,3}", 7).unwrap(), "007");
+        assert_eq!(generate_substitute("${-2}", 7).unwrap(), "5");
+        assert_eq!(generate_substitute("${0,0,o}", 8).unwrap(), "10");
+        assert_eq!(generate_substitute("${0,2,x}", 255).unwrap(), "ff");
+        assert_eq!(generate_substitute("${0,0,X}", 255).unwrap(), "FF");
+        assert_eq!(generate_substitute("${0,4,n}", 0x1ab).unwrap(), "b.a.1.0");
+        assert_eq!(generate_substitute("${0,0,N}", 0xab).unwrap(), "B.A");
+        assert_eq!(generate_substitute("cost-$$-\\$", 1).unwrap(), "cost-$-$");
+
+        assert!(generate_substitute("${-8}", 7).is_err());
+        assert!(generate_substitute("${0,1,q}", 7).is_err());
+        assert!(generate_substitute("${0", 7).is_err());
+    }
+
+    #[test]
+    fn test_generate_invalid_ranges() {
+        let expand = |line: &str| expand_generate(&tokenize_zone(line).unwrap()[0].tokens);
+
+        assert!(expand("$GENERATE 10-1 host-$ A 192.0.2.$").is_err());
+        assert!(expand("$GENERATE 1-10/0 host-$ A 192.0.2.$").is_err());
+        assert!(expand("$GENERATE 1 host-$ A 192.0.2.$").is_err());
+        assert!(expand("$GENERATE 0-4294967295 host-$ A 192.0.2.$").is_err());
+        assert_eq!(expand("$GENERATE 1-3 host-$ A 192.0.2.$").unwrap().len(), 3);
+
+        // Stepping past u64::MAX ends the range
+        assert_eq!(
+            expand("$GENERATE 18446744073709551615-18446744073709551615 host-$ A 192.0.2.1")
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            expand("$GENERATE 1-10/18446744073709551615 host-$ A 192.0.2.$")
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_parse_ttl_units() {
+        assert_eq!(parse_ttl("300").unwrap(), 300);
+        assert_eq!(parse_ttl("1h30m").unwrap(), 5400);
+        assert_eq!(parse_ttl("1w").unwrap(), 604800);
+        assert_eq!(parse_ttl("2D").unwrap(), 172800);
+        assert_eq!(parse_ttl("1m30").unwrap(), 90);
+        assert_eq!(parse_ttl("1h1m1s").unwrap(), 3661);
+
+        assert!(parse_ttl("1y").is_err());
+        assert!(parse_ttl("h").is_err());
+        assert!(parse_ttl("99999w").is_err());
+    }
+
+    #[test]
+    fn test_blank_owner_ttl_and_class_order() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
         writeln!(
             temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+            "@ 1h IN SOA ns1 admin 1 2h 1h 2w 1d ; timers with units"
         )
         .unwrap();
-        writeln!(temp_file, "_http._tcp IN SRV 10 60 80 www.example.com.").unwrap();
+        writeln!(temp_file, "  IN NS ns1").unwrap();
+        writeln!(temp_file, "\tin ns ns2").unwrap();
+        writeln!(temp_file, "www IN 300 A 192.0.2.1").unwrap();
+        writeln!(temp_file, "    AAAA 2001:db8::1").unwrap();
+        writeln!(temp_file, "mail 1h30m MX 10 @").unwrap();
+        writeln!(temp_file, "ftp A 192.0.2.2").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        let srv_records = zone.lookup(
-            &Name::from_str("_http._tcp.example.com.").unwrap(),
-            RecordType::SRV,
-        );
-        assert!(srv_records.is_some());
+        assert_eq!(zone.soa.refresh, 7200);
+        assert_eq!(zone.soa.expire, 1209600);
+        assert_eq!(zone.soa.minimum, 86400);
+
+        let apex = Name::from_str("example.com.").unwrap();
+        let ns = zone.lookup(&apex, RecordType::NS).unwrap();
+        assert_eq!(ns.len(), 2);
+        assert!(ns.iter().all(|r| r.ttl() == 3600));
+
+        let www = Name::from_str("www.example.com.").unwrap();
+        assert_eq!(zone.lookup(&www, RecordType::A).unwrap()[0].ttl(), 300);
+        // Blank owner and omitted TTL both carry over from the previous record
+        assert_eq!(zone.lookup(&www, RecordType::AAAA).unwrap()[0].ttl(), 300);
+
+        let mail = Name::from_str("mail.example.com.").unwrap();
+        let mx = zone.lookup(&mail, RecordType::MX).unwrap();
+        assert_eq!(mx[0].ttl(), 5400);
+        if let Some(RData::MX(mx)) = mx[0].data() {
+            assert_eq!(mx.exchange(), &apex);
+        } else {
+            panic!("Expected MX record");
+        }
+
+        let ftp = Name::from_str("ftp.example.com.").unwrap();
+        assert_eq!(zone.lookup(&ftp, RecordType::A).unwrap()[0].ttl(), 5400);
     }
 
     #[test]
-    fn test_multiline_soa_with_parentheses() {
+    fn test_ttl_directive_overrides_last_explicit_ttl() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
-        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
-        writeln!(temp_file, "    2024010101  ; Serial").unwrap();
-        writeln!(temp_file, "    7200        ; Refresh").unwrap();
-        writeln!(temp_file, "    3600        ; Retry").unwrap();
-        writeln!(temp_file, "    1209600     ; Expire").unwrap();
-        writeln!(temp_file, "    86400       ; Minimum TTL").unwrap();
-        writeln!(temp_file, ")").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
-        writeln!(temp_file, "ns1 IN A 192.0.2.1").unwrap();
+        writeln!(temp_file, "$TTL 1d").unwrap();
+        writeln!(temp_file, "@ 60 IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        writeln!(temp_file, "www IN A 192.0.2.1").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        let www = Name::from_str("www.example.com.").unwrap();
+        assert_eq!(zone.lookup(&www, RecordType::A).unwrap()[0].ttl(), 86400);
+    }
 
-        // Verify SOA was parsed correctly
-        assert_eq!(zone.soa.serial, 2024010101);
-        assert_eq!(zone.soa.refresh, 7200);
-        assert_eq!(zone.soa.retry, 3600);
-        assert_eq!(zone.soa.expire, 1209600);
-        assert_eq!(zone.soa.minimum, 86400);
+    #[test]
+    fn test_tokenizer_quotes_escapes_and_comments() {
+        let entries = tokenize_zone(
+            "txt IN TXT \"a ; (not) a comment\" ; real comment\n\
+             esc\\ aped IN TXT \"say \\\"hi\\\"\"\n",
+        )
+        .unwrap();
 
-        // Verify NS record was also parsed
-        let ns_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::NS);
-        assert!(ns_records.is_some());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].tokens.len(), 4);
+        assert_eq!(entries[0].tokens[3].text, "\"a ; (not) a comment\"");
+        assert_eq!(entries[1].tokens[0].text, "esc\\ aped");
+        assert_eq!(entries[1].tokens[3].text, "\"say \\\"hi\\\"\"");
+        assert_eq!(entries[1].tokens[3].line, 2);
+        assert_eq!(entries[1].tokens[3].col, 18);
+    }
+
+    #[test]
+    fn test_tokenizer_errors_report_position() {
+        let err = tokenize_zone("@ IN SOA ns1 admin (\n  1 2 3 4 5\n").unwrap_err();
+        assert_eq!(err.to_string(), "Unmatched '(' at line 1, column 20");
+
+        let err = tokenize_zone("@ IN NS ns1 )\n").unwrap_err();
+        assert_eq!(err.to_string(), "Unmatched ')' at line 1, column 13");
+
+        let err = tokenize_zone("@ IN TXT \"open\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unterminated quoted string at line 1, column 10"
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_recovers_at_next_line() {
+        let (entries, errors) = tokenize_zone_recovering(
+            "a IN TXT \"open\n\
+             b IN A 192.0.2.1\n\
+             c IN NS ns1 ) ; comment\n\
+             d IN A 192.0.2.2\n\
+             e IN SOA ns1 admin (\n\
+             f IN A 192.0.2.3\n",
+        );
+
+        let owners: Vec<&str> = entries.iter().map(|e| e.tokens[0].text.as_str()).collect();
+        assert_eq!(owners, ["b", "d", "f"]);
+        assert_eq!(entries[2].tokens[0].line, 6);
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "Unterminated quoted string at line 1, column 10",
+                "Unmatched ')' at line 3, column 13",
+                "Unmatched '(' at line 5, column 20",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_collects_syntax_errors() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        writeln!(temp_file, "txt IN TXT \"open").unwrap();
+        writeln!(temp_file, "www IN A 192.0.2.300").unwrap();
+        writeln!(temp_file, "ok IN A 192.0.2.1").unwrap();
+        temp_file.flush().unwrap();
+
+        let check = check_zone_file(temp_file.path(), "example.com.").unwrap();
+        let errors: Vec<_> = check
+            .errors()
+            .map(|e| (e.line, e.col, e.message.as_str()))
+            .collect();
+        assert_eq!(errors.len(), 2, "{:?}", check.diagnostics);
+        assert_eq!(errors[0], (3, 12, "Unterminated quoted string"));
+        assert_eq!((errors[1].0, errors[1].1), (4, 10));
+        let ok = Name::from_str("ok.example.com.").unwrap();
+        assert!(check.zone.unwrap().lookup(&ok, RecordType::A).is_some());
     }
 
     #[test]
-    fn test_multiline_soa_without_inline_comments() {
+    fn test_record_errors_report_position() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
-        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
-        writeln!(temp_file, "    2024010101").unwrap();
-        writeln!(temp_file, "    7200").unwrap();
-        writeln!(temp_file, "    3600").unwrap();
-        writeln!(temp_file, "    1209600").unwrap();
-        writeln!(temp_file, "    86400").unwrap();
-        writeln!(temp_file, ")").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        writeln!(temp_file, "www IN A 192.0.2.300").unwrap();
         temp_file.flush().unwrap();
 
-        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
+        let err = parse_zone_file(temp_file.path(), "example.com.").unwrap_err();
+        assert!(
+            format!("{:#}", err).contains(&format!(
+                "{}:3:10: error: Invalid A record",
+                temp_file.path().display()
+            )),
+            "{:#}",
+            err
+        );
     }
 
     #[test]
-    fn test_inline_comments_stripped() {
+    fn test_check_collects_all_errors() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600 ; default TTL").unwrap();
-        writeln!(
-            temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400 ; SOA record"
-        )
-        .unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com. ; nameserver").unwrap();
-        writeln!(temp_file, "ns1 IN A 192.0.2.1 ; nameserver IP").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin (").unwrap();
+        writeln!(temp_file, "    1 7200 3600 1209600 86400 )").unwrap();
+        writeln!(temp_file, "www IN A 192.0.2.300").unwrap();
+        writeln!(temp_file, "mail IN MX ten mail").unwrap();
+        writeln!(temp_file, "ok IN A 192.0.2.1").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 2 7200 3600 1209600 86400").unwrap();
         temp_file.flush().unwrap();
 
-        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
+        let check = check_zone_file(temp_file.path(), "example.com.").unwrap();
+        let errors: Vec<_> = check.errors().collect();
+        assert_eq!(errors.len(), 3, "{:?}", check.diagnostics);
+        assert_eq!((errors[0].line, errors[0].col), (4, 10));
+        assert!(errors[0].message.starts_with("Invalid A record"));
+        assert_eq!((errors[1].line, errors[1].col), (5, 12));
+        assert!(errors[1].message.starts_with("Invalid MX preference"));
+        assert_eq!((errors[2].line, errors[2].col), (7, 1));
+        assert_eq!(errors[2].message, "Duplicate SOA record");
+        assert_eq!(
+            errors[2].to_string(),
+            format!(
+                "{}:7:1: error: Duplicate SOA record",
+                temp_file.path().display()
+            )
+        );
 
-        // Should parse successfully despite comments
-        let ns_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::NS);
-        assert!(ns_records.is_some());
+        // Parsing carried on past the bad entries
+        let zone = check.zone.unwrap();
+        assert_eq!(zone.soa.serial, 1);
+        assert!(
+            zone.lookup(&Name::from_str("ok.example.com.").unwrap(), RecordType::A)
+                .is_some()
+        );
+
+        let err = parse_zone_file(temp_file.path(), "example.com.").unwrap_err();
+        assert!(err.to_string().starts_with("3 error(s) in zone file"));
     }
 
     #[test]
-    fn test_preprocess_zone_content() {
-        let content = r#"$ORIGIN example.com.
-$TTL 3600
-@ IN SOA ns1.example.com. admin.example.com. (
-    2024010101  ; Serial
-    7200        ; Refresh
-    3600        ; Retry
-    1209600     ; Expire
-    86400       ; Minimum TTL
-)
-@ IN NS ns1.example.com.
-"#;
+    fn test_check_reports_warnings() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
 
-        let processed = preprocess_zone_content(content);
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
+        writeln!(temp_file, "$FOO bar").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        writeln!(temp_file, "other.net. IN A 192.0.2.1").unwrap();
+        writeln!(temp_file, "host 300 IN A 192.0.2.1").unwrap();
+        writeln!(temp_file, "host 600 IN A 192.0.2.2").unwrap();
+        temp_file.flush().unwrap();
 
-        // Should have 4 lines: $ORIGIN, $TTL, SOA (merged), NS
-        assert_eq!(processed.len(), 4);
+        let check = check_zone_file(temp_file.path(), "example.com.").unwrap();
+        assert_eq!(check.errors().count(), 0, "{:?}", check.diagnostics);
+        let warnings: Vec<_> = check
+            .warnings()
+            .map(|d| (d.line, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (2, "Ignoring unsupported directive $FOO"),
+                (
+                    6,
+                    "TTL 600 differs from 300 for host.example.com. A RRset, using 300"
+                ),
+            ]
+        );
 
-        // SOA line should be merged
-        assert!(processed[2].contains("SOA"));
-        assert!(processed[2].contains("2024010101"));
-        assert!(processed[2].contains("86400"));
+        // The out-of-zone record is kept for the linter to report
+        let zone = check.zone.unwrap();
+        assert!(
+            zone.records
+                .contains_key(&Name::from_str("other.net.").unwrap())
+        );
+        let host = zone
+            .lookup(&Name::from_str("host.example.com.").unwrap(), RecordType::A)
+            .unwrap();
+        assert!(host.iter().all(|r| r.ttl() == 300));
 
-        // Comments should be stripped
-        assert!(!processed[2].contains(";"));
+        // Warnings alone do not fail the load
+        assert!(parse_zone_file(temp_file.path(), "example.com.").is_ok());
     }
 
     #[test]
-    fn test_soa_single_line_format() {
+    fn test_records_before_soa_are_kept() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
-        writeln!(
-            temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 2024010101 7200 3600 1209600 86400"
-        )
-        .unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "www IN A 192.0.2.1").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
-        assert_eq!(zone.soa.refresh, 7200);
-        assert_eq!(zone.soa.retry, 3600);
-        assert_eq!(zone.soa.expire, 1209600);
-        assert_eq!(zone.soa.minimum, 86400);
+        assert!(
+            zone.lookup(&Name::from_str("www.example.com.").unwrap(), RecordType::A)
+                .is_some()
+        );
     }
 
     #[test]
-    fn test_soa_multiline_compact() {
+    fn test_include_diagnostics_name_the_included_file() {
         use std::io::Write;
-        use tempfile::NamedTempFile;
 
-        // All values on one line within parentheses
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
-        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
-        writeln!(temp_file, "    2024010101 7200 3600 1209600 86400").unwrap();
-        writeln!(temp_file, ")").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
-        temp_file.flush().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let included = dir.path().join("hosts.zone");
+        let mut f = std::fs::File::create(&included).unwrap();
+        writeln!(f, "ok IN A 192.0.2.1").unwrap();
+        writeln!(f, "bad IN AAAA 192.0.2.1").unwrap();
+
+        let main = dir.path().join("main.zone");
+        let mut f = std::fs::File::create(&main).unwrap();
+        writeln!(f, "$ORIGIN example.com.").unwrap();
+        writeln!(f, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        writeln!(f, "$INCLUDE hosts.zone").unwrap();
+        writeln!(f, "$INCLUDE missing.zone").unwrap();
+
+        let check = check_zone_file(&main, "example.com.").unwrap();
+        let errors: Vec<_> = check.errors().collect();
+        assert_eq!(errors.len(), 2, "{:?}", check.diagnostics);
+        assert_eq!(errors[0].file, included);
+        assert_eq!((errors[0].line, errors[0].col), (2, 13));
+        assert_eq!(errors[1].file, main);
+        assert_eq!((errors[1].line, errors[1].col), (4, 1));
+        assert!(errors[1].message.starts_with("In $INCLUDE missing.zone"));
+    }
 
-        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
-        assert_eq!(zone.soa.refresh, 7200);
+    #[test]
+    fn test_grammar_errors() {
+        let parse = |content: &str| {
+            let entry = &tokenize_zone(content).unwrap()[0];
+            parse_resource_record(
+                entry,
+                &Name::from_str("example.com.").unwrap(),
+                &mut RecordDefaults::default(),
+                &mut Vec::new(),
+            )
+        };
+
+        let err = parse("  IN A 192.0.2.1").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Missing owner name for first record at line 1, column 3"
+        );
+
+        let err = parse("www CH A 192.0.2.1").unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported class CH at line 1, column 5");
+
+        let err = parse("www IN 300").unwrap_err();
+        assert!(err.to_string().starts_with("Missing record type"));
+
+        let err = parse("www IN MX 10").unwrap_err();
+        assert_eq!(err.to_string(), "Incomplete MX record at line 1, column 8");
     }
 
     #[test]
-    fn test_soa_multiline_each_field_separate_line() {
+    fn test_parse_character_string() {
+        assert_eq!(parse_character_string("\"a  b\"").unwrap(), b"a  b");
+        assert_eq!(parse_character_string("plain").unwrap(), b"plain");
+        assert_eq!(parse_character_string("\"\"").unwrap(), b"");
+        assert_eq!(
+            parse_character_string("\"say \\\"hi\\\"\"").unwrap(),
+            b"say \"hi\""
+        );
+        assert_eq!(
+            parse_character_string("\"a\\059b\\\\c\"").unwrap(),
+            b"a;b\\c"
+        );
+        assert_eq!(parse_character_string("\\255\\000").unwrap(), [255, 0]);
+        assert_eq!(parse_character_string("\\1x").unwrap(), b"1x");
+
+        assert!(parse_character_string("\\256").is_err());
+        assert!(parse_character_string("abc\\").is_err());
+    }
+
+    fn txt_strings(zone: &Zone, name: &str) -> Vec<Vec<u8>> {
+        let records = zone
+            .lookup(&Name::from_str(name).unwrap(), RecordType::TXT)
+            .unwrap();
+        match records[0].data() {
+            Some(RData::TXT(txt)) => txt.iter().map(|s| s.to_vec()).collect(),
+            _ => panic!("Expected TXT record"),
+        }
+    }
+
+    #[test]
+    fn test_txt_character_strings() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
-        // Each field on its own line
+        let long = "x".repeat(300);
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
-        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
-        writeln!(temp_file, "    2024010101").unwrap();
-        writeln!(temp_file, "    7200").unwrap();
-        writeln!(temp_file, "    3600").unwrap();
-        writeln!(temp_file, "    1209600").unwrap();
-        writeln!(temp_file, "    86400").unwrap();
-        writeln!(temp_file, ")").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN TXT \"v=spf1 ip4:192.0.2.0/24\" \" include:_spf.example.net -all\""
+        )
+        .unwrap();
+        writeln!(temp_file, "spaces IN TXT \"two  spaces\" bare").unwrap();
+        writeln!(temp_file, "escapes IN TXT \"quote \\\" semi \\059\"").unwrap();
+        writeln!(temp_file, "long IN TXT \"{}\"", long).unwrap();
+        writeln!(temp_file, "empty IN TXT \"\"").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
-        assert_eq!(zone.soa.minimum, 86400);
+
+        assert_eq!(
+            txt_strings(&zone, "example.com."),
+            [
+                b"v=spf1 ip4:192.0.2.0/24".to_vec(),
+                b" include:_spf.example.net -all".to_vec()
+            ]
+        );
+        assert_eq!(
+            txt_strings(&zone, "spaces.example.com."),
+            [b"two  spaces".to_vec(), b"bare".to_vec()]
+        );
+        assert_eq!(
+            txt_strings(&zone, "escapes.example.com."),
+            [b"quote \" semi ;".to_vec()]
+        );
+        assert_eq!(
+            txt_strings(&zone, "long.example.com."),
+            [vec![b'x'; 255], vec![b'x'; 45]]
+        );
+        assert_eq!(txt_strings(&zone, "empty.example.com."), [Vec::<u8>::new()]);
     }
 
     #[test]
-    fn test_soa_multiline_mixed_grouping() {
+    fn test_txt_dkim_and_spf_wire_round_trip() {
+        use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
         use std::io::Write;
         use tempfile::NamedTempFile;
 
-        // Some fields grouped, some separate
+        let key = "MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAxvZ2S1qJ3Fhd0mTiDqOPJAZ5VvUtuFC\
+                   2uSDvR2TKDxwF0lC0DPlh86bhKoP9dkBr5yFzgkgpR/Uyb7HhKYhP7j8pN5jXYgJvhSPsTZb\
+                   N0lFfJOvfHKqtGQTLZb8E2RyMa30WyQ0G2jx7rH3XoWvvvpbMQYe4oj5cSyK9Zz1Bs7VbnRW\
+                   n3Xk6wMo1UKEo7ljSYTOqwGkBLgPEjQyC3jNfqHOEVVIjo2NBFZGkIMzRRLa2QbDNylGGUaF\
+                   DZ3B7aGYPuvz4xfQKm9HjYYL5fY9Bq0pmOkL7qXqtDNUXEnpPN07p2oEUOtZa3P6mfGgpm0G\
+                   UMy6D9ZfsRSx3TbN2kNnfwIDAQAB";
+        let dkim = format!("v=DKIM1; k=rsa; p={}", key);
+        let spf = "v=spf1 ip4:192.0.2.0/24 ip6:2001:db8::/32 include:_spf.example.net ~all";
+
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
-        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
-        writeln!(temp_file, "    2024010101 7200").unwrap();
-        writeln!(temp_file, "    3600").unwrap();
-        writeln!(temp_file, "    1209600 86400").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        // DKIM keys are usually published split across quoted strings
+        writeln!(temp_file, "sel._domainkey IN TXT ( \"v=DKIM1; k=rsa; p=\"").unwrap();
+        for chunk in key.as_bytes().chunks(64) {
+            writeln!(temp_file, "    \"{}\"", std::str::from_utf8(chunk).unwrap()).unwrap();
+        }
         writeln!(temp_file, ")").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "@ IN TXT \"{}\"", spf).unwrap();
+        // A single over-long DKIM string is split automatically
+        writeln!(temp_file, "auto._domainkey IN TXT \"{}\"", dkim).unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
-        assert_eq!(zone.soa.refresh, 7200);
-        assert_eq!(zone.soa.retry, 3600);
-        assert_eq!(zone.soa.expire, 1209600);
-        assert_eq!(zone.soa.minimum, 86400);
+
+        for (name, expected) in [
+            ("sel._domainkey.example.com.", dkim.as_str()),
+            ("auto._domainkey.example.com.", dkim.as_str()),
+            ("example.com.", spf),
+        ] {
+            let record = &zone
+                .lookup(&Name::from_str(name).unwrap(), RecordType::TXT)
+                .unwrap()[0];
+            let decoded = Record::from_bytes(&record.to_bytes().unwrap()).unwrap();
+            assert_eq!(&decoded, record);
+
+            let Some(RData::TXT(txt)) = decoded.data() else {
+                panic!("Expected TXT record");
+            };
+            assert!(txt.iter().all(|s| s.len() <= 255));
+            let joined: Vec<u8> = txt.iter().flat_map(|s| s.iter().copied()).collect();
+            assert_eq!(joined, expected.as_bytes(), "{}", name);
+        }
     }
 
     #[test]
-    fn test_soa_with_inline_comments() {
+    fn test_caa_values_round_trip() {
+        use hickory_proto::rr::rdata::caa::{Property, Value};
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
         writeln!(
             temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. ( ; Start SOA"
+            "@ IN CAA 128 issue \"ca.example.net; accounturi=https://ca.example.net/acct/1\""
         )
         .unwrap();
-        writeln!(temp_file, "    2024010101  ; Serial number").unwrap();
-        writeln!(temp_file, "    7200        ; Refresh - 2 hours").unwrap();
-        writeln!(temp_file, "    3600        ; Retry - 1 hour").unwrap();
-        writeln!(temp_file, "    1209600     ; Expire - 2 weeks").unwrap();
-        writeln!(temp_file, "    86400       ; Minimum TTL - 1 day").unwrap();
-        writeln!(temp_file, ") ; End SOA").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com. ; Primary NS").unwrap();
+        writeln!(temp_file, "deny IN CAA 0 issuewild \";\"").unwrap();
+        writeln!(temp_file, "custom IN CAA 0 tbs \"Unknown value\"").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
-        assert_eq!(zone.soa.refresh, 7200);
-        assert_eq!(zone.soa.retry, 3600);
-        assert_eq!(zone.soa.expire, 1209600);
-        assert_eq!(zone.soa.minimum, 86400);
+        let caa = |name: &str| match zone
+            .lookup(&Name::from_str(name).unwrap(), RecordType::CAA)
+            .unwrap()[0]
+            .data()
+        {
+            Some(RData::CAA(caa)) => caa.clone(),
+            _ => panic!("Expected CAA record"),
+        };
+
+        let issue = caa("example.com.");
+        assert!(issue.issuer_critical());
+        assert_eq!(issue.tag(), &Property::Issue);
+        let Value::Issuer(Some(issuer), params) = issue.value() else {
+            panic!("Expected issuer value");
+        };
+        assert_eq!(issuer, &Name::from_str("ca.example.net").unwrap());
+        assert_eq!(params[0].key(), "accounturi");
+        assert_eq!(params[0].value(), "https://ca.example.net/acct/1");
+
+        let deny = caa("deny.example.com.");
+        assert_eq!(deny.tag(), &Property::IssueWild);
+        assert_eq!(deny.value(), &Value::Issuer(None, vec![]));
+
+        let custom = caa("custom.example.com.");
+        assert_eq!(custom.tag(), &Property::Unknown("tbs".to_string()));
+        assert_eq!(custom.value(), &Value::Unknown(b"Unknown value".to_vec()));
     }
 
     #[test]
-    fn test_soa_single_line_with_comment() {
+    fn test_naptr_regexp_escapes() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
         writeln!(
             temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 2024010101 7200 3600 1209600 86400 ; Main SOA"
+            "@ IN NAPTR 100 10 \"u\" \"E2U+sip\" \"!^(.*) x$!sip:\\\\1@example.com!\" ."
         )
         .unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
+        let records = zone
+            .lookup(&Name::from_str("example.com.").unwrap(), RecordType::NAPTR)
+            .unwrap();
+        let Some(RData::NAPTR(naptr)) = records[0].data() else {
+            panic!("Expected NAPTR record");
+        };
+        assert_eq!(naptr.flags(), b"u");
+        assert_eq!(naptr.services(), b"E2U+sip");
+        assert_eq!(naptr.regexp(), b"!^(.*) x$!sip:\\1@example.com!");
     }
 
     #[test]
-    fn test_soa_with_tabs_and_spaces() {
+    fn test_generic_rdata_syntax() {
+        use hickory_proto::rr::rdata::NULL;
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        writeln!(temp_file, "opaque IN TYPE65534 \\# 4 0A000001").unwrap();
+        writeln!(temp_file, "empty CLASS1 TYPE65535 \\# 0").unwrap();
+        // Known types may use the generic form, or TYPE### with normal RDATA
+        writeln!(temp_file, "www IN A \\# 4 C0000201").unwrap();
+        writeln!(temp_file, "www IN TYPE28 2001:db8::1").unwrap();
         writeln!(
             temp_file,
-            "@\tIN\tSOA\tns1.example.com.\tadmin.example.com.\t("
+            "mail IN TYPE15 \\# ( 16 000A 04 6d61696c 07 6578616d706c65 00 )"
         )
         .unwrap();
-        writeln!(temp_file, "\t\t2024010101\t\t; Serial").unwrap();
-        writeln!(temp_file, "        7200          ; Refresh").unwrap();
-        writeln!(temp_file, "\t3600\t\t; Retry").unwrap();
-        writeln!(temp_file, "    1209600    ; Expire").unwrap();
-        writeln!(temp_file, "\t86400\t; Minimum").unwrap();
-        writeln!(temp_file, ")").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
-        assert_eq!(zone.soa.refresh, 7200);
-        assert_eq!(zone.soa.retry, 3600);
+
+        let opaque = zone
+            .lookup(
+                &Name::from_str("opaque.example.com.").unwrap(),
+                RecordType::Unknown(65534),
+            )
+            .unwrap();
+        assert_eq!(
+            opaque[0].data(),
+            Some(&RData::Unknown {
+                code: RecordType::Unknown(65534),
+                rdata: NULL::with(vec![0x0a, 0, 0, 1]),
+            })
+        );
+        assert!(
+            zone.lookup(
+                &Name::from_str("empty.example.com.").unwrap(),
+                RecordType::from(65535)
+            )
+            .is_some()
+        );
+
+        let www = Name::from_str("www.example.com.").unwrap();
+        assert_eq!(
+            zone.lookup(&www, RecordType::A).unwrap()[0].data(),
+            Some(&RData::A(Ipv4Addr::new(192, 0, 2, 1).into()))
+        );
+        assert!(zone.lookup(&www, RecordType::AAAA).is_some());
+
+        let mail = zone
+            .lookup(
+                &Name::from_str("mail.example.com.").unwrap(),
+                RecordType::MX,
+            )
+            .unwrap();
+        assert_eq!(
+            mail[0].data(),
+            Some(&RData::MX(hickory_proto::rr::rdata::MX::new(
+                10,
+                Name::from_str("mail.example.").unwrap()
+            )))
+        );
     }
 
     #[test]
-    fn test_soa_opening_paren_on_same_line_as_name() {
+    fn test_generic_rdata_errors() {
+        let parse = |content: &str| {
+            let entry = &tokenize_zone(content).unwrap()[0];
+            parse_resource_record(
+                entry,
+                &Name::from_str("example.com.").unwrap(),
+                &mut RecordDefaults::default(),
+                &mut Vec::new(),
+            )
+        };
+
+        let err = parse("x IN TYPE65534 \\# 3 0A000001").unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("RDATA length 3 does not match 4")
+        );
+
+        assert!(parse("x IN A \\# 3 0A0000").is_err());
+        assert!(parse("x IN TYPE70000 \\# 0").is_err());
+        assert!(parse("x IN OPT \\# 0").is_err());
+        assert!(parse("x IN TYPE255 \\# 0").is_err());
+        assert!(parse("x IN FOO \\# 0").is_err());
+        assert!(parse("x CLASS3 TYPE65534 \\# 0").is_err());
+        let err = parse("x IN TYPE65534 \\# 2 ZZZZ").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid hex in generic RDATA at line 1, column 21"
+        );
+    }
+
+    fn svcb_wire(zone: &Zone, name: &str, rtype: RecordType) -> Vec<u8> {
+        use hickory_proto::serialize::binary::BinEncodable;
+
+        zone.lookup(&Name::from_str(name).unwrap(), rtype).unwrap()[0]
+            .data()
+            .unwrap()
+            .to_bytes()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_svcb_https_presentation_format() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
-        // Opening paren on same line as mname and rname
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        // RFC 9460 Appendix D test vectors
+        writeln!(temp_file, "alias IN HTTPS 0 foo.example.com.").unwrap();
+        writeln!(temp_file, "port IN SVCB 16 foo.example.com. port=53").unwrap();
         writeln!(
             temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. ( 2024010101"
+            "mandatory IN SVCB 16 foo.example.org. ( alpn=h2,h3-19 mandatory=ipv4hint,alpn\n    ipv4hint=192.0.2.1 )"
+        )
+        .unwrap();
+        writeln!(
+            temp_file,
+            "v6 IN SVCB 1 foo.example.com. ipv6hint=2001:db8::1,2001:db8::53:1"
+        )
+        .unwrap();
+        writeln!(
+            temp_file,
+            "@ IN HTTPS 1 . alpn=\"h2,h3\" no-default-alpn ech=AAMBAgM="
         )
         .unwrap();
-        writeln!(temp_file, "    7200 3600").unwrap();
-        writeln!(temp_file, "    1209600 86400 )").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
-        assert_eq!(zone.soa.minimum, 86400);
+
+        assert_eq!(
+            svcb_wire(&zone, "alias.example.com.", RecordType::HTTPS),
+            hex::decode("000003666f6f076578616d706c6503636f6d00").unwrap()
+        );
+        assert_eq!(
+            svcb_wire(&zone, "port.example.com.", RecordType::SVCB),
+            hex::decode("001003666f6f076578616d706c6503636f6d00000300020035").unwrap()
+        );
+        assert_eq!(
+            svcb_wire(&zone, "mandatory.example.com.", RecordType::SVCB),
+            hex::decode(
+                "001003666f6f076578616d706c65036f726700\
+                 0000000400010004\
+                 000100090268320568332d3139\
+                 00040004c0000201"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            svcb_wire(&zone, "v6.example.com.", RecordType::SVCB),
+            hex::decode(
+                "000103666f6f076578616d706c6503636f6d00\
+                 00060020\
+                 20010db8000000000000000000000001\
+                 20010db8000000000000000000530001"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            svcb_wire(&zone, "example.com.", RecordType::HTTPS),
+            hex::decode(
+                "000100\
+                 00010006026832026833\
+                 00020000\
+                 000500050003010203"
+            )
+            .unwrap()
+        );
     }
 
     #[test]
-    fn test_soa_multiple_blank_lines_in_parens() {
-        use std::io::Write;
-        use tempfile::NamedTempFile;
-
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
-        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
-        writeln!(temp_file).unwrap();
-        writeln!(temp_file, "    2024010101").unwrap();
-        writeln!(temp_file).unwrap();
-        writeln!(temp_file, "    7200 3600").unwrap();
-        writeln!(temp_file).unwrap();
-        writeln!(temp_file, "    1209600 86400").unwrap();
-        writeln!(temp_file).unwrap();
-        writeln!(temp_file, ")").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
-        temp_file.flush().unwrap();
+    fn test_svcb_invalid_params() {
+        let parse = |content: &str| {
+            let entry = &tokenize_zone(content).unwrap()[0];
+            parse_resource_record(
+                entry,
+                &Name::from_str("example.com.").unwrap(),
+                &mut RecordDefaults::default(),
+                &mut Vec::new(),
+            )
+        };
 
-        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
+        assert!(parse("@ HTTPS 1 . alpn=h2").is_ok());
+        assert!(parse("@ HTTPS 1 . port=1 port=2").is_err());
+        assert!(parse("@ HTTPS 1 . mandatory=port alpn=h2").is_err());
+        assert!(parse("@ HTTPS 1 . mandatory=mandatory").is_err());
+        assert!(parse("@ HTTPS 1 . mandatory=alpn,alpn alpn=h2").is_err());
+        assert!(parse("@ HTTPS 1 . no-default-alpn").is_err());
+        assert!(parse("@ HTTPS 1 . no-default-alpn=x alpn=h2").is_err());
+        assert!(parse("@ HTTPS 1 . port").is_err());
+        assert!(parse("@ HTTPS 1 . port=65536").is_err());
+        assert!(parse("@ HTTPS 1 . ipv4hint=2001:db8::1").is_err());
+        assert!(parse("@ HTTPS 1 . alpn=h2,,h3").is_err());
+        assert!(parse("@ HTTPS 1 . key65000=abc").is_err());
+        assert!(parse("@ HTTPS 1 . ech=AAQBAgM=").is_err());
+        assert!(parse("@ HTTPS 1").is_err());
     }
 
     #[test]
-    fn test_soa_comment_only_lines_in_parens() {
+    fn test_svcb_https_axfr_round_trip() {
+        use hickory_proto::op::Message;
+        use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
-        writeln!(temp_file, "@ IN SOA ns1.example.com. admin.example.com. (").unwrap();
-        writeln!(temp_file, "    ; This is the serial number").unwrap();
-        writeln!(temp_file, "    2024010101").unwrap();
-        writeln!(temp_file, "    ; Time values below").unwrap();
-        writeln!(temp_file, "    7200").unwrap();
-        writeln!(temp_file, "    3600").unwrap();
-        writeln!(temp_file, "    1209600").unwrap();
-        writeln!(temp_file, "    86400").unwrap();
-        writeln!(temp_file, ")").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "@ IN SOA ns1 admin 1 7200 3600 1209600 86400").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN HTTPS 1 . alpn=h3,h2 port=8443 ipv4hint=192.0.2.1 ipv6hint=2001:db8::1 ech=AAMBAgM= mandatory=alpn"
+        )
+        .unwrap();
+        writeln!(temp_file, "www IN HTTPS 0 example.com.").unwrap();
+        writeln!(
+            temp_file,
+            "_dns IN SVCB 1 dns.example.com. alpn=dot port=853"
+        )
+        .unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        assert_eq!(zone.soa.serial, 2024010101);
-        assert_eq!(zone.soa.refresh, 7200);
+        let records = zone.get_all_records();
+        assert_eq!(records.len(), 5);
+
+        // Each record travels in its own message, as in the TCP AXFR handler
+        for record in records {
+            let mut axfr_msg = Message::new();
+            axfr_msg.add_answer(record.clone());
+            let decoded = Message::from_bytes(&axfr_msg.to_bytes().unwrap()).unwrap();
+            assert_eq!(decoded.answers(), [record]);
+        }
+    }
+
+    fn rdata_wire(zone: &Zone, name: &str, rtype: RecordType) -> Vec<u8> {
+        zone.lookup(&Name::from_str(name).unwrap(), rtype).unwrap()[0]
+            .data()
+            .unwrap()
+            .to_bytes()
+            .unwrap()
     }
 
     #[test]
-    fn test_multiline_mx_record() {
+    fn test_additional_rr_types() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
-        // Test that multiline format works for other record types too
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
         writeln!(
             temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+            "@ IN SOA ns1 admin 2024010101 7200 3600 1209600 86400"
         )
         .unwrap();
-        writeln!(temp_file, "@ IN MX (").unwrap();
-        writeln!(temp_file, "    10").unwrap();
-        writeln!(temp_file, "    mail.example.com.").unwrap();
-        writeln!(temp_file, ")").unwrap();
-        writeln!(temp_file, "@ IN NS ns1.example.com.").unwrap();
+        writeln!(temp_file, "host IN HINFO \"PC-Intel-700mhz\" \"Linux 6\"").unwrap();
+        writeln!(temp_file, "host IN RP admin.Example.com. txt").unwrap();
+        writeln!(temp_file, "host IN LOC 42 21 54 N 71 06 18 W -24m 30m").unwrap();
+        writeln!(
+            temp_file,
+            "_ftp._tcp IN URI 10 1 \"ftp://ftp1.example.com/public\""
+        )
+        .unwrap();
+        writeln!(temp_file, "host IN CERT PGP 0 0 AQID").unwrap();
+        writeln!(temp_file, "host IN OPENPGPKEY AQID BAU=").unwrap();
+        writeln!(temp_file, "_smimecert IN SMIMEA 3 0 1 ( 0102 0304 )").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN CDS 12345 8 2 A8B1C2D3E4F506172839405A6B7C8D9E0F1A2B3C4D5E6F708192A3B4C5D6E7F8"
+        )
+        .unwrap();
+        writeln!(temp_file, "@ IN CDNSKEY 257 3 8 AwEAAQ==").unwrap();
+        writeln!(
+            temp_file,
+            "@ IN ZONEMD 2024010101 1 1 ( {} )",
+            "ab".repeat(48)
+        )
+        .unwrap();
+        writeln!(temp_file, "@ IN CSYNC 2024010101 3 A NS AAAA").unwrap();
+        writeln!(temp_file, "delete IN CDS 0 0 0 00").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        let mx_records = zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::MX);
-        assert!(mx_records.is_some());
-        assert_eq!(mx_records.unwrap().len(), 1);
+
+        assert_eq!(
+            rdata_wire(&zone, "host.example.com.", RecordType::HINFO),
+            b"\x0fPC-Intel-700mhz\x07Linux 6"
+        );
+        // Names are written uncompressed
+        assert_eq!(
+            rdata_wire(&zone, "host.example.com.", RecordType::from(17)),
+            b"\x05admin\x07example\x03com\x00\x03txt\x07example\x03com\x00"
+        );
+        // RFC 1876 example for cambridge-net.kei.com
+        assert_eq!(
+            rdata_wire(&zone, "host.example.com.", RecordType::from(29)),
+            hex::decode("0033161389172dd070be15f000988d20").unwrap()
+        );
+        assert_eq!(
+            rdata_wire(&zone, "_ftp._tcp.example.com.", RecordType::from(256)),
+            b"\x00\x0a\x00\x01ftp://ftp1.example.com/public"
+        );
+        assert_eq!(
+            rdata_wire(&zone, "host.example.com.", RecordType::from(37)),
+            [0, 3, 0, 0, 0, 1, 2, 3]
+        );
+        assert_eq!(
+            rdata_wire(&zone, "host.example.com.", RecordType::OPENPGPKEY),
+            [1, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            rdata_wire(&zone, "_smimecert.example.com.", RecordType::from(53)),
+            [3, 0, 1, 1, 2, 3, 4]
+        );
+        assert_eq!(
+            rdata_wire(&zone, "example.com.", RecordType::CDS),
+            hex::decode(
+                "303908 02A8B1C2D3E4F506172839405A6B7C8D9E0F1A2B3C4D5E6F708192A3B4C5D6E7F8"
+                    .replace(' ', "")
+            )
+            .unwrap()
+        );
+        assert!(matches!(
+            zone.lookup(&Name::from_str("example.com.").unwrap(), RecordType::CDS)
+                .unwrap()[0]
+                .data(),
+            Some(RData::DNSSEC(
+                hickory_proto::rr::dnssec::rdata::DNSSECRData::CDS(_)
+            ))
+        ));
+        assert_eq!(
+            rdata_wire(&zone, "example.com.", RecordType::CDNSKEY),
+            [0x01, 0x01, 3, 8, 3, 1, 0, 1]
+        );
+        let mut zonemd = hex::decode("78a3f1750101").unwrap();
+        zonemd.extend([0xab; 48]);
+        assert_eq!(
+            rdata_wire(&zone, "example.com.", RecordType::from(63)),
+            zonemd
+        );
+        assert_eq!(
+            rdata_wire(&zone, "example.com.", RecordType::CSYNC),
+            hex::decode("78a3f1750003000460000008").unwrap()
+        );
+        // The RFC 8078 delete form is kept opaque
+        assert_eq!(
+            rdata_wire(&zone, "delete.example.com.", RecordType::CDS),
+            [0, 0, 0, 0, 0]
+        );
     }
 
     #[test]
-    fn test_naptr_record_parsing() {
+    fn test_loc_presentation_variants() {
+        let loc = |s: &str| {
+            let entry = &tokenize_zone(&format!("x LOC {}", s)).unwrap()[0];
+            parse_loc(&entry.tokens[2..])
+        };
+
+        // Minutes and seconds are optional; defaults for size and precision
+        assert_eq!(
+            hex::decode("0012161380000000800000000098968 0".replace(' ', "")).unwrap(),
+            loc("0 N 0 E 0").unwrap()
+        );
+        // Southern latitude with fractional seconds
+        assert_eq!(
+            &loc("33 51 35.5 S 151 12 40 E 10.5m 1m 1m 1m").unwrap()[4..8],
+            hex::decode("78bc05b4").unwrap()
+        );
+        assert_eq!(
+            loc("1 N 1 E 0 0.01m 90000000m").unwrap()[1..3],
+            [0x10, 0x99]
+        );
+
+        assert!(loc("91 N 0 E 0").is_err());
+        assert!(loc("90 1 N 0 E 0").is_err());
+        assert!(loc("0 60 N 0 E 0").is_err());
+        assert!(loc("0 N 181 E 0").is_err());
+        assert!(loc("0 N 0 X 0").is_err());
+        assert!(loc("0 N 0 E").is_err());
+        assert!(loc("0 N 0 E 0 1m 1m 1m 1m").is_err());
+        assert!(loc("0 N 0 E -100001m").is_err());
+    }
+
+    #[test]
+    fn test_parse_scaled_decimal() {
+        assert_eq!(parse_scaled_decimal("1.5", 2).unwrap(), 150);
+        assert_eq!(parse_scaled_decimal("-24", 2).unwrap(), -2400);
+        assert_eq!(parse_scaled_decimal("54.123", 3).unwrap(), 54123);
+        assert_eq!(parse_scaled_decimal("7", 0).unwrap(), 7);
+        assert!(parse_scaled_decimal("1.234", 2).is_err());
+        assert!(parse_scaled_decimal("", 2).is_err());
+        assert!(parse_scaled_decimal("1e3", 2).is_err());
+    }
+
+    /// Writes `zone` in canonical form and parses the output back
+    fn reparse(zone: &Zone) -> Zone {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file
+            .write_all(write_zone(zone).unwrap().as_bytes())
+            .unwrap();
+        temp_file.flush().unwrap();
+        parse_zone_file(temp_file.path(), &zone.origin.to_string()).unwrap()
+    }
+
+    /// Every record other than the SOA as (owner, type, TTL, RDATA), sorted
+    fn canonical_records(zone: &Zone) -> Vec<(Name, RecordType, u32, Vec<u8>)> {
+        let mut records: Vec<_> = zone
+            .records
+            .values()
+            .flat_map(|rrsets| rrsets.values().flatten())
+            .filter(|record| record.record_type() != RecordType::SOA)
+            .map(|record| {
+                (
+                    record.name().clone(),
+                    record.record_type(),
+                    record.ttl(),
+                    rdata_to_wire(record.data().unwrap()).unwrap(),
+                )
+            })
+            .collect();
+        records.sort();
+        records
+    }
+
+    #[test]
+    fn test_write_zone_canonical_form() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
+        writeln!(temp_file, "$TTL 300").unwrap();
         writeln!(
             temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
+            "@ 3600 IN SOA ns1 hostmaster ( 5 7200 3600 1209600 300 )"
         )
         .unwrap();
+        writeln!(temp_file, "www IN A 192.0.2.2").unwrap();
+        writeln!(temp_file, "@ IN NS ns1").unwrap();
+        writeln!(temp_file, "@ IN NS ns.example.net.").unwrap();
+        writeln!(temp_file, "@ IN MX 10 @").unwrap();
         writeln!(
             temp_file,
-            "_sip._tcp IN NAPTR 100 10 \"u\" \"E2U+sip\" \"!^.*$!sip:info@example.com!\" ."
+            "*.wild IN TXT \"say \\\"hi\\\"\" \"tab\\009end\""
         )
         .unwrap();
+        writeln!(temp_file, "ns1 1h IN A 192.0.2.53").unwrap();
+        writeln!(temp_file, "www IN A 192.0.2.1").unwrap();
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        let naptr_records = zone.lookup(
-            &Name::from_str("_sip._tcp.example.com.").unwrap(),
-            RecordType::NAPTR,
+        assert_eq!(
+            write_zone(&zone).unwrap(),
+            "$ORIGIN example.com.\n\
+             @\t3600\tIN\tSOA\tns1 hostmaster 5 7200 3600 1209600 300\n\
+             @\t300\tIN\tNS\tns.example.net.\n\
+             @\t300\tIN\tNS\tns1\n\
+             @\t300\tIN\tMX\t10 @\n\
+             ns1\t3600\tIN\tA\t192.0.2.53\n\
+             *.wild\t300\tIN\tTXT\t\"say \\\"hi\\\"\" \"tab\\009end\"\n\
+             www\t300\tIN\tA\t192.0.2.1\n\
+             www\t300\tIN\tA\t192.0.2.2\n"
         );
-        assert!(naptr_records.is_some());
-        assert_eq!(naptr_records.unwrap().len(), 1);
     }
 
     #[test]
-    fn test_tlsa_record_parsing() {
+    fn test_write_zone_round_trips_every_type() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
-        writeln!(
-            temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
-        )
-        .unwrap();
-        writeln!(temp_file, "_443._tcp IN TLSA 3 1 1 D2ABDE240D7CD3EE6B4B28C54DF034B97983A1D16E8A410E4561CB106618E971").unwrap();
+        for line in [
+            "$ORIGIN example.com.",
+            "@ IN SOA ns1 admin 2024010101 7200 3600 1209600 86400",
+            "@ IN NS ns1",
+            "@ IN ALIAS cdn.example.net.",
+            "@ IN CAA 0 issue \"letsencrypt.org\"",
+            "@ IN CAA 128 tbs \"Unknown; \\\"quoted\\\"\"",
+            "@ IN HTTPS 1 . alpn=h3,h2 port=8443 ipv4hint=192.0.2.1 ipv6hint=2001:db8::1 ech=AAMBAgM= mandatory=alpn",
+            "_svc IN SVCB 1 svc.example.net. alpn=\"h2,with\\,comma\" no-default-alpn",
+            "_svc IN SVCB 0 target",
+            "@ IN DNSKEY 257 3 8 AwEAAaetidLzsKWU",
+            "@ IN RRSIG A 8 2 3600 1767139200 1764547200 12345 example.com. AwEAAaetidLzsKWU",
+            "@ IN NSEC www.example.com. A NS SOA RRSIG NSEC DNSKEY LOC TYPE65534",
+            "sub IN DS 12345 8 2 A8B1C2D3E4F506172839405A6B7C8D9E0F1A2B3C4D5E6F70",
+            "@ IN CDS 12345 8 2 A8B1C2D3E4F506172839405A6B7C8D9E0F1A2B3C4D5E6F70",
+            "@ IN CDNSKEY 257 3 8 AwEAAQ==",
+            "delete IN CDS 0 0 0 00",
+            "delete IN CDNSKEY 0 3 0 AA==",
+            "@ IN CSYNC 2024010101 3 A NS AAAA",
+            "other IN CSYNC 2024010101 32771 A",
+            "@ IN ZONEMD 2024010101 1 1 ( 0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF 0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF )",
+            "_sip._tcp IN NAPTR 100 10 \"u\" \"E2U+sip\" \"!^(.*) x$!sip:\\\\1@example.com!\" .",
+            "_sip._tcp IN SRV 10 60 5060 sip",
+            "_443._tcp IN TLSA 3 1 1 D2ABDE240D7CD3EE6B4B28C54DF034B97983A1D16E8A410E4561CB106618E971",
+            "host IN SSHFP 1 2 123456789ABCDEF67890123456789ABCDEF67890123456789ABCDEF6789012",
+            "host IN HINFO \"PC Intel\" \"Linux\"",
+            "host IN RP admin.example.com. .",
+            "host IN LOC 42 21 54 N 71 06 18 W -24m 30m",
+            "south IN LOC 33 51 35.5 S 151 12 40 E 10.5m 1m 0.01m 90000000m",
+            "host IN CERT PGP 0 0 AQID",
+            "host IN CERT 65000 1 2 AQID",
+            "host IN OPENPGPKEY AQIDBAU=",
+            "_smimecert IN SMIMEA 3 0 1 01020304",
+            "_ftp._tcp IN URI 10 1 \"ftp://ftp1.example.com/public\"",
+            "ptr IN PTR host.example.net.",
+            "opaque IN TYPE65534 \\# 4 0A000001",
+            "empty IN TYPE65533 \\# 0",
+            "mail IN MX 10 mail.example.net.",
+            "mail IN AAAA 2001:db8::25",
+            "alias IN CNAME host",
+        ] {
+            writeln!(temp_file, "{}", line).unwrap();
+        }
         temp_file.flush().unwrap();
 
         let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        let tlsa_records = zone.lookup(
-            &Name::from_str("_443._tcp.example.com.").unwrap(),
-            RecordType::TLSA,
-        );
-        assert!(tlsa_records.is_some());
-        assert_eq!(tlsa_records.unwrap().len(), 1);
+        let written = write_zone(&zone).unwrap();
+        let reparsed = reparse(&zone);
+
+        assert_eq!(canonical_records(&reparsed), canonical_records(&zone));
+        assert_eq!(write_zone(&reparsed).unwrap(), written);
+
+        // The structured formats carry the same zone
+        let doc = zone_to_document(&zone).unwrap();
+        let from_json: ZoneDocument =
+            serde_json::from_str(&write_zone_as(&zone, ZoneFormat::Json).unwrap()).unwrap();
+        let from_yaml: ZoneDocument =
+            serde_yaml::from_str(&write_zone_as(&zone, ZoneFormat::Yaml).unwrap()).unwrap();
+        assert_eq!(from_json, doc);
+        assert_eq!(from_yaml, doc);
+        let loaded = zone_from_document(&doc, "example.com.").unwrap();
+        assert_eq!(canonical_records(&loaded), canonical_records(&zone));
+        assert_eq!(write_zone(&loaded).unwrap(), written);
+
+        // Only the two unknown types fall back to the generic syntax
+        assert_eq!(written.matches("\\#").count(), 2, "{}", written);
+        for expected in [
+            "@\t3600\tIN\tALIAS\tcdn.example.net.\n",
+            "other\t3600\tIN\tCSYNC\t2024010101 32771 A\n",
+            "@\t3600\tIN\tRRSIG\tA 8 2 3600 1767139200 1764547200 12345 @ AwEAAaetidLzsKWU\n",
+            "host\t3600\tIN\tLOC\t42 21 54.000 N 71 6 18.000 W -24m 30m 10000m 10m\n",
+            "south\t3600\tIN\tLOC\t33 51 35.500 S 151 12 40.000 E 10.50m 1m 0.01m 90000000m\n",
+            "host\t3600\tIN\tRP\tadmin .\n",
+            "host\t3600\tIN\tCERT\tPGP 0 0 AQID\n",
+            "_svc\t3600\tIN\tSVCB\t1 svc.example.net. alpn=h2,with\\,comma no-default-alpn\n",
+            "delete\t3600\tIN\tCDS\t0 0 0 00\n",
+            "opaque\t3600\tIN\tTYPE65534\t\\# 4 0A000001\n",
+            "empty\t3600\tIN\tTYPE65533\t\\# 0\n",
+            "ptr\t3600\tIN\tPTR\thost.example.net.\n",
+        ] {
+            assert!(
+                written.contains(expected),
+                "missing {:?} in\n{}",
+                expected,
+                written
+            );
+        }
     }
 
     #[test]
-    fn test_sshfp_record_parsing() {
+    fn test_load_yaml_zone_document() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "$ORIGIN example.com.").unwrap();
-        writeln!(temp_file, "$TTL 3600").unwrap();
-        writeln!(
-            temp_file,
-            "@ IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 86400"
-        )
-        .unwrap();
-        writeln!(
+        write!(
             temp_file,
-            "host IN SSHFP 1 2 123456789ABCDEF67890123456789ABCDEF67890123456789ABCDEF6789012"
+            r#"
+origin: example.com.
+soa:
+  mname: ns1
+  rname: hostmaster.example.com.
+  serial: 7
+  refresh: 7200
+  retry: 3600
+  expire: 1209600
+  minimum: 300
+rrsets:
+  - name: "@"
+    type: NS
+    ttl: 3600
+    rdata: [ns1, ns.example.net.]
+  - name: www
+    type: TXT
+    ttl: 300
+    rdata: ['"hello world" "second"']
+  - name: ns1
+    type: A
+    ttl: 3600
+    rdata: [192.0.2.53]
+"#
         )
         .unwrap();
         temp_file.flush().unwrap();
 
-        let zone = parse_zone_file(temp_file.path(), "example.com.").unwrap();
-        let sshfp_records = zone.lookup(
-            &Name::from_str("host.example.com.").unwrap(),
-            RecordType::SSHFP,
+        let zone = load_zone_file(temp_file.path(), "example.com.", ZoneFormat::Yaml).unwrap();
+        assert_eq!(zone.soa.serial, 7);
+        assert_eq!(zone.soa.mname, Name::from_str("ns1.example.com.").unwrap());
+        let soa = zone.lookup(&zone.origin, RecordType::SOA).unwrap();
+        assert_eq!(soa[0].ttl(), 300);
+        assert_eq!(zone.lookup(&zone.origin, RecordType::NS).unwrap().len(), 2);
+        let txt = zone
+            .lookup(
+                &Name::from_str("www.example.com.").unwrap(),
+                RecordType::TXT,
+            )
+            .unwrap();
+        assert_eq!(txt[0].ttl(), 300);
+        match txt[0].data() {
+            Some(RData::TXT(txt)) => assert_eq!(txt.txt_data().len(), 2),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_zone_document_errors() {
+        let doc = |rrsets: &str| -> ZoneDocument {
+            serde_json::from_str(&format!(
+                r#"{{"origin": "example.com.",
+                    "soa": {{"mname": "ns1", "rname": "admin", "serial": 1, "refresh": 7200,
+                             "retry": 3600, "expire": 1209600, "minimum": 300}},
+                    "rrsets": {}}}"#,
+                rrsets
+            ))
+            .unwrap()
+        };
+
+        let err = zone_from_document(&doc("[]"), "example.net.").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Document origin example.com. does not match zone example.net."
         );
-        assert!(sshfp_records.is_some());
-        assert_eq!(sshfp_records.unwrap().len(), 1);
+
+        let err = zone_from_document(
+            &doc(
+                r#"[{"name": "www", "type": "A", "ttl": 300, "rdata": ["192.0.2.1", "192.0.2.300"]},
+                    {"name": "mail", "type": "MX", "ttl": 300, "rdata": ["ten mail"]},
+                    {"name": "other.net.", "type": "A", "ttl": 300, "rdata": ["192.0.2.1"]},
+                    {"name": "@", "type": "SOA", "ttl": 300, "rdata": ["ns1 admin 2 1 1 1 1"]}]"#,
+            ),
+            "example.com.",
+        )
+        .unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.starts_with("4 error(s) in zone document"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("rrsets[0] www A \"192.0.2.300\": Invalid A record"),
+            "{}",
+            message
+        );
+        assert!(message.contains("rrsets[1] mail MX"), "{}", message);
+        assert!(
+            message.contains("other.net. is outside the zone"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("The SOA belongs in the soa section"),
+            "{}",
+            message
+        );
+        assert!(!message.contains("line 1"), "{}", message);
+    }
+
+    #[test]
+    fn test_format_name() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let name = |s: &str| Name::from_ascii(s).unwrap();
+
+        assert_eq!(format_name(&origin, &origin), "@");
+        assert_eq!(format_name(&name("a.b.example.com."), &origin), "a.b");
+        assert_eq!(format_name(&name("*.example.com."), &origin), "*");
+        assert_eq!(format_name(&name("example.net."), &origin), "example.net.");
+        assert_eq!(format_name(&Name::root(), &origin), ".");
+        assert_eq!(
+            format_name(&name("www.example.com."), &Name::root()),
+            "www.example.com."
+        );
+        assert_eq!(format_name(&name("a\\.b.example.com."), &origin), "a\\.b");
+        let special = Name::from_labels(vec![&b"@$x"[..], b"example", b"com"]).unwrap();
+        assert_eq!(format_name(&special, &origin), "\\@\\$x");
+    }
+
+    #[test]
+    fn test_format_character_string() {
+        assert_eq!(format_character_string(b""), "\"\"");
+        assert_eq!(format_character_string(b"a b;c"), "\"a b;c\"");
+        assert_eq!(format_character_string(b"\"\\"), "\"\\\"\\\\\"");
+        assert_eq!(
+            format_character_string(&[0, 9, 127, 255]),
+            "\"\\000\\009\\127\\255\""
+        );
+        for bytes in [b"x\"y".to_vec(), vec![0, 1, 2, 200], b"\\123".to_vec()] {
+            assert_eq!(
+                parse_character_string(&format_character_string(&bytes)).unwrap(),
+                bytes
+            );
+        }
     }
 
     mod proptests {
@@ -1790,6 +5771,43 @@ $TTL 3600
         use proptest::prelude::*;
 
         proptest! {
+            #[test]
+            fn prop_write_parse_round_trip(
+                records in prop::collection::vec(
+                    prop::collection::vec(arb_dns_label(), 1..=3)
+                        .prop_map(|labels| {
+                            Name::from_str(&format!("{}.example.com.", labels.join("."))).unwrap()
+                        })
+                        .prop_flat_map(arb_zone_file_record),
+                    0..16,
+                )
+            ) {
+                let origin = Name::from_str("example.com.").unwrap();
+                let soa = SoaRecord {
+                    mname: Name::from_str("ns1.example.com.").unwrap(),
+                    rname: Name::from_str("admin.example.com.").unwrap(),
+                    serial: 1,
+                    refresh: 7200,
+                    retry: 3600,
+                    expire: 1209600,
+                    minimum: 86400,
+                };
+
+                let mut zone = Zone::new(origin, soa);
+                for record in records {
+                    zone.add_record(record);
+                }
+
+                let reparsed = reparse(&zone);
+                prop_assert_eq!(canonical_records(&reparsed), canonical_records(&zone));
+                prop_assert_eq!(write_zone(&reparsed).unwrap(), write_zone(&zone).unwrap());
+
+                let json = write_zone_as(&zone, ZoneFormat::Json).unwrap();
+                let doc: ZoneDocument = serde_json::from_str(&json).unwrap();
+                let loaded = zone_from_document(&doc, "example.com.").unwrap();
+                prop_assert_eq!(canonical_records(&loaded), canonical_records(&zone));
+            }
+
             #[test]
             fn prop_add_and_lookup_record_consistent(name in arb_dns_name(), ttl in arb_ttl()) {
                 let origin = Name::from_str("example.com.").unwrap();
@@ -1991,6 +6009,94 @@ $TTL 3600
                 prop_assert_eq!(soa_record.record_type(), RecordType::SOA);
             }
 
+            #[test]
+            fn prop_lookup_is_case_insensitive((name, variant) in arb_dns_name_with_case_variant()) {
+                let origin = Name::from_str("example.com.").unwrap();
+                let soa = SoaRecord {
+                    mname: Name::from_str("ns1.example.com.").unwrap(),
+                    rname: Name::from_str("admin.example.com.").unwrap(),
+                    serial: 1,
+                    refresh: 7200,
+                    retry: 3600,
+                    expire: 1209600,
+                    minimum: 86400,
+                };
+
+                let mut zone = Zone::new(origin, soa);
+                zone.add_record(Record::from_rdata(
+                    name.clone(),
+                    3600,
+                    RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 1))),
+                ));
+
+                prop_assert!(zone.contains_name(&variant));
+                let records = zone.lookup(&variant, RecordType::A);
+                prop_assert!(records.is_some(), "Random-case lookup should match");
+                prop_assert!(
+                    records.unwrap()[0].name().eq_case(&name),
+                    "Answer owner should keep the zone's case"
+                );
+            }
+
+            #[test]
+            fn prop_find_zone_is_case_insensitive(
+                apex in arb_random_case("example.com."),
+                qname in arb_random_case("www.example.com."),
+            ) {
+                let origin = Name::from_str("example.com.").unwrap();
+                let soa = SoaRecord {
+                    mname: Name::from_str("ns1.example.com.").unwrap(),
+                    rname: Name::from_str("admin.example.com.").unwrap(),
+                    serial: 1,
+                    refresh: 7200,
+                    retry: 3600,
+                    expire: 1209600,
+                    minimum: 86400,
+                };
+
+                let mut zone = Zone::new(origin.clone(), soa);
+                zone.add_record(Record::from_rdata(
+                    Name::from_str("www.example.com.").unwrap(),
+                    3600,
+                    RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 1))),
+                ));
+                let mut store = ZoneStore::new();
+                store.add_zone(zone);
+
+                let found = store.find_zone(&qname);
+                prop_assert!(found.is_some());
+                prop_assert!(found.unwrap().origin.eq_case(&origin));
+                prop_assert!(store.find_zone(&apex).is_some());
+            }
+
+            #[test]
+            fn prop_wildcard_match_is_case_insensitive(qname in arb_random_case("host.sub.example.com.")) {
+                let origin = Name::from_str("example.com.").unwrap();
+                let soa = SoaRecord {
+                    mname: Name::from_str("ns1.example.com.").unwrap(),
+                    rname: Name::from_str("admin.example.com.").unwrap(),
+                    serial: 1,
+                    refresh: 7200,
+                    retry: 3600,
+                    expire: 1209600,
+                    minimum: 86400,
+                };
+
+                let mut zone = Zone::new(origin, soa);
+                zone.add_record(Record::from_rdata(
+                    Name::from_str("*.Sub.Example.com.").unwrap(),
+                    3600,
+                    RData::A(hickory_proto::rr::rdata::A(Ipv4Addr::new(192, 0, 2, 100))),
+                ));
+
+                let records = zone.lookup_wildcard(&qname, RecordType::A);
+                prop_assert!(records.is_some(), "Wildcard should match regardless of case");
+                prop_assert!(
+                    records.unwrap()[0].name().eq_case(&qname),
+                    "Synthesized owner should be the query name as sent"
+                );
+            }
+
             #[test]
             fn prop_record_ttl_preserved(name in arb_dns_name(), ttl in arb_ttl()) {
                 let origin = Name::from_str("example.com.").unwrap();